@@ -1,77 +1,36 @@
-use std::{cmp::max, env, error::Error, process::exit};
+use std::{env, error::Error, fs, process::exit};
 
+use intcode::Pipeline;
 use itertools::Itertools;
 
-async fn part_1(program_file: &str) -> Result<i64, Box<dyn Error + Send + Sync>> {
-    async fn try_phase(
-        program_file: &str,
-        phase_settings: &[i64],
-    ) -> Result<i64, Box<dyn Error + Send + Sync>> {
-        let mut forward = 0;
-        for phase in phase_settings {
-            let mut interpreter =
-                intcode::Interpreter::from_file(program_file, vec![*phase, forward]).await?;
-
-            interpreter.exec().await?;
-
-            forward = interpreter
-                .output()
-                .await
-                .ok_or("Program exited without output")?;
-        }
-
-        Ok(forward)
-    }
-
-    let mut res = 0;
+fn part_1(program: &[i64]) -> Result<i64, Box<dyn Error + Send + Sync>> {
+    let mut res = None;
     for phase_settings in (0..=4).permutations(5) {
-        res = max(res, try_phase(program_file, &phase_settings).await?);
+        let output = Pipeline::from_phases(program, &phase_settings)
+            .run([0])?
+            .ok_or("Amplifiers exited without output")?;
+
+        res = res.max(Some(output));
     }
 
-    Ok(res)
+    Ok(res.ok_or("No phase settings were tried")?)
 }
 
-async fn part_2(program_file: &str) -> Result<i64, Box<dyn Error + Send + Sync>> {
-    async fn try_phase(
-        program_file: &str,
-        phase_settings: &[i64],
-    ) -> Result<i64, Box<dyn Error + Send + Sync>> {
-        let mut forward = 0;
-        let mut interpreters = vec![];
-
-        for phase in phase_settings {
-            let interpreter = intcode::Interpreter::from_file(program_file, vec![*phase]).await?;
-            let interpreter_io = interpreter.exec_spawn()?;
-            interpreters.push(interpreter_io);
-        }
-
-        let mut halted = vec![false; interpreters.len()];
-
-        while halted.iter().any(|x| !(*x)) {
-            for ((input_tx, output_rx), halted) in interpreters.iter_mut().zip(halted.iter_mut()) {
-                match input_tx.send(forward).await {
-                    Ok(()) => (),
-                    Err(_) => *halted = true,
-                }
-
-                if let Some(output) = output_rx.recv().await {
-                    forward = output;
-                }
-            }
-        }
-        Ok(forward)
-    }
-
-    let mut res = 0;
+fn part_2(program: &[i64]) -> Result<i64, Box<dyn Error + Send + Sync>> {
+    let mut res = None;
     for phase_settings in (5..=9).permutations(5) {
-        res = max(res, try_phase(program_file, &phase_settings).await?);
+        let output = Pipeline::from_phases(program, &phase_settings)
+            .with_feedback()
+            .run([0])?
+            .ok_or("Amplifiers exited without output")?;
+
+        res = res.max(Some(output));
     }
 
-    Ok(res)
+    Ok(res.ok_or("No phase settings were tried")?)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
@@ -79,8 +38,10 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         exit(1);
     }
 
-    println!("Part 1: {:?}", part_1(&args[1]).await?);
-    println!("Part 2: {:?}", part_2(&args[1]).await?);
+    let program = intcode::parse_program(&fs::read_to_string(&args[1])?)?;
+
+    println!("Part 1: {:?}", part_1(&program)?);
+    println!("Part 2: {:?}", part_2(&program)?);
 
     Ok(())
 }
@@ -1,7 +1,8 @@
 use core::time;
-use std::{cmp::Ordering, collections::HashMap, env, error::Error, process::exit, thread};
+use std::{cmp::Ordering, collections::HashMap, env, error::Error, fs, process::exit, thread};
 
 use console::Term;
+use intcode::{Executor, InputProvider, Interpreter, ListInput, OutputSink, State, Tuples};
 
 fn draw_screen(screen: &HashMap<(i64, i64), i64>, score: i64) -> String {
     let mut min_x = i64::MAX;
@@ -36,111 +37,123 @@ fn draw_screen(screen: &HashMap<(i64, i64), i64>, score: i64) -> String {
     output
 }
 
-async fn part_1(program_file: &str) -> Result<usize, Box<dyn Error + Send + Sync>> {
-    let mut interpreter = intcode::Interpreter::from_file(program_file, vec![]).await?;
-    interpreter.exec().await?;
-
-    let output = interpreter.output_history();
-    Ok(output.chunks(3).filter(|chunk| chunk[2] == 2).count())
+/// What the arcade cabinet has drawn so far, which also steers the paddle
+#[derive(Debug, Default)]
+struct Game {
+    screen: HashMap<(i64, i64), i64>,
+    score: i64,
+    paddle_x: i64,
+    ball_x: i64,
+    /// Outputs of the tile being drawn
+    tile: Vec<i64>,
+    /// Whether the program waits for input until the screen has been shown
+    show_frames: bool,
+    frame_shown: bool,
 }
 
-async fn part_2(
-    program_file: &str,
-    draw_game: bool,
-) -> Result<usize, Box<dyn Error + Send + Sync>> {
-    let mut interpreter = intcode::Interpreter::from_file(program_file, vec![]).await?;
-    interpreter.set_memory(0, 2);
-    let (input_tx, mut output_rx) = interpreter.exec_spawn()?;
-
-    let mut term = if draw_game {
-        Some(Term::stdout())
-    } else {
-        None
-    };
-
-    let mut screen: HashMap<(i64, i64), i64> = HashMap::new();
-    let mut score = 0;
-    let mut paddle_x = 0;
-    let mut ball_x;
+impl Game {
+    fn draw_tile(&mut self, [x, y, tile]: [i64; 3]) {
+        if x == -1 && y == 0 {
+            self.score = tile;
+        } else {
+            self.screen.insert((x, y), tile);
+        }
 
-    loop {
-        let mut output = [0; 3];
-        for i in &mut output {
-            if let Some(val) = output_rx.recv().await {
-                *i = val;
-            }
+        if tile == 3 {
+            self.paddle_x = x;
         }
 
-        if output[0] == -1 && output[1] == 0 {
-            score = output[2];
-        } else {
-            screen.insert((output[0], output[1]), output[2]);
+        if tile == 4 {
+            self.ball_x = x;
         }
+    }
 
-        if output[2] == 3 {
-            paddle_x = output[0];
+    /// Tilts the joystick towards the ball
+    fn joystick(&self) -> i64 {
+        match self.paddle_x.cmp(&self.ball_x) {
+            Ordering::Less => 1,
+            Ordering::Greater => -1,
+            Ordering::Equal => 0,
         }
+    }
+}
 
-        if output[2] == 4 {
-            ball_x = output[0];
-            break;
+impl InputProvider for Game {
+    /// Tilts the joystick once the screen has been shown, if frames are shown at all
+    fn next_input(&mut self, _executor: &Executor) -> Option<i64> {
+        if self.show_frames && !self.frame_shown {
+            return None;
         }
+
+        self.frame_shown = false;
+        Some(self.joystick())
     }
+}
 
-    loop {
-        if let Some(term) = &mut term {
-            term.clear_screen()?;
-            term.write_line(&draw_screen(&screen, score))?;
-            thread::sleep(time::Duration::from_millis(20));
+impl OutputSink for Game {
+    fn output(&mut self, value: i64) {
+        self.tile.push(value);
+
+        if let Ok(tile) = <[i64; 3]>::try_from(self.tile.as_slice()) {
+            self.tile.clear();
+            self.draw_tile(tile);
         }
+    }
+}
 
-        let paddle_dir = match paddle_x.cmp(&ball_x) {
-            Ordering::Less => 1,
-            Ordering::Greater => -1,
-            Ordering::Equal => 0,
-        };
+fn part_1(program: &str) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut interpreter = Interpreter::parse(program)?;
 
-        match input_tx.send(paddle_dir).await {
-            Ok(()) => {}
-            Err(_) => break,
+    let mut blocks = 0;
+    let screen = Tuples::new(|[_, _, tile]: [i64; 3]| {
+        if tile == 2 {
+            blocks += 1;
         }
+    });
+    match interpreter.run_with(&mut (ListInput::default(), screen))? {
+        State::NeedsInput => Err("Program unexpectedly asked for input")?,
+        State::Output(_) | State::Halted => {}
+    }
 
-        loop {
-            let mut output = [0; 3];
-            for i in &mut output {
-                if let Some(val) = output_rx.recv().await {
-                    *i = val;
-                } else {
-                    if let Some(term) = &mut term {
-                        term.clear_screen()?;
-                        term.write_line(&draw_screen(&screen, score))?;
-                    }
-                    return Ok(score.try_into()?);
-                }
-            }
+    Ok(blocks)
+}
 
-            if output[0] == -1 && output[1] == 0 {
-                score = output[2];
-            } else {
-                screen.insert((output[0], output[1]), output[2]);
-            }
+fn part_2(program: &str, draw_game: bool) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut interpreter = Interpreter::parse(program)?;
+    interpreter.set_memory(0, 2);
 
-            if output[2] == 3 {
-                paddle_x = output[0];
-            }
+    let term = draw_game.then(Term::stdout);
+    let mut game = Game {
+        show_frames: draw_game,
+        ..Game::default()
+    };
 
-            if output[2] == 4 {
-                ball_x = output[0];
-                break;
+    loop {
+        match interpreter.run_with(&mut game)? {
+            State::Halted => break,
+            State::NeedsInput => {
+                let Some(term) = &term else {
+                    Err("Program stopped before the game was over")?
+                };
+
+                term.clear_screen()?;
+                term.write_line(&draw_screen(&game.screen, game.score))?;
+                thread::sleep(time::Duration::from_millis(20));
+                game.frame_shown = true;
             }
+            State::Output(_) => {}
         }
     }
 
-    Ok(score.try_into()?)
+    if let Some(term) = &term {
+        term.clear_screen()?;
+        term.write_line(&draw_screen(&game.screen, game.score))?;
+    }
+
+    Ok(game.score.try_into()?)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
@@ -148,8 +161,10 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         exit(1);
     }
 
-    println!("Part 1: {:?}", part_1(&args[1]).await?);
-    println!("Part 2: {}", part_2(&args[1], false).await?);
+    let program = fs::read_to_string(&args[1])?;
+
+    println!("Part 1: {:?}", part_1(&program)?);
+    println!("Part 2: {}", part_2(&program, false)?);
 
     Ok(())
 }
@@ -1,7 +1,10 @@
-use std::{env, error::Error};
+use std::{env, error::Error, fs};
 
 use intcode::Interpreter;
 
+/// Longest a movement function or the main routine can be, not counting the newline
+const MAX_ROUTINE_LEN: usize = 20;
+
 fn neighbors(
     coords: (usize, usize),
     max: (usize, usize),
@@ -17,20 +20,20 @@ fn neighbors(
     .filter(move |(x, y)| *x < max.0 && *y < max.1)
 }
 
-async fn part_1(program_file: &str) -> Result<usize, Box<dyn Error + Send + Sync>> {
-    let mut interpreter = Interpreter::from_file(&program_file, vec![]).await?;
-    interpreter.exec().await?;
+fn camera_view(program: &[i64]) -> Result<Vec<Vec<char>>, Box<dyn Error + Send + Sync>> {
+    let mut interpreter = Interpreter::new(program.to_vec());
 
-    let map = interpreter
-        .output_history()
-        .iter()
-        .map(|&x| -> Result<char, Box<dyn Error + Send + Sync>> {
-            Ok(char::from(u8::try_from(x)?))
-        })
-        .collect::<Result<String, _>>()?
+    Ok(interpreter
+        .read_ascii()?
+        .text
         .lines()
+        .filter(|line| !line.is_empty())
         .map(|s| s.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
+        .collect::<Vec<_>>())
+}
+
+fn part_1(program: &[i64]) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let map = camera_view(program)?;
 
     Ok(map
         .iter()
@@ -44,12 +47,137 @@ async fn part_1(program_file: &str) -> Result<usize, Box<dyn Error + Send + Sync
         .sum())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+/// Turns and distances that take the robot along the whole scaffold, always going straight on
+/// where the scaffold crosses itself
+fn path(map: &[Vec<char>]) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let (mut x, mut y, robot) = map
+        .iter()
+        .enumerate()
+        .flat_map(|(y, line)| line.iter().enumerate().map(move |(x, c)| (x, y, *c)))
+        .find(|(_, _, c)| "^v<>".contains(*c))
+        .ok_or("Robot not found on the camera view")?;
+
+    let mut dir: (isize, isize) = match robot {
+        '^' => (0, -1),
+        'v' => (0, 1),
+        '<' => (-1, 0),
+        _ => (1, 0),
+    };
+
+    let scaffold = |(x, y): (usize, usize), (dx, dy): (isize, isize)| {
+        let next = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        (map.get(next.1)?.get(next.0)? == &'#').then_some(next)
+    };
+
+    let mut moves = vec![];
+    loop {
+        let left = (dir.1, -dir.0);
+        let right = (-dir.1, dir.0);
+
+        let turn = if scaffold((x, y), left).is_some() {
+            dir = left;
+            "L"
+        } else if scaffold((x, y), right).is_some() {
+            dir = right;
+            "R"
+        } else {
+            break;
+        };
+
+        let mut steps = 0;
+        while let Some((next_x, next_y)) = scaffold((x, y), dir) {
+            (x, y) = (next_x, next_y);
+            steps += 1;
+        }
+
+        moves.push(format!("{turn},{steps}"));
+    }
+
+    Ok(moves)
+}
+
+/// Splits moves into a main routine calling at most three movement functions
+fn compress<'a>(
+    moves: &'a [String],
+    functions: &mut Vec<&'a [String]>,
+    main: &mut Vec<usize>,
+) -> bool {
+    if moves.is_empty() {
+        return true;
+    }
+
+    // every call takes a letter and a comma
+    if main.len() * 2 >= MAX_ROUTINE_LEN {
+        return false;
+    }
+
+    for i in 0..functions.len() {
+        if moves.starts_with(functions[i]) {
+            main.push(i);
+            if compress(&moves[functions[i].len()..], functions, main) {
+                return true;
+            }
+            main.pop();
+        }
+    }
+
+    if functions.len() < 3 {
+        for len in 1..=moves.len() {
+            let function = &moves[..len];
+            if function.join(",").len() > MAX_ROUTINE_LEN {
+                break;
+            }
+
+            functions.push(function);
+            main.push(functions.len() - 1);
+            if compress(&moves[len..], functions, main) {
+                return true;
+            }
+            main.pop();
+            functions.pop();
+        }
+    }
+
+    false
+}
+
+fn part_2(program: &[i64]) -> Result<i64, Box<dyn Error + Send + Sync>> {
+    let moves = path(&camera_view(program)?)?;
+
+    let mut functions = vec![];
+    let mut main = vec![];
+    if !compress(&moves, &mut functions, &mut main) {
+        return Err("Path does not fit into three movement functions".into());
+    }
+
+    let mut interpreter = Interpreter::new(program.to_vec());
+    interpreter
+        .set_memory(0, 2)
+        .ok_or("Could not wake up the robot")?;
+
+    let main = main
+        .iter()
+        .map(|i| ["A", "B", "C"][*i])
+        .collect::<Vec<_>>();
+    interpreter.send_line(&main.join(","));
+
+    for i in 0..3 {
+        interpreter.send_line(&functions.get(i).map(|f| f.join(",")).unwrap_or_default());
+    }
+    interpreter.send_line("n");
+
+    Ok(interpreter
+        .read_ascii()?
+        .value
+        .ok_or("Robot did not report the dust it collected")?)
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let program_file = env::args().nth(1).expect("Input file expected as argument");
+    let program = intcode::parse_program(&fs::read_to_string(program_file)?)?;
 
-    println!("Part 1: {:?}", part_1(&program_file).await?);
-    // println!("Part 2: {:?}", part_2(&mut robot).await?);
+    println!("Part 1: {:?}", part_1(&program)?);
+    println!("Part 2: {:?}", part_2(&program)?);
 
     Ok(())
 }
@@ -0,0 +1,111 @@
+use core::fmt;
+
+use crate::parse_address;
+
+/// Condition that stops a continued run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// The program counter reaches an address
+    Pc(usize),
+    /// An instruction reads a parameter from an address
+    Read(usize),
+    /// An instruction writes to an address
+    Write(usize),
+    /// The next instruction has an opcode, named by its mnemonic
+    Opcode(&'static str),
+}
+
+impl Breakpoint {
+    /// Parses the text typed into the breakpoint prompt
+    ///
+    /// `ADDR` breaks on the program counter, `r ADDR` and `w ADDR` watch reads and writes and
+    /// `op NAME` breaks on an opcode, which is one of `mnemonics`. Addresses are decimal or `0x`
+    /// hex.
+    pub fn parse(s: &str, mnemonics: &[&'static str]) -> Result<Self, String> {
+        let s = s.trim();
+        let (kind, arg) = s.split_once(char::is_whitespace).unwrap_or(("", s));
+
+        match kind {
+            "" => parse_address(arg).map(Self::Pc),
+            "r" => parse_address(arg).map(Self::Read),
+            "w" => parse_address(arg).map(Self::Write),
+            "op" => mnemonics
+                .iter()
+                .find(|mnemonic| mnemonic.eq_ignore_ascii_case(arg.trim()))
+                .map(|mnemonic| Self::Opcode(mnemonic))
+                .ok_or_else(|| format!("Unknown instruction `{}`", arg.trim())),
+            _ => Err(format!("Unknown breakpoint kind `{kind}`")),
+        }
+    }
+
+    /// Whether the breakpoint fires before executing the instruction at `pc`
+    fn before(self, pc: usize, opcode: Option<&str>) -> bool {
+        match self {
+            Self::Pc(addr) => addr == pc,
+            Self::Opcode(op) => Some(op) == opcode,
+            Self::Read(_) | Self::Write(_) => false,
+        }
+    }
+
+    /// Whether the breakpoint fires after an instruction that read from and wrote to the given
+    /// cells ran
+    fn after(self, reads: &[usize], write: Option<usize>) -> bool {
+        match self {
+            Self::Read(addr) => reads.contains(&addr),
+            Self::Write(addr) => write == Some(addr),
+            Self::Pc(_) | Self::Opcode(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pc(addr) => write!(f, "pc {addr:#010x}"),
+            Self::Read(addr) => write!(f, "read [{addr}]"),
+            Self::Write(addr) => write!(f, "write [{addr}]"),
+            Self::Opcode(op) => write!(f, "op {op}"),
+        }
+    }
+}
+
+/// Breakpoints that are set, and which of them stopped the program last
+#[derive(Debug, Default)]
+pub struct Breakpoints {
+    pub list: Vec<Breakpoint>,
+    pub last_hit: Option<usize>,
+}
+
+impl Breakpoints {
+    /// Adds a breakpoint, or removes it if it is already set
+    pub fn toggle(&mut self, breakpoint: Breakpoint) {
+        if let Some(i) = self.list.iter().position(|b| *b == breakpoint) {
+            self.list.remove(i);
+            self.last_hit = None;
+        } else {
+            self.list.push(breakpoint);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.last_hit = None;
+    }
+
+    /// Index of the first breakpoint that fires before executing the instruction at `pc`
+    pub fn hit_before(&mut self, pc: usize, opcode: Option<&str>) -> Option<usize> {
+        let hit = self.list.iter().position(|b| b.before(pc, opcode));
+        self.last_hit = hit.or(self.last_hit);
+
+        hit
+    }
+
+    /// Index of the first watchpoint that fires on an executed instruction, given the cells it
+    /// read from and wrote to
+    pub fn hit_after(&mut self, reads: &[usize], write: Option<usize>) -> Option<usize> {
+        let hit = self.list.iter().position(|b| b.after(reads, write));
+        self.last_hit = hit.or(self.last_hit);
+
+        hit
+    }
+}
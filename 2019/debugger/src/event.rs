@@ -4,9 +4,10 @@ use crossterm::event::{Event as CrosstermEvent, KeyEvent, MouseEvent};
 use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc;
 
-use crate::debugger::DebuggerResult;
+use crate::DebuggerResult;
 
 /// Terminal events.
+#[allow(dead_code)]
 #[derive(Clone, Copy, Debug)]
 pub enum Event {
     /// Terminal tick.
@@ -83,11 +84,10 @@ impl EventHandler {
     /// This function will always block the current thread if
     /// there is no data available and it's possible for more data to be sent.
     pub async fn next(&mut self) -> DebuggerResult<Event> {
-        Ok(self.receiver.recv().await.ok_or_else(|| {
-            Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "This is an IO error",
-            ))
-        })?)
+        Ok(self
+            .receiver
+            .recv()
+            .await
+            .ok_or_else(|| Box::new(std::io::Error::other("This is an IO error")))?)
     }
 }
@@ -0,0 +1,103 @@
+use crate::{Debugger, DebuggerResult, Machine, PromptKind};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Handles the key events and updates the state of [`App`].
+pub async fn handle_key_events<M: Machine>(
+    key_event: KeyEvent,
+    debugger: &mut Debugger<M>,
+) -> DebuggerResult<()> {
+    // Exit application on `Ctrl-C`, even while typing into the prompt
+    if matches!(key_event.code, KeyCode::Char('c' | 'C'))
+        && key_event.modifiers == KeyModifiers::CONTROL
+    {
+        debugger.quit();
+        return Ok(());
+    }
+
+    if let Some(prompt) = &mut debugger.prompt {
+        match key_event.code {
+            KeyCode::Esc => debugger.prompt = None,
+            KeyCode::Enter => debugger.submit_prompt(),
+            KeyCode::Backspace => {
+                prompt.text.pop();
+            }
+            KeyCode::Char(c) => prompt.text.push(c),
+            _ => {}
+        }
+
+        return Ok(());
+    }
+
+    match key_event.code {
+        // Exit application on `ESC` or `q`
+        KeyCode::Esc | KeyCode::Char('q') => {
+            debugger.quit();
+        }
+        KeyCode::Char('n') => {
+            debugger.next();
+        }
+        KeyCode::Char('o') => {
+            debugger.step_over();
+        }
+        KeyCode::Char('u') => {
+            debugger.step_out();
+        }
+        KeyCode::Char('c') => {
+            debugger.toggle_continue();
+        }
+        KeyCode::Char('p') => {
+            debugger.step_back();
+        }
+        KeyCode::Char('r') => {
+            debugger.reverse_continue();
+        }
+        KeyCode::Char('w') => {
+            debugger.open_prompt(PromptKind::LastWrite);
+        }
+        KeyCode::Char('i') => {
+            debugger.open_prompt(PromptKind::Input);
+        }
+        KeyCode::Char('b') => {
+            debugger.open_prompt(PromptKind::Breakpoint);
+        }
+        KeyCode::Char('d') => {
+            debugger.toggle_text_view();
+        }
+        KeyCode::Char('h') => {
+            debugger.cycle_highlight();
+        }
+        KeyCode::Char('f') => {
+            debugger.memory_view.cycle_format();
+        }
+        KeyCode::Char('l') => {
+            debugger.toggle_frame();
+        }
+        KeyCode::Char('g') => {
+            debugger.open_prompt(PromptKind::Goto);
+        }
+        KeyCode::Char('m') => {
+            debugger.toggle_step_mode();
+        }
+        KeyCode::Tab => {
+            debugger.focus_next();
+        }
+        KeyCode::Char(c @ '0'..='9') => {
+            debugger.focus(usize::from(c as u8 - b'0'));
+        }
+        KeyCode::PageDown => {
+            debugger.memory_page_down();
+        }
+        KeyCode::PageUp => {
+            debugger.memory_page_up();
+        }
+        KeyCode::Down => {
+            debugger.scroll_down();
+        }
+        KeyCode::Up => {
+            debugger.scroll_up();
+        }
+        // Other handlers you could add here.
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,625 @@
+//! Terminal debugger for the virtual machines of the puzzles
+//!
+//! The debugger runs anything that implements [`Machine`], like the intcode computer of 2019.
+//! Panes and keys for what a machine does not have, like input or calls, are left empty or report
+//! that they do not apply.
+
+use crate::utils::StringExt;
+use std::{collections::VecDeque, error};
+
+use std::io;
+
+use ratatui::{backend::CrosstermBackend, Terminal};
+
+use crate::{
+    breakpoints::{Breakpoint, Breakpoints},
+    event::{Event, EventHandler},
+    handler::handle_key_events,
+    memory_view::MemoryView,
+    tui::Tui,
+};
+
+pub use crate::machine::{Machine, Pane, Stepped};
+pub use ::machine::{instruction_address, parse_address, LoopDetector, State};
+
+pub mod breakpoints;
+pub mod event;
+pub mod handler;
+pub mod machine;
+pub mod memory_view;
+pub mod tui;
+pub mod ui;
+mod utils;
+
+/// Instructions executed per tick while continuing, so the interface stays responsive
+const STEPS_PER_TICK: usize = 50_000;
+
+/// Number of executed instructions that can be stepped back over
+const HISTORY_LEN: usize = 100_000;
+
+/// Rows the Memory pane moves by a page
+const MEMORY_PAGE: usize = 16;
+
+/// Application result type.
+#[allow(clippy::module_name_repetitions)]
+pub type DebuggerResult<T> = std::result::Result<T, Box<dyn error::Error + Sync + Send>>;
+
+/// How the Text pane lays out the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextView {
+    /// Every cell decoded in order, as if memory only held instructions
+    Linear,
+    /// Basic blocks found by following control flow, with everything else shown as data
+    ControlFlow,
+}
+
+/// What the Text pane colours instructions by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    None,
+    /// How often each instruction was executed, relative to the most executed one
+    Hotness,
+    /// Whether each instruction was executed at all
+    Coverage,
+}
+
+/// What the line at the bottom of the screen is being used to enter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    Breakpoint,
+    /// Address to rewind to the last write of
+    LastWrite,
+    /// Values to queue for the machine to read
+    Input,
+    /// Address to show in the Memory pane
+    Goto,
+}
+
+#[derive(Debug)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+}
+
+/// Application.
+#[derive(Debug)]
+pub struct Debugger<M: Machine> {
+    /// Is the application running?
+    pub running: bool,
+
+    pub scroll_offset: (u16, u16),
+
+    pub machine: M,
+    pub text: String,
+    pub text_view: TextView,
+    pub highlight: Highlight,
+    pub memory_view: MemoryView,
+
+    /// Most recently executed instructions, newest last
+    pub history: VecDeque<M::Record>,
+
+    pub breakpoints: Breakpoints,
+    /// Loop detection for the run that continues until a breakpoint fires, if one is going
+    pub continuing: Option<LoopDetector<u64>>,
+    /// Call depth at which the run stops, when stepping over or out of a call
+    pub until_depth: Option<usize>,
+    /// State the last executed instruction left the program in
+    pub state: Option<State>,
+    /// Set when the program failed, after which it can not be stepped any more
+    pub fault: bool,
+    pub status: String,
+    pub prompt: Option<Prompt>,
+}
+
+impl<M: Machine> Debugger<M> {
+    pub fn new(machine: M) -> Self {
+        let text = machine
+            .listing(TextView::Linear)
+            .unwrap_or_default()
+            .expand_tabs(8);
+        Self {
+            running: true,
+            scroll_offset: (0, 0),
+            machine,
+            text,
+            text_view: TextView::Linear,
+            highlight: Highlight::None,
+            memory_view: MemoryView::default(),
+            history: VecDeque::new(),
+            breakpoints: Breakpoints::default(),
+            continuing: None,
+            until_depth: None,
+            state: None,
+            fault: false,
+            status: String::new(),
+            prompt: None,
+        }
+    }
+
+    /// Handles the tick event of the terminal.
+    pub fn tick(&mut self) {
+        if self.continuing.is_some() {
+            self.run_until_break(STEPS_PER_TICK);
+        }
+    }
+
+    /// Set running to false to quit the application.
+    pub fn quit(&mut self) {
+        self.running = false;
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll_offset.0 += 1;
+    }
+
+    pub fn scroll_up(&mut self) {
+        if self.scroll_offset.0 > 0 {
+            self.scroll_offset.0 -= 1;
+        }
+    }
+
+    pub fn memory_page_down(&mut self) {
+        self.memory_view
+            .scroll_down(MEMORY_PAGE, self.machine.memory_len());
+    }
+
+    pub fn memory_page_up(&mut self) {
+        self.memory_view.scroll_up(MEMORY_PAGE);
+    }
+
+    /// Switches the Text pane between the linear and control-flow disassembly
+    pub fn toggle_text_view(&mut self) {
+        let view = match self.text_view {
+            TextView::Linear => TextView::ControlFlow,
+            TextView::ControlFlow => TextView::Linear,
+        };
+        if self.machine.listing(view).is_none() {
+            self.status = "No other view of this machine".to_string();
+            return;
+        }
+
+        self.text_view = view;
+        self.refresh();
+    }
+
+    /// Cycles the colouring of the Text pane through hotness, coverage and none
+    ///
+    /// Profiling only starts the first time the colouring is switched on, so runs that never show
+    /// it are not slowed down by counting instructions.
+    pub fn cycle_highlight(&mut self) {
+        if self.machine.executions().is_none() {
+            if !self.machine.enable_profiling() {
+                self.status = "This machine can not be profiled".to_string();
+                return;
+            }
+            self.status = "Profiling instructions executed from here on".to_string();
+        }
+
+        self.highlight = match self.highlight {
+            Highlight::None => Highlight::Hotness,
+            Highlight::Hotness => Highlight::Coverage,
+            Highlight::Coverage => Highlight::None,
+        };
+    }
+
+    pub fn next(&mut self) {
+        self.continuing = None;
+        self.execute();
+        self.refresh();
+    }
+
+    /// Starts running until a breakpoint fires, or pauses a run that is already going
+    pub fn toggle_continue(&mut self) {
+        if self.continuing.is_some() {
+            self.continuing = None;
+            self.status = "Paused".to_string();
+            self.refresh();
+            return;
+        }
+
+        self.start_run(None);
+    }
+
+    /// Shows the machine at `index` in the Text, Inspector and Memory panes, for machines made of
+    /// several
+    pub fn focus(&mut self, index: usize) {
+        if self.machine.panes().is_empty() {
+            self.status = "There is only one machine".to_string();
+            return;
+        }
+
+        if !self.machine.focus(index) {
+            self.status = format!("No machine {index}");
+            return;
+        }
+
+        // the focused machine decides whether stepping only it can go on
+        self.state = None;
+        self.status = format!("Showing machine {index}");
+        self.refresh();
+    }
+
+    /// Moves the focus to the machine after the focused one
+    pub fn focus_next(&mut self) {
+        let panes = self.machine.panes();
+        let index = panes
+            .iter()
+            .position(|pane| pane.focused)
+            .map_or(0, |i| (i + 1) % panes.len());
+
+        self.focus(index);
+    }
+
+    /// Switches between stepping the machines in turn and stepping only the focused one
+    pub fn toggle_step_mode(&mut self) {
+        match self.machine.toggle_step_mode() {
+            Some(description) => {
+                self.state = None;
+                self.status = description.to_string();
+                self.refresh();
+            }
+            None => self.status = "There is only one machine".to_string(),
+        }
+    }
+
+    /// Switches the Memory pane between following the relative base and staying where scrolled to
+    pub fn toggle_frame(&mut self) {
+        if self.machine.rel().is_none() && !self.memory_view.frame {
+            self.status = "This machine has no relative base".to_string();
+            return;
+        }
+
+        self.memory_view.toggle_frame();
+    }
+
+    /// Executes an instruction, running on until the call returns if it was a call
+    pub fn step_over(&mut self) {
+        self.continuing = None;
+        let depth = self.machine.call_depth();
+
+        if self.execute() && self.machine.call_depth() > depth && !self.stopped() {
+            self.start_run(Some(depth));
+        } else {
+            self.refresh();
+        }
+    }
+
+    /// Runs until the innermost call returns
+    pub fn step_out(&mut self) {
+        self.continuing = None;
+
+        match self.machine.call_depth() {
+            0 => self.status = "Not inside a call".to_string(),
+            depth => self.start_run(Some(depth - 1)),
+        }
+    }
+
+    /// Starts running until a breakpoint fires, or until the call stack is no deeper than
+    /// `until_depth`
+    fn start_run(&mut self, until_depth: Option<usize>) {
+        if self.stopped() {
+            return;
+        }
+
+        self.continuing = Some(LoopDetector::new());
+        self.until_depth = until_depth;
+        self.status = "Running".to_string();
+
+        // the breakpoint at the program counter is where the last run stopped
+        if self.execute() && !self.returned() {
+            self.run_until_break(STEPS_PER_TICK);
+        } else {
+            self.continuing = None;
+            self.refresh();
+        }
+    }
+
+    /// Whether a run stepping over or out of a call has got back out of it
+    fn returned(&mut self) -> bool {
+        if self
+            .until_depth
+            .is_none_or(|depth| self.machine.call_depth() > depth)
+        {
+            return false;
+        }
+
+        self.status = format!("Returned to {:#x}", self.machine.pc());
+        true
+    }
+
+    /// Executes up to `steps` instructions, stopping early when a breakpoint fires or the program
+    /// can not go on
+    fn run_until_break(&mut self, steps: usize) {
+        for _ in 0..steps {
+            let (pc, opcode) = self.machine.next_instruction();
+
+            if let Some(i) = self.breakpoints.hit_before(pc, opcode) {
+                self.status = format!("Breakpoint {i} hit: {}", self.breakpoints.list[i]);
+                self.continuing = None;
+                break;
+            }
+
+            let period = self
+                .continuing
+                .as_mut()
+                .zip(self.machine.fingerprint())
+                .and_then(|(loops, fingerprint)| loops.check(fingerprint));
+            if let Some(period) = period {
+                self.status = format!("Stuck in a loop of {period} instructions at {pc:#x}");
+                self.continuing = None;
+                break;
+            }
+
+            if !self.execute() {
+                self.continuing = None;
+                break;
+            }
+
+            let hit = self.history.back().and_then(|record| {
+                Self::watchpoint(&mut self.breakpoints, record)
+                    .map(|i| format!("Breakpoint {i} hit: {record}"))
+            });
+            if let Some(status) = hit {
+                self.status = status.expand_tabs(1);
+                self.continuing = None;
+                break;
+            }
+
+            if self.stopped() || self.returned() {
+                self.continuing = None;
+                break;
+            }
+        }
+
+        self.refresh();
+    }
+
+    /// Index of the first watchpoint that fires on an executed instruction
+    fn watchpoint(breakpoints: &mut Breakpoints, record: &M::Record) -> Option<usize> {
+        breakpoints.hit_after(&M::reads(record), M::write(record))
+    }
+
+    /// Reverts the most recently executed instruction
+    fn undo(&mut self) -> Option<M::Record> {
+        let record = self.history.pop_back()?;
+        self.machine.undo(&record);
+
+        self.state = None;
+        self.fault = false;
+
+        Some(record)
+    }
+
+    pub fn step_back(&mut self) {
+        self.continuing = None;
+        self.status = match self.undo() {
+            Some(record) => format!("Undid {record}").expand_tabs(1),
+            None => "No history to step back through".to_string(),
+        };
+
+        self.refresh();
+    }
+
+    /// Steps backwards until a breakpoint fires or the history runs out
+    ///
+    /// Watchpoints fire when the instruction that triggered them is undone, which leaves the
+    /// program just before it.
+    pub fn reverse_continue(&mut self) {
+        self.continuing = None;
+        self.status = "Reached the start of the history".to_string();
+
+        while let Some(record) = self.undo() {
+            if let Some(i) = Self::watchpoint(&mut self.breakpoints, &record) {
+                self.status = format!("Breakpoint {i} hit: {record}").expand_tabs(1);
+                break;
+            }
+
+            let (pc, opcode) = self.machine.next_instruction();
+            if let Some(i) = self.breakpoints.hit_before(pc, opcode) {
+                self.status = format!("Breakpoint {i} hit: {}", self.breakpoints.list[i]);
+                break;
+            }
+        }
+
+        self.refresh();
+    }
+
+    /// Steps backwards to just before the last instruction that wrote to `addr`
+    fn rewind_to_write(&mut self, addr: usize) {
+        let Some(steps) = self
+            .history
+            .iter()
+            .rev()
+            .position(|record| M::write(record) == Some(addr))
+        else {
+            self.status = format!("No write to [{addr}] in the history");
+            return;
+        };
+
+        self.continuing = None;
+        for _ in 0..steps {
+            self.undo();
+        }
+
+        if let Some(record) = self.undo() {
+            self.status = format!("Last write: {record}").expand_tabs(1);
+        }
+
+        self.refresh();
+    }
+
+    /// Whether the program can not execute another instruction without outside help
+    fn stopped(&self) -> bool {
+        self.fault || matches!(self.state, Some(State::NeedsInput | State::Halted))
+    }
+
+    /// Executes a single instruction and records how it went in the status line, returning
+    /// whether it was executed
+    fn execute(&mut self) -> bool {
+        if self.fault {
+            return false;
+        }
+
+        match self.machine.step() {
+            Ok((state, record)) => {
+                self.state = state;
+                self.status = match state {
+                    Some(State::NeedsInput) => "Waiting for input".to_string(),
+                    Some(State::Output(value)) => format!("Output {value}"),
+                    Some(State::Halted) => "Halted".to_string(),
+                    None if self.continuing.is_some() => "Running".to_string(),
+                    None => String::new(),
+                };
+
+                let Some(record) = record else {
+                    return false;
+                };
+
+                if self.history.len() == HISTORY_LEN {
+                    self.history.pop_front();
+                }
+                self.history.push_back(record);
+
+                true
+            }
+            Err(e) => {
+                self.fault = true;
+                self.status = format!("Fault: {e}");
+
+                false
+            }
+        }
+    }
+
+    pub fn open_prompt(&mut self, kind: PromptKind) {
+        self.prompt = Some(Prompt {
+            kind,
+            text: String::new(),
+        });
+    }
+
+    /// Acts on the text entered into the prompt and closes it
+    pub fn submit_prompt(&mut self) {
+        let Some(Prompt { kind, text }) = self.prompt.take() else {
+            return;
+        };
+
+        match kind {
+            PromptKind::Breakpoint => self.edit_breakpoints(text.trim()),
+            PromptKind::Input => self.queue_input(&text),
+            PromptKind::LastWrite => match parse_address(&text) {
+                Ok(addr) => self.rewind_to_write(addr),
+                Err(e) => self.status = e,
+            },
+            PromptKind::Goto => match parse_address(&text) {
+                Ok(addr) => self.memory_view.goto(addr),
+                Err(e) => self.status = e,
+            },
+        }
+    }
+
+    /// Queues input typed into the prompt
+    ///
+    /// Integers separated by commas or spaces are queued as they are. Anything else is taken as
+    /// ASCII text and queued as character codes followed by a newline.
+    fn queue_input(&mut self, text: &str) {
+        let values = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(str::parse::<i64>)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|_| text.bytes().chain([b'\n']).map(i64::from).collect());
+
+        for value in values {
+            if !self.machine.push_input(value) {
+                self.status = "This machine takes no input".to_string();
+                return;
+            }
+        }
+
+        if self.state == Some(State::NeedsInput) {
+            self.state = None;
+            self.status = String::new();
+        }
+    }
+
+    /// Runs a command from the breakpoint prompt
+    ///
+    /// An empty command toggles a breakpoint on the program counter, `del N` removes the
+    /// breakpoint at index `N`, `clear` removes all of them and anything else is parsed as a
+    /// [`Breakpoint`] and toggled.
+    fn edit_breakpoints(&mut self, command: &str) {
+        if command.is_empty() {
+            let pc = self.machine.pc();
+            self.breakpoints.toggle(Breakpoint::Pc(pc));
+        } else if command == "clear" {
+            self.breakpoints.clear();
+        } else if let Some(i) = command.strip_prefix("del ") {
+            match i.trim().parse::<usize>() {
+                Ok(i) if i < self.breakpoints.list.len() => {
+                    let breakpoint = self.breakpoints.list[i];
+                    self.breakpoints.toggle(breakpoint);
+                }
+                _ => self.status = format!("No breakpoint `{}`", i.trim()),
+            }
+        } else {
+            match Breakpoint::parse(command, M::MNEMONICS) {
+                Ok(breakpoint) => self.breakpoints.toggle(breakpoint),
+                Err(e) => self.status = e,
+            }
+        }
+    }
+
+    /// Redraws the Text pane and scrolls it to the program counter
+    fn refresh(&mut self) {
+        self.update_text();
+
+        if let Some(pc_index) = self.text.find('>') {
+            self.scroll_offset.0 = self.text[0..pc_index]
+                .chars()
+                .filter(|x| *x == '\n')
+                .count()
+                .saturating_sub(7)
+                .try_into()
+                .expect("Could not convert line number to offset for pc marker");
+        }
+    }
+
+    fn update_text(&mut self) {
+        self.text = self
+            .machine
+            .listing(self.text_view)
+            .unwrap_or_default()
+            .expand_tabs(8);
+    }
+}
+
+/// Debugs a machine in the terminal until the user quits
+pub async fn debug<M: Machine>(machine: M) -> DebuggerResult<()> {
+    // Create an application.
+    let mut debugger = Debugger::new(machine);
+
+    // Initialize the terminal user interface.
+    let backend = CrosstermBackend::new(io::stdout());
+    let terminal = Terminal::new(backend)?;
+    let events = EventHandler::new(250);
+    let mut tui = Tui::new(terminal, events);
+    tui.init()?;
+
+    // Start the main loop.
+    while debugger.running {
+        // Render the user interface.
+        tui.draw(&debugger)?;
+        // Handle events.
+        match tui.events.next().await? {
+            Event::Tick => debugger.tick(),
+            Event::Key(key_event) => handle_key_events(key_event, &mut debugger).await?,
+            Event::Mouse(_) => {}
+            Event::Resize(_, _) => {}
+        }
+    }
+
+    // Exit the user interface.
+    tui.exit()?;
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use core::fmt::{self, Write};
+use std::collections::HashMap;
+
+use ::machine::State;
+
+use crate::TextView;
+
+/// State an executed instruction left a machine in, if it stopped, and what it changed
+pub type Stepped<R> = (Option<State>, Option<R>);
+
+/// Summary of one of the machines a machine is made of, shown in a small pane of its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pane {
+    pub title: String,
+    pub text: String,
+    /// Whether it is the machine the other panes show
+    pub focused: bool,
+}
+
+/// A virtual machine the debugger can run one instruction at a time
+///
+/// Stepping, undoing, the registers, memory, disassembly and output are all the debugger needs.
+/// The other methods have defaults for machines without input, memory accesses worth watching,
+/// calls or profiling, and the debugger leaves out whatever a machine does not have.
+pub trait Machine {
+    /// What executing an instruction changed, kept so it can be undone
+    type Record: fmt::Display;
+    type Error: fmt::Display;
+
+    /// Names of the instructions, which opcode breakpoints can be set on
+    const MNEMONICS: &'static [&'static str];
+
+    /// Executes the instruction at the program counter
+    ///
+    /// Returns the state it left the machine in, if it stopped, and a record of what it changed,
+    /// which is `None` when it could not execute anything.
+    fn step(&mut self) -> Result<Stepped<Self::Record>, Self::Error>;
+
+    /// Reverts an instruction, which must be the last executed one that has not been undone yet
+    fn undo(&mut self, record: &Self::Record);
+
+    fn pc(&self) -> usize;
+
+    /// Names and values of the registers besides the program counter
+    fn registers(&self) -> Vec<(&'static str, i64)>;
+
+    /// Number of cells in use, which the Memory pane scrolls through
+    fn memory_len(&self) -> usize;
+
+    fn cell(&self, addr: usize) -> i64;
+
+    /// Instruction starting at `addr` and the number of cells it takes up, or `None` if the cell
+    /// does not hold one
+    fn disassemble(&self, addr: usize) -> Option<(String, usize)>;
+
+    /// Name of the instruction at the program counter, from [`Machine::MNEMONICS`]
+    fn opcode(&self) -> Option<&'static str>;
+
+    /// Address and name of the instruction the next step executes, which breakpoints are checked
+    /// against
+    ///
+    /// That is the one at the program counter, unless the machine is made of several and the next
+    /// step is taken by one that is not shown.
+    fn next_instruction(&self) -> (usize, Option<&'static str>) {
+        (self.pc(), self.opcode())
+    }
+
+    /// Every value output so far
+    fn outputs(&self) -> &[i64];
+
+    /// Lines of the Text pane, or `None` if the machine can not lay its program out that way
+    ///
+    /// Lines start with `> ` on the program counter and two spaces elsewhere, followed by the
+    /// address in hex. By default every cell is decoded in order, and cells that do not hold an
+    /// instruction are shown as data.
+    fn listing(&self, view: TextView) -> Option<String> {
+        if view != TextView::Linear {
+            return None;
+        }
+
+        let pc = self.pc();
+        let mut listing = String::new();
+        let mut addr = 0;
+        while addr < self.memory_len() {
+            let marker = if addr == pc { "> " } else { "  " };
+
+            match self.disassemble(addr) {
+                // an instruction that overlaps the program counter is skipped to decode from it
+                Some((_, len)) if (addr + 1..addr + len).contains(&pc) => addr = pc,
+                Some((instruction, len)) => {
+                    let _ = writeln!(listing, "{marker}{addr:08x}:\t{instruction}");
+                    addr += len.max(1);
+                }
+                None => {
+                    let _ = writeln!(listing, "{marker}{addr:08x}:\t.data\t{}", self.cell(addr));
+                    addr += 1;
+                }
+            }
+        }
+
+        Some(listing)
+    }
+
+    /// Values waiting to be read and values read so far, or `None` if the machine takes no input
+    fn inputs(&self) -> Option<(Vec<i64>, &[i64])> {
+        None
+    }
+
+    /// Queues a value for the machine to read, returning whether it takes input at all
+    fn push_input(&mut self, _value: i64) -> bool {
+        false
+    }
+
+    /// Cells an executed instruction read from
+    fn reads(_record: &Self::Record) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// Cell an executed instruction wrote to
+    fn write(_record: &Self::Record) -> Option<usize> {
+        None
+    }
+
+    /// Cells the instruction at the program counter will read from, and the cell it will write to
+    fn next_accesses(&self) -> (Vec<usize>, Option<usize>) {
+        (Vec::new(), None)
+    }
+
+    /// Cell the relative base points at, for machines that have one
+    fn rel(&self) -> Option<usize> {
+        None
+    }
+
+    /// Number of calls that have not returned
+    fn call_depth(&self) -> usize {
+        0
+    }
+
+    /// Where execution is in every call, innermost first, one line each
+    fn backtrace(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Number of times each instruction was executed, or `None` if the machine is not profiled
+    fn executions(&self) -> Option<&HashMap<usize, u64>> {
+        None
+    }
+
+    /// Starts counting the instructions executed from now on, returning whether the machine can
+    /// be profiled at all
+    fn enable_profiling(&mut self) -> bool {
+        false
+    }
+
+    /// Hash of everything that decides what the machine does next, which runs use to notice
+    /// they are stuck in a loop, or `None` if the machine can not tell
+    fn fingerprint(&self) -> Option<u64> {
+        None
+    }
+
+    /// Summaries of the machines this one is made of, or nothing if it is a single machine
+    fn panes(&self) -> Vec<Pane> {
+        Vec::new()
+    }
+
+    /// Shows the machine at `index` in the other panes, returning whether there is one
+    fn focus(&mut self, _index: usize) -> bool {
+        false
+    }
+
+    /// Switches between stepping the machines in turn and stepping the focused one, returning a
+    /// description of the new mode, or `None` if this is a single machine
+    fn toggle_step_mode(&mut self) -> Option<&'static str> {
+        None
+    }
+}
@@ -0,0 +1,200 @@
+use crate::Machine;
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// Number of cells on each row of the Memory pane
+pub const ROW_LEN: usize = 8;
+
+/// Rows kept above the cell moved to with goto
+const GOTO_CONTEXT: usize = 2;
+
+/// How the Memory pane writes out the value of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellFormat {
+    Decimal,
+    Hex,
+    /// Printable ASCII codes as characters and everything else in decimal
+    Ascii,
+}
+
+impl CellFormat {
+    fn format(self, value: i64) -> String {
+        match self {
+            Self::Decimal => value.to_string(),
+            Self::Hex if value < 0 => format!("-{:#x}", value.unsigned_abs()),
+            Self::Hex => format!("{value:#x}"),
+            Self::Ascii => match u8::try_from(value) {
+                Ok(b'\n') => "'\\n'".to_string(),
+                Ok(byte) if byte == b' ' || byte.is_ascii_graphic() => {
+                    format!("'{}'", char::from(byte))
+                }
+                _ => value.to_string(),
+            },
+        }
+    }
+}
+
+/// Cells the Memory pane picks out
+#[derive(Debug, Default)]
+pub struct Marks {
+    /// Cell changed by the last step
+    pub changed: Option<usize>,
+    /// Cells the instruction at the program counter will read its parameters from
+    pub reads: Vec<usize>,
+    /// Cell the instruction at the program counter will write to
+    pub write: Option<usize>,
+    /// Cell the relative base points at
+    pub rel: Option<usize>,
+}
+
+/// Position and format of the Memory pane
+#[derive(Debug)]
+pub struct MemoryView {
+    pub format: CellFormat,
+    /// First row shown
+    pub top: usize,
+    /// Cell last moved to with goto
+    pub cursor: Option<usize>,
+    /// Whether the rows follow the relative base, to show the memory of the current frame
+    pub frame: bool,
+}
+
+impl Default for MemoryView {
+    fn default() -> Self {
+        Self {
+            format: CellFormat::Decimal,
+            top: 0,
+            cursor: None,
+            frame: false,
+        }
+    }
+}
+
+impl MemoryView {
+    /// Cycles the format of the cells through decimal, hex and ASCII
+    pub fn cycle_format(&mut self) {
+        self.format = match self.format {
+            CellFormat::Decimal => CellFormat::Hex,
+            CellFormat::Hex => CellFormat::Ascii,
+            CellFormat::Ascii => CellFormat::Decimal,
+        };
+    }
+
+    /// Switches between rows that follow the relative base and rows that stay where scrolled to
+    pub fn toggle_frame(&mut self) {
+        self.frame = !self.frame;
+    }
+
+    /// Scrolls down by `rows`, without going past memory of `len` cells
+    pub fn scroll_down(&mut self, rows: usize, len: usize) {
+        self.top = (self.top + rows).min(self.last_row(len));
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        self.top = self.top.saturating_sub(rows);
+    }
+
+    /// Scrolls to the row holding `addr` and marks the cell
+    pub fn goto(&mut self, addr: usize) {
+        self.top = (addr / ROW_LEN).saturating_sub(GOTO_CONTEXT);
+        self.cursor = Some(addr);
+    }
+
+    /// Lines of `height` rows, each starting with the address of its first cell
+    ///
+    /// Rows start from the top row, or a little before the relative base when showing the
+    /// current frame, in which case the offset from the relative base follows the address. Only
+    /// the cells on those rows are read and formatted, so the pane costs the same to draw however
+    /// much memory the program uses.
+    pub fn lines(
+        &self,
+        machine: &impl Machine,
+        height: usize,
+        marks: &Marks,
+    ) -> Vec<Line<'static>> {
+        let starts = match marks.rel.filter(|_| self.frame) {
+            Some(rel) => {
+                let first = rel.saturating_sub(GOTO_CONTEXT * ROW_LEN);
+                (0..height)
+                    .map(|row| first + row * ROW_LEN)
+                    .collect::<Vec<_>>()
+            }
+            None => {
+                let last_row = self.last_row(machine.memory_len());
+                let top = self.top.min(last_row);
+                let end = (top + height).min(last_row + 1);
+                (top..end).map(|row| row * ROW_LEN).collect()
+            }
+        };
+
+        let rows = starts
+            .iter()
+            .map(|start| {
+                (*start..start + ROW_LEN)
+                    .map(|addr| (addr, self.format.format(machine.cell(addr))))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // every cell on screen is as wide as the widest one, so columns line up
+        let width = rows
+            .iter()
+            .flatten()
+            .map(|(_, cell)| cell.len())
+            .max()
+            .unwrap_or_default();
+
+        rows.into_iter()
+            .map(|cells| {
+                let start = cells[0].0;
+                let gutter = match marks.rel.filter(|_| self.frame) {
+                    #[allow(clippy::cast_possible_wrap)]
+                    Some(rel) => format!("{start:08x} rel{:+4}:", start as isize - rel as isize),
+                    None => format!("{start:08x}:"),
+                };
+                let mut spans = vec![Span::styled(gutter, Style::default().fg(Color::DarkGray))];
+
+                for (addr, cell) in cells {
+                    spans.push(Span::raw(" "));
+                    spans.push(Span::styled(
+                        format!("{cell:>width$}"),
+                        self.style(addr, marks),
+                    ));
+                }
+
+                Line::from(spans)
+            })
+            .collect()
+    }
+
+    /// Index of the last row that can be scrolled to, which holds the last of `len` cells in use
+    /// or the cell moved to with goto
+    fn last_row(&self, len: usize) -> usize {
+        let last = len.saturating_sub(1);
+        last.max(self.cursor.unwrap_or_default()) / ROW_LEN
+    }
+
+    fn style(&self, addr: usize, marks: &Marks) -> Style {
+        let mut style = Style::default();
+
+        if marks.reads.contains(&addr) {
+            style = style.fg(Color::Cyan).add_modifier(Modifier::BOLD);
+        }
+        if marks.write == Some(addr) {
+            style = style.fg(Color::Magenta).add_modifier(Modifier::BOLD);
+        }
+        if marks.changed == Some(addr) {
+            style = style.fg(Color::Black).bg(Color::Yellow);
+        }
+        if marks.rel == Some(addr) {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+        if self.cursor == Some(addr) {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+
+        style
+    }
+}
@@ -1,6 +1,6 @@
-use crate::debugger::event::EventHandler;
-use crate::debugger::ui;
-use crate::debugger::{Debugger, DebuggerResult};
+use crate::event::EventHandler;
+use crate::ui;
+use crate::{Debugger, DebuggerResult, Machine};
 use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::Backend;
@@ -50,7 +50,7 @@ impl<B: Backend> Tui<B> {
     ///
     /// [`Draw`]: ratatui::Terminal::draw
     /// [`rendering`]: crate::ui::render
-    pub fn draw(&mut self, app: &Debugger) -> DebuggerResult<()> {
+    pub fn draw<M: Machine>(&mut self, app: &Debugger<M>) -> DebuggerResult<()> {
         self.terminal.draw(|frame| ui::render(app, frame))?;
         Ok(())
     }
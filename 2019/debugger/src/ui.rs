@@ -0,0 +1,347 @@
+use crate::utils::StringExt;
+use ratatui::{
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    symbols,
+    text::{Line, Text},
+    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+use std::collections::HashMap;
+
+use crate::{
+    instruction_address,
+    memory_view::{CellFormat, Marks},
+    Debugger, Highlight, Machine, Pane, PromptKind, State, TextView,
+};
+
+/// Rows taken by the pane of each machine a machine is made of, including the borders
+const MACHINE_PANE_HEIGHT: u16 = 6;
+
+/// Colour of an instruction executed `count` times, on a log scale up to the most executed
+/// instruction
+#[allow(clippy::cast_precision_loss)]
+fn heat(count: u64, max: u64) -> Color {
+    if count == 0 {
+        return Color::DarkGray;
+    }
+
+    let heat = (count as f64).ln_1p() / (max as f64).ln_1p();
+    match heat {
+        h if h < 0.25 => Color::Blue,
+        h if h < 0.5 => Color::Cyan,
+        h if h < 0.75 => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+/// Text pane with its instructions coloured according to how often they were executed
+fn highlighted_text<'a>(
+    text: &'a str,
+    highlight: Highlight,
+    executions: &HashMap<usize, u64>,
+) -> Text<'a> {
+    let max = executions.values().copied().max().unwrap_or_default();
+
+    text.lines()
+        .map(|line| {
+            let Some(addr) = instruction_address(line) else {
+                return Line::from(line);
+            };
+
+            let count = executions.get(&addr).copied().unwrap_or_default();
+            let color = match highlight {
+                Highlight::None => return Line::from(line),
+                Highlight::Hotness => heat(count, max),
+                Highlight::Coverage if count == 0 => Color::Red,
+                Highlight::Coverage => Color::Green,
+            };
+
+            Line::from(line).style(Style::default().fg(color))
+        })
+        .collect()
+}
+
+/// Renders the user interface widgets.
+pub fn render<M: Machine>(debugger: &Debugger<M>, frame: &mut Frame) {
+    let panes = debugger.machine.panes();
+    let bottom_line = bottom_line(debugger, !panes.is_empty());
+
+    // the help wraps onto a second line when the screen is too narrow for it
+    let bottom_height = if bottom_line.len() > usize::from(frame.area().width) {
+        2
+    } else {
+        1
+    };
+    let panes_height = if panes.is_empty() {
+        0
+    } else {
+        MACHINE_PANE_HEIGHT
+    };
+
+    let screen = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Length(panes_height),
+            Constraint::Min(0),
+            Constraint::Length(bottom_height),
+        ])
+        .split(frame.area());
+
+    render_machine_panes(&panes, frame, screen[0]);
+
+    let layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Percentage(35), Constraint::Percentage(65)])
+        .split(screen[1]);
+
+    let inspector = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Percentage(20),
+            Constraint::Percentage(20),
+            Constraint::Percentage(60),
+        ])
+        .split(layout[1]);
+
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(inspector[1]);
+
+    let inspector_border_set = symbols::border::Set {
+        top_left: symbols::line::ROUNDED.horizontal_down,
+        ..symbols::border::ROUNDED
+    };
+
+    let breakpoints_border_set = symbols::border::Set {
+        top_right: symbols::line::NORMAL.vertical_left,
+        top_left: symbols::line::NORMAL.vertical_right,
+        ..symbols::border::ROUNDED
+    };
+
+    let backtrace_border_set = symbols::border::Set {
+        top_right: symbols::line::NORMAL.vertical_left,
+        top_left: symbols::line::NORMAL.horizontal_down,
+        ..symbols::border::ROUNDED
+    };
+
+    let memory_border_set = symbols::border::Set {
+        top_right: symbols::line::NORMAL.vertical_left,
+        top_left: symbols::line::NORMAL.vertical_right,
+        bottom_left: symbols::line::NORMAL.horizontal_up,
+        ..symbols::border::ROUNDED
+    };
+
+    let text_title = match (debugger.text_view, debugger.highlight) {
+        (TextView::Linear, Highlight::None) => "Text",
+        (TextView::Linear, Highlight::Hotness) => "Text (hotness)",
+        (TextView::Linear, Highlight::Coverage) => "Text (coverage)",
+        (TextView::ControlFlow, Highlight::None) => "Text (control flow)",
+        (TextView::ControlFlow, Highlight::Hotness) => "Text (control flow, hotness)",
+        (TextView::ControlFlow, Highlight::Coverage) => "Text (control flow, coverage)",
+    };
+
+    let text = match debugger.machine.executions() {
+        Some(executions) if debugger.highlight != Highlight::None => {
+            highlighted_text(&debugger.text, debugger.highlight, executions)
+        }
+        _ => Text::from(debugger.text.as_str()),
+    };
+
+    frame.render_widget(
+        Paragraph::new(text)
+            .block(
+                Block::bordered()
+                    .title(text_title)
+                    .borders(Borders::TOP | Borders::LEFT | Borders::BOTTOM)
+                    .border_type(BorderType::Rounded)
+                    .title_alignment(Alignment::Center),
+            )
+            .style(Style::default())
+            .scroll(debugger.scroll_offset),
+        layout[0],
+    );
+
+    let inspector_title = if debugger.state == Some(State::NeedsInput) {
+        Line::from("Inspector: waiting for input").style(Style::default().fg(Color::Yellow))
+    } else {
+        Line::from("Inspector")
+    };
+
+    let pc = debugger.machine.pc();
+    let registers = debugger
+        .machine
+        .registers()
+        .iter()
+        .map(|(name, value)| format!("\t{name}: {value:#08x}\t{value:08}"))
+        .collect::<String>();
+    let input = match debugger.machine.inputs() {
+        Some((queued, consumed)) => format!("\nInput: {queued:?} queued, {consumed:?} consumed"),
+        None => String::new(),
+    };
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{}{}{input}\nOutput:\n{:x?}",
+            format!("PC: {pc:#08}\t{pc:08}{registers}\n").expand_tabs(8),
+            debugger.status,
+            debugger.machine.outputs(),
+        ))
+        .wrap(Wrap::default())
+        .block(
+            Block::bordered()
+                .title(inspector_title)
+                .border_set(inspector_border_set)
+                .borders(Borders::TOP | Borders::RIGHT | Borders::LEFT)
+                .title_alignment(Alignment::Center),
+        )
+        .style(Style::default()),
+        inspector[0],
+    );
+
+    let breakpoints = debugger
+        .breakpoints
+        .list
+        .iter()
+        .enumerate()
+        .map(|(i, breakpoint)| {
+            let hit = if debugger.breakpoints.last_hit == Some(i) {
+                "*"
+            } else {
+                " "
+            };
+
+            format!("{hit}{i:>3}  {breakpoint}")
+        })
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(breakpoints.join("\n"))
+            .block(
+                Block::bordered()
+                    .title("Breakpoints")
+                    .border_set(breakpoints_border_set)
+                    .borders(Borders::TOP | Borders::LEFT)
+                    .title_alignment(Alignment::Center),
+            )
+            .style(Style::default()),
+        middle[0],
+    );
+
+    let backtrace = debugger
+        .machine
+        .backtrace()
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| format!("#{i:<3} {entry}"))
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(backtrace.join("\n"))
+            .block(
+                Block::bordered()
+                    .title("Backtrace")
+                    .border_set(backtrace_border_set)
+                    .borders(Borders::TOP | Borders::RIGHT | Borders::LEFT)
+                    .title_alignment(Alignment::Center),
+            )
+            .style(Style::default()),
+        middle[1],
+    );
+
+    let (reads, write) = debugger.machine.next_accesses();
+    let marks = Marks {
+        rel: debugger.machine.rel(),
+        changed: debugger.history.back().and_then(M::write),
+        reads,
+        write,
+    };
+
+    let format = match debugger.memory_view.format {
+        CellFormat::Decimal => None,
+        CellFormat::Hex => Some("hex"),
+        CellFormat::Ascii => Some("ASCII"),
+    };
+    let memory_title = match (debugger.memory_view.frame, format) {
+        (false, None) => "Memory".to_string(),
+        (false, Some(format)) => format!("Memory ({format})"),
+        (true, None) => "Memory (frame)".to_string(),
+        (true, Some(format)) => format!("Memory (frame, {format})"),
+    };
+
+    // rows between the top and bottom borders
+    let memory_rows = usize::from(inspector[2].height.saturating_sub(2));
+
+    frame.render_widget(
+        Paragraph::new(
+            debugger
+                .memory_view
+                .lines(&debugger.machine, memory_rows, &marks),
+        )
+        .block(
+            Block::bordered()
+                .title(memory_title)
+                .border_set(memory_border_set)
+                .title_alignment(Alignment::Center),
+        )
+        .style(Style::default()),
+        inspector[2],
+    );
+
+    frame.render_widget(Paragraph::new(bottom_line).wrap(Wrap::default()), screen[2]);
+}
+
+/// Small panes side by side, one for each machine a machine is made of
+fn render_machine_panes(panes: &[Pane], frame: &mut Frame, area: Rect) {
+    let Ok(count) = u32::try_from(panes.len()) else {
+        return;
+    };
+
+    let areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Ratio(1, count); panes.len()])
+        .split(area);
+
+    for (pane, area) in panes.iter().zip(areas.iter()) {
+        let style = if pane.focused {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+
+        frame.render_widget(
+            Paragraph::new(pane.text.as_str()).block(
+                Block::bordered()
+                    .title(pane.title.as_str())
+                    .border_type(BorderType::Rounded)
+                    .border_style(style)
+                    .title_alignment(Alignment::Center),
+            ),
+            *area,
+        );
+    }
+}
+
+/// Prompt being typed into, or the keys when there is none
+fn bottom_line<M: Machine>(debugger: &Debugger<M>, several: bool) -> String {
+    let Some(prompt) = &debugger.prompt else {
+        let keys = "n: step  o: over  u: out  p: back  c: continue  i: input  r: reverse  \
+                    w: last write  b: break  d: view  h: highlight  f: format  g: goto  l: frame  \
+                    PgUp/PgDn: memory  q: quit";
+
+        return if several {
+            format!("tab/0-9: focus  m: step mode  {keys}")
+        } else {
+            keys.to_string()
+        };
+    };
+
+    match prompt.kind {
+        PromptKind::Breakpoint => format!("break> {}", prompt.text),
+        PromptKind::Input => format!("input (integers or text)> {}", prompt.text),
+        PromptKind::LastWrite => format!("last write to> {}", prompt.text),
+        PromptKind::Goto => format!("goto address> {}", prompt.text),
+    }
+}
@@ -0,0 +1,66 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+use intcode::Interpreter;
+
+/// Builds a program that loops `n` times
+type Program = fn(i64) -> Vec<i64>;
+
+/// Counts a cell down from `n` to zero, executing two instructions per iteration
+fn countdown(n: i64) -> Vec<i64> {
+    vec![
+        1001, 8, -1, 8, // Add [8], -1 -> [8]
+        1005, 8, 0,  // Jt [8], 0
+        99, // Halt
+        n,
+    ]
+}
+
+/// Sums 1..=n into a cell using relative addressing, then outputs the sum
+fn relative_sum(n: i64) -> Vec<i64> {
+    vec![
+        109, 19, // Rel 19
+        22201, 0, 1, 1, // Add [0 + rel], [1 + rel] -> [1 + rel]
+        21201, 0, -1, 0, // Add [0 + rel], -1 -> [0 + rel]
+        1005, 19, 2, // Jt [19], 2
+        204, 1,  // Out [1 + rel]
+        99, // Halt
+        0, 0, 0, n, 0,
+    ]
+}
+
+/// Runs each program with every instruction decoded afresh and with decoded instructions cached,
+/// to measure what the cache saves
+fn criterion_benchmark(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let programs: [(&str, Program); 2] = [
+        ("countdown 100000", countdown),
+        ("relative sum 100000", relative_sum),
+    ];
+
+    for (name, program) in programs {
+        let mut group = c.benchmark_group(name);
+
+        for cached in [false, true] {
+            let id = if cached { "cached" } else { "uncached" };
+
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    let mut interpreter = Interpreter::new(program(black_box(100_000)));
+                    if !cached {
+                        interpreter.executor.disable_decode_cache();
+                    }
+
+                    runtime.block_on(interpreter.exec()).unwrap();
+                    interpreter.output_history().first().copied()
+                });
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
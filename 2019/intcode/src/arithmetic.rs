@@ -0,0 +1,218 @@
+use core::str::FromStr;
+
+use num_bigint::{BigInt, Sign};
+
+use crate::opcode::Opcode;
+
+/// How `Add` and `Mul` deal with results that do not fit in a 64 bit cell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arithmetic {
+    /// Results wrap around like two's complement hardware, which is the only policy compiled code
+    /// follows
+    #[default]
+    Wrapping,
+    /// A result that overflows stops the program with [`IntcodeError::Overflow`]
+    ///
+    /// [`IntcodeError::Overflow`]: crate::IntcodeError::Overflow
+    Checked,
+    /// Results are kept exactly, in cells that hold a big integer once they need to
+    ///
+    /// Comparisons and jumps see the exact values, but using a big cell as an address, output or
+    /// relative base adjustment fails with [`IntcodeError::ValueTooLarge`]. Every instruction
+    /// that does arithmetic allocates, so this is a lot slower than the other policies.
+    ///
+    /// [`IntcodeError::ValueTooLarge`]: crate::IntcodeError::ValueTooLarge
+    Big,
+}
+
+impl Arithmetic {
+    /// Result of an `Add` or `Mul`, or `None` if it overflows under checked arithmetic
+    ///
+    /// Big integer arithmetic is done with [`exact`] instead, so it wraps around here.
+    pub(crate) const fn apply(self, opcode: Opcode, a: i64, b: i64) -> Option<i64> {
+        match (self, opcode) {
+            (Self::Checked, Opcode::Mul) => a.checked_mul(b),
+            (Self::Checked, _) => a.checked_add(b),
+            (_, Opcode::Mul) => Some(a.wrapping_mul(b)),
+            (_, _) => Some(a.wrapping_add(b)),
+        }
+    }
+}
+
+impl FromStr for Arithmetic {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrapping" => Ok(Self::Wrapping),
+            "checked" => Ok(Self::Checked),
+            "big" => Ok(Self::Big),
+            _ => Err(format!(
+                "Unknown arithmetic `{s}`, expected wrapping, checked or big"
+            )),
+        }
+    }
+}
+
+/// Exact result of an `Add` or `Mul`
+pub(crate) fn exact(opcode: Opcode, a: BigInt, b: BigInt) -> BigInt {
+    match opcode {
+        Opcode::Mul => a * b,
+        _ => a + b,
+    }
+}
+
+/// Low 64 bits of a big integer, which is what wrapping arithmetic would have left in a cell
+pub(crate) fn low_bits(value: &BigInt) -> i64 {
+    let low = value.iter_u64_digits().next().unwrap_or(0) as i64;
+
+    match value.sign() {
+        Sign::Minus => low.wrapping_neg(),
+        Sign::NoSign | Sign::Plus => low,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CellValue, IntcodeError, Interpreter, Memory, State};
+
+    const POLICIES: [Arithmetic; 3] = [Arithmetic::Wrapping, Arithmetic::Checked, Arithmetic::Big];
+
+    /// Multiplies `i64::MAX` by 2 into address 13, then outputs whether the result is below 0
+    const DOUBLE_MAX: &str = "1102,9223372036854775807,2,13,1007,13,0,14,4,14,99,0,0,0,0";
+
+    fn run(program: &str, input: &[i64], arithmetic: Arithmetic) -> Result<Vec<i64>, IntcodeError> {
+        let mut interpreter = Interpreter::parse(program)?.with_arithmetic(arithmetic);
+        for value in input {
+            interpreter.push_input(*value);
+        }
+
+        loop {
+            match interpreter.run()? {
+                State::Output(_) => {}
+                State::NeedsInput | State::Halted => {
+                    return Ok(interpreter.output_history().to_vec())
+                }
+            }
+        }
+    }
+
+    /// The large number checks of the BOOST program from 2019 day 9, which every policy has to
+    /// pass the same way
+    #[test]
+    fn policies_agree_on_boost_checks() {
+        let cases: [(&str, &[i64], &[i64]); 5] = [
+            (
+                "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+                &[],
+                &[
+                    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+                ],
+            ),
+            (
+                "1102,34915192,34915192,7,4,7,99,0",
+                &[],
+                &[1_219_070_632_396_864],
+            ),
+            ("104,1125899906842624,99", &[], &[1_125_899_906_842_624]),
+            // reads a value into a relative address far past the image and echoes it back
+            ("109,1000000,203,5,204,5,99", &[-7], &[-7]),
+            // squares 34463338 and checks the result is larger than it
+            (
+                "1102,34463338,34463338,63,1007,63,34463338,64,4,63,4,64,99",
+                &[],
+                &[1_187_721_666_102_244, 0],
+            ),
+        ];
+
+        for (program, input, expected) in cases {
+            for arithmetic in POLICIES {
+                assert_eq!(
+                    run(program, input, arithmetic).unwrap(),
+                    expected,
+                    "{program} with {arithmetic:?} arithmetic"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn policies_differ_on_overflow() {
+        assert_eq!(run(DOUBLE_MAX, &[], Arithmetic::Wrapping).unwrap(), [1]);
+        assert_eq!(run(DOUBLE_MAX, &[], Arithmetic::Big).unwrap(), [0]);
+
+        let error = run(DOUBLE_MAX, &[], Arithmetic::Checked).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                IntcodeError::Overflow {
+                    pc: 0,
+                    opcode: Opcode::Mul,
+                    a: i64::MAX,
+                    b: 2,
+                }
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn big_values_in_jumps() {
+        // 2^32 * 2^32 has no low bits set, but is not 0
+        let program = "1102,4294967296,4294967296,13,1006,13,10,104,1,99,99,0,0,0";
+
+        assert!(run(program, &[], Arithmetic::Wrapping).unwrap().is_empty());
+        assert_eq!(run(program, &[], Arithmetic::Big).unwrap(), [1]);
+    }
+
+    #[test]
+    fn big_values_do_not_fit_in_outputs() {
+        let program = "1102,9223372036854775807,2,7,4,7,99,0";
+
+        let error = run(program, &[], Arithmetic::Big).unwrap_err();
+        assert!(
+            matches!(error, IntcodeError::ValueTooLarge { pc: 4, addr: 7 }),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn undo_restores_big_values() {
+        // writes 2^64 - 2 to address 9, then overwrites it with 5
+        let program = "1102,9223372036854775807,2,9,1101,0,5,9,99,0";
+        let mut interpreter = Interpreter::parse(program)
+            .unwrap()
+            .with_arithmetic(Arithmetic::Big);
+
+        let (_, first) = interpreter.trace_step().unwrap();
+        let big = interpreter.executor.memory.value(9);
+        assert_eq!(big, CellValue::Big(BigInt::from(u64::MAX - 1)));
+
+        let (_, second) = interpreter.trace_step().unwrap();
+        assert_eq!(interpreter.executor.memory.value(9), CellValue::Int(5));
+
+        interpreter.undo(&second.unwrap());
+        assert_eq!(interpreter.executor.memory.value(9), big);
+
+        interpreter.undo(&first.unwrap());
+        assert_eq!(interpreter.executor.memory.value(9), CellValue::Int(0));
+    }
+
+    #[test]
+    fn hash_counts_high_bits() {
+        let value = |shift: u32| (BigInt::from(1) << shift) + 1;
+
+        let mut left = Memory::new(vec![0; 4]);
+        let mut right = Memory::new(vec![0; 4]);
+        left.set_big(2, value(64)).unwrap();
+        right.set_big(2, value(65)).unwrap();
+        assert_eq!(left.get(2), right.get(2));
+        assert_ne!(left.hash(), right.hash());
+
+        // a cell that no longer holds a big integer hashes like any other
+        left.set(2, 1).unwrap();
+        right.set(2, 1).unwrap();
+        assert_eq!(left.hash(), right.hash());
+        assert_eq!(left.hash(), Memory::new(vec![0, 0, 1, 0]).hash());
+    }
+}
@@ -0,0 +1,226 @@
+//! Helpers for programs that talk in lines of ASCII text
+
+use std::io::{BufRead, Write};
+
+use crate::{error::IntcodeError, executor::State, interpreter::Interpreter};
+
+/// Everything a program output before it stopped to wait for input or halted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsciiOutput {
+    /// Outputs that are ASCII codes, as text
+    pub text: String,
+    /// Last output that is not an ASCII code, such as the answer to a puzzle
+    pub value: Option<i64>,
+    /// Either [`State::NeedsInput`] or [`State::Halted`]
+    pub state: State,
+}
+
+/// Character an output stands for, if it is an ASCII code
+pub(crate) fn ascii(value: i64) -> Option<char> {
+    u8::try_from(value)
+        .ok()
+        .filter(u8::is_ascii)
+        .map(char::from)
+}
+
+#[allow(clippy::missing_errors_doc)]
+impl Interpreter {
+    /// Queues a line of text as ASCII codes, followed by a newline
+    pub fn send_line(&mut self, line: &str) {
+        for byte in line.bytes().chain([b'\n']) {
+            self.push_input(i64::from(byte));
+        }
+    }
+
+    /// Runs until the program outputs a newline, returning the line without it
+    ///
+    /// If the program needs input or halts first, whatever it output since the last newline is
+    /// returned, which is usually a prompt. `None` is returned if that is nothing. Outputs that are
+    /// not ASCII codes are written into the line as numbers.
+    pub fn read_line(&mut self) -> Result<Option<String>, IntcodeError> {
+        let mut line = String::new();
+
+        loop {
+            match self.run()? {
+                State::Output(value) => match ascii(value) {
+                    Some('\n') => return Ok(Some(line)),
+                    Some(c) => line.push(c),
+                    None => line.push_str(&value.to_string()),
+                },
+                State::NeedsInput | State::Halted => {
+                    return Ok((!line.is_empty()).then_some(line));
+                }
+            }
+        }
+    }
+
+    /// Runs until the program needs input or halts, collecting its output
+    pub fn read_ascii(&mut self) -> Result<AsciiOutput, IntcodeError> {
+        let mut text = String::new();
+        let mut value = None;
+
+        loop {
+            match self.run()? {
+                State::Output(output) => match ascii(output) {
+                    Some(c) => text.push(c),
+                    None => value = Some(output),
+                },
+                state @ (State::NeedsInput | State::Halted) => {
+                    return Ok(AsciiOutput { text, value, state });
+                }
+            }
+        }
+    }
+
+    /// Plays the program like a terminal would, writing everything it outputs to `output` and
+    /// sending it every line read from `input` when it waits for input
+    ///
+    /// Outputs that are not ASCII codes are written on a line of their own once the program
+    /// stops. Returns [`State::Halted`] once the program halts, or [`State::NeedsInput`] if it
+    /// waits for input after `input` ended.
+    pub fn play(
+        &mut self,
+        input: impl BufRead,
+        mut output: impl Write,
+    ) -> Result<State, IntcodeError> {
+        let mut lines = input.lines();
+
+        loop {
+            let ascii = self.read_ascii()?;
+            write!(output, "{}", ascii.text)?;
+            if let Some(value) = ascii.value {
+                writeln!(output, "{value}")?;
+            }
+            output.flush()?;
+
+            if ascii.state == State::Halted {
+                return Ok(State::Halted);
+            }
+
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(State::NeedsInput);
+            };
+            self.send_line(&line);
+        }
+    }
+
+    /// Every output so far that is an ASCII code, as text
+    #[must_use]
+    pub fn output_text(&self) -> String {
+        self.output_history()
+            .iter()
+            .filter_map(|value| ascii(*value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    /// Outputs every value it reads
+    const ECHO: &str = "3,7,4,7,1105,1,0,0";
+
+    /// Asks a question, reads a line for the answer and outputs a large number, like the dust
+    /// count of 2019/17
+    const QUESTION: &str = "
+                Out     71
+                Out     111
+                Out     63
+                Out     10
+        loop:   In      [char]
+                Eq      [char], 10, [newline]
+                Jf      [newline], loop
+                Out     1000000
+                Halt
+        char:   .data   0
+        newline: .data  0
+        ";
+
+    fn outputs(values: &[i64]) -> Interpreter {
+        let mut program = vec![];
+        for value in values {
+            program.extend([104, *value]);
+        }
+        program.push(99);
+
+        Interpreter::new(program)
+    }
+
+    #[test]
+    fn ascii_codes() {
+        assert_eq!(ascii(10), Some('\n'));
+        assert_eq!(ascii(65), Some('A'));
+        assert_eq!(ascii(127), Some('\u{7f}'));
+
+        for value in [-1, 128, 255, 1_000_000, i64::MIN] {
+            assert_eq!(ascii(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn sends_lines_as_ascii_codes() {
+        let mut interpreter = Interpreter::parse(ECHO).unwrap();
+        interpreter.send_line("hi");
+        interpreter.send_line("");
+
+        assert_eq!(interpreter.read_line().unwrap().as_deref(), Some("hi"));
+        assert_eq!(interpreter.read_line().unwrap().as_deref(), Some(""));
+        assert_eq!(interpreter.read_line().unwrap(), None);
+        assert_eq!(interpreter.input_history(), [104, 105, 10, 10]);
+    }
+
+    #[test]
+    fn reads_lines() {
+        let mut interpreter = outputs(&[97, 98, 10, 65, 1000, -1, 10, 62, 32]);
+
+        assert_eq!(interpreter.read_line().unwrap().as_deref(), Some("ab"));
+        assert_eq!(interpreter.read_line().unwrap().as_deref(), Some("A1000-1"));
+        // a prompt without a newline is returned once the program stops
+        assert_eq!(interpreter.read_line().unwrap().as_deref(), Some("> "));
+        assert_eq!(interpreter.read_line().unwrap(), None);
+    }
+
+    #[test]
+    fn reads_text_and_values() {
+        let mut interpreter = outputs(&[79, 75, 10, 128, 68, 1_234_567]);
+
+        let output = interpreter.read_ascii().unwrap();
+        assert_eq!(
+            output,
+            AsciiOutput {
+                text: "OK\nD".to_string(),
+                value: Some(1_234_567),
+                state: State::Halted,
+            }
+        );
+        assert_eq!(interpreter.output_text(), "OK\nD");
+    }
+
+    #[test]
+    fn plays_until_halted() {
+        let mut interpreter = Interpreter::new(assemble(QUESTION).unwrap());
+        let mut output = vec![];
+
+        let state = interpreter
+            .play("yes\nignored\n".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(state, State::Halted);
+        assert_eq!(String::from_utf8(output).unwrap(), "Go?\n1000000\n");
+        assert_eq!(interpreter.input_history(), [121, 101, 115, 10]);
+    }
+
+    #[test]
+    fn plays_until_input_ends() {
+        let mut interpreter = Interpreter::parse(ECHO).unwrap();
+        let mut output = vec![];
+
+        // line endings are normalized to a single newline
+        let state = interpreter
+            .play("one\r\ntwo".as_bytes(), &mut output)
+            .unwrap();
+        assert_eq!(state, State::NeedsInput);
+        assert_eq!(String::from_utf8(output).unwrap(), "one\ntwo\n");
+    }
+}
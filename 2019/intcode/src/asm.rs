@@ -0,0 +1,472 @@
+//! Assembler for the syntax printed by the disassembly of an [`Executor`](crate::executor::Executor)
+//!
+//! Every line holds at most one statement, optionally preceded by labels and followed by a
+//! comment:
+//!
+//! ```text
+//! ; outputs the numbers from 10 down to 1
+//! loop:   Out     [counter]
+//!         Add     [counter], -1, [counter]
+//!         Jt      [counter], loop
+//!         Halt
+//! counter: .data 10
+//! ```
+//!
+//! Parameters are written as `N` for immediate mode, `[N]` for position mode and `[N + rel]` for
+//! relative mode, where `N` is an integer, a label, or a sum of them. Lines printed by the
+//! disassembler, including the `> ` program counter marker, the `00000000:` address prefix and
+//! `Invalid instruction` lines for data, are accepted as well. An address prefix must match the
+//! address the statement is assembled at.
+//!
+//! The mode digits of parameters an instruction does not have are ignored when it is executed.
+//! To keep them, the whole instruction word can be given after the mnemonic, as in `Halt(10099)`,
+//! which is how the disassembler prints instructions with such digits.
+
+use std::collections::HashMap;
+
+use crate::{
+    decode::Decoded,
+    error::IntcodeError,
+    opcode::{ModeOpt, Opcode},
+};
+
+#[derive(Debug)]
+enum Term {
+    /// Number as written, which is wider than a cell so that `-9223372036854775808` can be
+    /// written as a negated term
+    Number(i128),
+    Label(String),
+}
+
+/// Sum of signed terms
+#[derive(Debug)]
+struct Expr(Vec<(i64, Term)>);
+
+#[derive(Debug)]
+enum Statement {
+    Instruction {
+        /// Opcode and parameter modes, as they are stored in memory
+        word: i64,
+        opcode: Opcode,
+        operands: Vec<(ModeOpt, Expr)>,
+    },
+    Data(Vec<Expr>),
+}
+
+impl Statement {
+    fn len(&self) -> usize {
+        match self {
+            Self::Instruction { opcode, .. } => opcode.len(),
+            Self::Data(values) => values.len(),
+        }
+    }
+}
+
+/// Assembles source text into a program
+///
+/// # Errors
+///
+/// Returns [`IntcodeError::Asm`] with the line number of the first statement that could not be
+/// assembled
+pub fn assemble(source: &str) -> Result<Vec<i64>, IntcodeError> {
+    let mut labels = HashMap::new();
+    let mut statements = vec![];
+    let mut addr = 0;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |message: String| IntcodeError::Asm {
+            line: line_no,
+            message,
+        };
+
+        let line = line.split_once(';').map_or(line, |(code, _)| code).trim();
+        let mut rest = line.strip_prefix('>').unwrap_or(line).trim_start();
+
+        // address prefixes and labels
+        while let Some((head, tail)) = rest.split_once(':') {
+            let head = head.trim();
+
+            if is_address(head) {
+                let expected = usize::from_str_radix(head, 16).map_err(|e| err(e.to_string()))?;
+                if expected != addr {
+                    return Err(err(format!(
+                        "Address prefix {expected:#x} does not match assembled address {addr:#x}"
+                    )));
+                }
+            } else if is_label(head) {
+                if labels.insert(head.to_string(), addr).is_some() {
+                    return Err(err(format!("Label `{head}` is defined more than once")));
+                }
+            } else {
+                break;
+            }
+
+            rest = tail.trim_start();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = rest
+            .split_once(char::is_whitespace)
+            .map_or((rest, ""), |(m, o)| (m, o.trim()));
+
+        let statement = match mnemonic {
+            ".data" => Statement::Data(
+                split_operands(operands)
+                    .map(parse_expr)
+                    .collect::<Result<_, _>>()
+                    .map_err(err)?,
+            ),
+            "Invalid" => {
+                let (value, at) = operands
+                    .strip_prefix("instruction:")
+                    .and_then(|o| o.split_once("at:"))
+                    .ok_or_else(|| err(format!("Unknown statement `{rest}`")))?;
+
+                let value = parse_number(value.trim()).map_err(err)?;
+                let value = i64::try_from(value)
+                    .map_err(|_| err(format!("Value {value} does not fit in a cell")))?;
+                let at = parse_number(at.trim()).map_err(err)?;
+                if usize::try_from(at).ok() != Some(addr) {
+                    return Err(err(format!(
+                        "Data address {at:#x} does not match assembled address {addr:#x}"
+                    )));
+                }
+
+                Statement::Data(vec![Expr(vec![(1, Term::Number(value.into()))])])
+            }
+            _ => {
+                let (mnemonic, word) = match mnemonic.split_once('(') {
+                    Some((mnemonic, word)) => {
+                        let word = word
+                            .strip_suffix(')')
+                            .ok_or_else(|| err(format!("Unclosed instruction word `{rest}`")))?;
+                        (mnemonic, Some(parse_number(word).map_err(err)?))
+                    }
+                    None => (mnemonic, None),
+                };
+
+                let opcode = mnemonic.parse::<Opcode>().map_err(err)?;
+                let operands = split_operands(operands)
+                    .map(parse_operand)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+
+                if operands.len() != opcode.len() - 1 {
+                    return Err(err(format!(
+                        "{opcode:?} takes {} parameters but {} were given",
+                        opcode.len() - 1,
+                        operands.len()
+                    )));
+                }
+
+                let mut canonical = opcode as i64;
+                for (place, (mode, _)) in [100, 1_000, 10_000].iter().zip(&operands) {
+                    canonical += place * *mode as i64;
+                }
+
+                let matches = |decoded: Decoded| {
+                    let modes = operands.iter().map(|(mode, _)| mode);
+                    decoded.opcode == opcode && decoded.modes[..operands.len()].iter().eq(modes)
+                };
+                let word = match word {
+                    None => canonical,
+                    Some(word) => i64::try_from(word)
+                        .ok()
+                        .filter(|word| Decoded::new(*word, addr).is_ok_and(matches))
+                        .ok_or_else(|| {
+                            err(format!("Instruction word {word} does not match `{rest}`"))
+                        })?,
+                };
+
+                Statement::Instruction {
+                    word,
+                    opcode,
+                    operands,
+                }
+            }
+        };
+
+        addr += statement.len();
+        statements.push((line_no, statement));
+    }
+
+    let mut program = Vec::with_capacity(addr);
+    for (line_no, statement) in statements {
+        let resolve = |expr: &Expr| {
+            expr.resolve(&labels).map_err(|message| IntcodeError::Asm {
+                line: line_no,
+                message,
+            })
+        };
+
+        match statement {
+            Statement::Instruction { word, operands, .. } => {
+                program.push(word);
+                for (_, expr) in &operands {
+                    program.push(resolve(expr)?);
+                }
+            }
+            Statement::Data(values) => {
+                for expr in &values {
+                    program.push(resolve(expr)?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+impl Expr {
+    fn resolve(&self, labels: &HashMap<String, usize>) -> Result<i64, String> {
+        let mut total: i128 = 0;
+        for (sign, term) in &self.0 {
+            let value = match term {
+                Term::Number(n) => *n,
+                Term::Label(label) => labels
+                    .get(label)
+                    .map(|addr| *addr as i128)
+                    .ok_or_else(|| format!("Undefined label `{label}`"))?,
+            };
+
+            total = i128::from(*sign)
+                .checked_mul(value)
+                .and_then(|value| total.checked_add(value))
+                .ok_or("Expression overflows")?;
+        }
+
+        i64::try_from(total).map_err(|_| "Expression overflows".to_string())
+    }
+}
+
+fn is_address(s: &str) -> bool {
+    s.len() == 8 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && s != "rel"
+}
+
+fn split_operands(operands: &str) -> impl Iterator<Item = &str> {
+    operands
+        .split(',')
+        .map(str::trim)
+        .filter(|operand| !operand.is_empty())
+}
+
+fn parse_number(s: &str) -> Result<i128, String> {
+    let (negative, digits) = s.strip_prefix('-').map_or((false, s), |d| (true, d));
+
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse::<i128>(),
+    }
+    .map_err(|e| format!("Invalid number `{s}`: {e}"))?;
+
+    Ok(if negative { -value } else { value })
+}
+
+fn parse_operand(operand: &str) -> Result<(ModeOpt, Expr), String> {
+    let Some(inner) = operand.strip_prefix('[').and_then(|o| o.strip_suffix(']')) else {
+        return Ok((ModeOpt::Immediate, parse_expr(operand)?));
+    };
+
+    let mut expr = parse_expr(inner)?;
+
+    let rel = expr
+        .0
+        .iter()
+        .position(|term| matches!(term, (1, Term::Label(label)) if label == "rel"));
+
+    Ok(match rel {
+        Some(i) => {
+            expr.0.remove(i);
+            (ModeOpt::Relative, expr)
+        }
+        None => (ModeOpt::Position, expr),
+    })
+}
+
+fn parse_expr(expr: &str) -> Result<Expr, String> {
+    let mut terms = vec![];
+    let mut sign = 1;
+    let mut expect_term = true;
+    let mut chars = expr.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '+' | '-' => {
+                if expect_term {
+                    // unary sign
+                    if c == '-' {
+                        sign = -sign;
+                    }
+                } else {
+                    sign = if c == '-' { -1 } else { 1 };
+                    expect_term = true;
+                }
+
+                chars.next();
+            }
+            _ if expect_term => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                        end = i + c.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                let token = &expr[start..end];
+                let term = if token.starts_with(|c: char| c.is_ascii_digit()) {
+                    Term::Number(parse_number(token)?)
+                } else if is_label(token) || token == "rel" {
+                    Term::Label(token.to_string())
+                } else {
+                    return Err(format!("Unexpected `{c}` in `{expr}`"));
+                };
+
+                terms.push((sign, term));
+                sign = 1;
+                expect_term = false;
+            }
+            _ => return Err(format!("Expected `+` or `-` in `{expr}`")),
+        }
+    }
+
+    if expect_term {
+        return Err(format!("Incomplete expression `{expr}`"));
+    }
+
+    Ok(Expr(terms))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::{parse_program, Disassembler};
+
+    /// Example programs from the 2019 puzzles
+    const EXAMPLES: [&str; 8] = [
+        "1,9,10,3,2,3,11,0,99,30,40,50",
+        "1002,4,3,4,33",
+        "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+        "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,\
+         1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+        "1102,34915192,34915192,7,4,7,99,0",
+        "104,1125899906842624,99",
+    ];
+
+    /// Disassembles a program and assembles the listing again
+    fn round_trip(program: &[i64]) -> Vec<i64> {
+        let listing = Disassembler::new(program).disassemble().to_string();
+
+        assemble(&listing).unwrap_or_else(|e| panic!("{e} in\n{listing}"))
+    }
+
+    #[test]
+    fn round_trips_examples() {
+        for example in EXAMPLES {
+            let program = parse_program(example).unwrap();
+            assert_eq!(round_trip(&program), program, "{example}");
+        }
+    }
+
+    /// Puzzle inputs are not committed, so only the ones that are checked out next to the crate
+    /// are round tripped
+    #[test]
+    fn round_trips_inputs() {
+        let days = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+
+        for entry in fs::read_dir(days).unwrap() {
+            let input = entry.unwrap().path().join("input.txt");
+            let Ok(text) = fs::read_to_string(&input) else {
+                continue;
+            };
+            let Ok(program) = parse_program(&text) else {
+                continue;
+            };
+
+            assert_eq!(round_trip(&program), program, "{}", input.display());
+        }
+    }
+
+    #[test]
+    fn round_trips_edge_values() {
+        let programs: [&[i64]; 6] = [
+            // largest and smallest values as immediates and as data
+            &[
+                1101,
+                i64::MIN,
+                i64::MAX,
+                9,
+                99,
+                i64::MIN,
+                i64::MAX,
+                -1,
+                0,
+                0,
+            ],
+            // mode digits for parameters the instruction does not have
+            &[10099],
+            &[21104, 3, 1099, 20099],
+            &[11103, 5, 99, 0, 0, 0],
+            // jumps through memory, to the end of the program and past it
+            &[1105, 1, 7, 1006, 8, 9, 99, 99, 0, 1_000_000],
+            &[1106, 0, -1, 99],
+        ];
+
+        for program in programs {
+            assert_eq!(round_trip(program), program, "{program:?}");
+        }
+    }
+
+    #[test]
+    fn parses_extreme_numbers() {
+        assert_eq!(assemble(".data -9223372036854775808").unwrap(), [i64::MIN]);
+        assert_eq!(assemble(".data -0x8000000000000000").unwrap(), [i64::MIN]);
+        assert_eq!(assemble(".data 9223372036854775807").unwrap(), [i64::MAX]);
+        assert_eq!(
+            assemble(".data 1 - 9223372036854775808").unwrap(),
+            [i64::MIN + 1]
+        );
+
+        for overflowing in [".data 9223372036854775808", ".data -9223372036854775809"] {
+            assert!(assemble(overflowing).is_err(), "{overflowing}");
+        }
+    }
+
+    #[test]
+    fn keeps_instruction_words() {
+        assert_eq!(assemble("Halt(10099)").unwrap(), [10099]);
+        assert_eq!(assemble("Out(21104)\t7").unwrap(), [21104, 7]);
+
+        // the word has to encode the mnemonic and the modes of the operands
+        for mismatched in [
+            "Halt(10001)",
+            "Out(21004)\t7",
+            "Out(21104)\t[7]",
+            "Halt(30099)",
+        ] {
+            assert!(assemble(mismatched).is_err(), "{mismatched}");
+        }
+    }
+}
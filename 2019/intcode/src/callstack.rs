@@ -0,0 +1,333 @@
+//! Inferring function calls from executed instructions
+//!
+//! Intcode has no call or return instructions. Compiled programs store the address after a jump in
+//! a cell near the relative base and then jump to the function, which moves the relative base past
+//! its own frame with `Rel`. Returning is a jump to the address read back from that cell.
+//! [`CallStack`] watches executed instructions for that pattern, so it is a guess that programs
+//! doing something else can fool.
+
+use core::fmt;
+
+use crate::{
+    memory::Memory,
+    opcode::{ModeOpt, Opcode},
+    trace::StepRecord,
+};
+
+/// How far from the relative base a return address is looked for when a jump is taken
+const RETURN_SLOT_RANGE: isize = 16;
+
+/// A call that has not returned yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Address of the jump that made the call
+    pub call_site: usize,
+    /// Address the call jumped to, which is the start of the function
+    pub entry: usize,
+    /// Address after the jump, which the function returns to
+    pub return_addr: usize,
+    /// Cell the return address was found in
+    pub return_slot: usize,
+    /// Relative base when the call was made
+    pub rel: isize,
+}
+
+/// What an executed instruction did to the call stack, kept so it can be reverted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackChange {
+    Call(Frame),
+    /// Frames that were returned from, outermost first
+    Return(Vec<Frame>),
+}
+
+/// One line of a backtrace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BacktraceEntry {
+    /// Where execution is in the frame, which is the call site for every frame but the innermost
+    pub pc: usize,
+    /// Start of the function, or `None` for code that was not entered through a call
+    pub function: Option<usize>,
+}
+
+impl fmt::Display for BacktraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.function {
+            Some(function) => write!(f, "{:08x} in fn_{function:08x}", self.pc),
+            None => write!(f, "{:08x} in <start>", self.pc),
+        }
+    }
+}
+
+/// Calls that have been made and not returned from, outermost first
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    #[must_use]
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Number of calls that have not returned
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Updates the stack for an instruction that was just executed, given memory and the program
+    /// counter after it ran
+    ///
+    /// A taken jump is a return if its target was read from memory and is the return address of a
+    /// frame on the stack, which also returns from every frame inside that one. Otherwise it is a
+    /// call if the address after it is stored close to the relative base.
+    pub fn observe(
+        &mut self,
+        record: &StepRecord,
+        pc: usize,
+        memory: &Memory,
+    ) -> Option<StackChange> {
+        let return_addr = record.pc + Opcode::Jt.len();
+        let jumped = matches!(record.instruction.opcode, Opcode::Jt | Opcode::Jf);
+        if !jumped || pc == return_addr {
+            return None;
+        }
+
+        if record.instruction.modes[1] != ModeOpt::Immediate {
+            if let Some(i) = self
+                .frames
+                .iter()
+                .rposition(|frame| frame.return_addr == pc)
+            {
+                return Some(StackChange::Return(self.frames.split_off(i)));
+            }
+        }
+
+        let value = i64::try_from(return_addr).ok()?;
+        let return_slot = (-RETURN_SLOT_RANGE..=RETURN_SLOT_RANGE)
+            .filter_map(|offset| usize::try_from(record.rel.checked_add(offset)?).ok())
+            .find(|addr| memory.get(*addr) == value)?;
+
+        let frame = Frame {
+            call_site: record.pc,
+            entry: pc,
+            return_addr,
+            return_slot,
+            rel: record.rel,
+        };
+        self.frames.push(frame);
+
+        Some(StackChange::Call(frame))
+    }
+
+    /// Undoes a change returned by [`CallStack::observe`], which must be the last one not
+    /// reverted yet
+    pub fn revert(&mut self, change: StackChange) {
+        match change {
+            StackChange::Call(_) => {
+                self.frames.pop();
+            }
+            StackChange::Return(frames) => self.frames.extend(frames),
+        }
+    }
+
+    /// Where execution is in every frame, innermost first, given the program counter
+    #[must_use]
+    pub fn backtrace(&self, pc: usize) -> Vec<BacktraceEntry> {
+        let functions = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| Some(frame.entry))
+            .chain([None]);
+        let pcs = [pc]
+            .into_iter()
+            .chain(self.frames.iter().rev().map(|frame| frame.call_site));
+
+        pcs.zip(functions)
+            .map(|(pc, function)| BacktraceEntry { pc, function })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Interpreter, State};
+
+    /// Calls `outer`, which calls `inner`, the way compiled code does
+    const NESTED: &str = "
+                Rel     100
+                Add     ret_main, 0, [rel]
+        call_outer: Jt  1, outer
+        ret_main: Out   [result]
+                Halt
+        outer:  Rel     2
+                Add     ret_outer, 0, [rel]
+        call_inner: Jt  1, inner
+        ret_outer: Rel  -2
+                Jt      1, [rel]
+        inner:  Add     7, 0, [result]
+                Jt      1, [rel]
+        result: .data   0
+        ";
+
+    /// Runs a program to the end, returning the stack changes and the depth after every step
+    fn observe(source: &str) -> (Vec<StackChange>, Vec<usize>, CallStack) {
+        let mut interpreter = Interpreter::new(assemble(source).unwrap());
+        let mut stack = CallStack::default();
+        let (mut changes, mut depths) = (vec![], vec![]);
+
+        loop {
+            let (state, record) = interpreter.trace_step().unwrap();
+            if let Some(record) = record {
+                let executor = &interpreter.executor;
+                changes.extend(stack.observe(&record, executor.pc, &executor.memory));
+                depths.push(stack.depth());
+            }
+
+            if state == Some(State::Halted) {
+                return (changes, depths, stack);
+            }
+        }
+    }
+
+    #[test]
+    fn call_and_return() {
+        let (changes, _, stack) = observe(
+            "
+                    Rel     100
+                    Add     back, 0, [rel]
+                    Jt      1, function
+            back:   Halt
+            function: Jt    1, [rel]
+            ",
+        );
+
+        let frame = Frame {
+            call_site: 6,
+            entry: 10,
+            return_addr: 9,
+            return_slot: 100,
+            rel: 100,
+        };
+        assert_eq!(
+            changes,
+            [StackChange::Call(frame), StackChange::Return(vec![frame])]
+        );
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn nested_calls() {
+        let (changes, depths, stack) = observe(NESTED);
+
+        let outer = Frame {
+            call_site: 6,
+            entry: 12,
+            return_addr: 9,
+            return_slot: 100,
+            rel: 100,
+        };
+        let inner = Frame {
+            call_site: 18,
+            entry: 26,
+            return_addr: 21,
+            return_slot: 102,
+            rel: 102,
+        };
+        assert_eq!(
+            changes,
+            [
+                StackChange::Call(outer),
+                StackChange::Call(inner),
+                StackChange::Return(vec![inner]),
+                StackChange::Return(vec![outer]),
+            ]
+        );
+        assert_eq!(depths, [0, 0, 1, 1, 1, 2, 2, 1, 1, 0, 0, 0]);
+        assert!(stack.frames().is_empty());
+    }
+
+    #[test]
+    fn backtrace_and_revert() {
+        let mut interpreter = Interpreter::new(assemble(NESTED).unwrap());
+        let mut stack = CallStack::default();
+        let mut changes = vec![];
+
+        // run into `inner`
+        while interpreter.executor.pc != 26 {
+            let (_, record) = interpreter.trace_step().unwrap();
+            let executor = &interpreter.executor;
+            changes.extend(stack.observe(&record.unwrap(), executor.pc, &executor.memory));
+        }
+
+        assert_eq!(
+            stack.backtrace(26),
+            [
+                BacktraceEntry {
+                    pc: 26,
+                    function: Some(26),
+                },
+                BacktraceEntry {
+                    pc: 18,
+                    function: Some(12),
+                },
+                BacktraceEntry {
+                    pc: 6,
+                    function: None,
+                },
+            ]
+        );
+        assert_eq!(
+            stack.backtrace(26)[1].to_string(),
+            "00000012 in fn_0000000c"
+        );
+
+        for change in changes.into_iter().rev() {
+            stack.revert(change);
+        }
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn jumps_through_memory_that_are_not_returns() {
+        // the jump reads its target from memory, but no call stored it
+        let (changes, _, _) = observe(
+            "
+                    Rel     1000
+                    Jt      1, [target]
+                    Halt
+            there:  Halt
+            target: .data   there
+            ",
+        );
+        assert_eq!(changes, []);
+
+        // returning to the caller of the caller leaves both frames
+        let (changes, _, stack) = observe(
+            "
+                    Rel     100
+                    Add     back, 0, [rel]
+                    Jt      1, first
+            back:   Halt
+            first:  Rel     1
+                    Add     never, 0, [rel]
+                    Jt      1, second
+            never:  Halt
+            second: Rel     -1
+                    Jt      1, [rel]
+            ",
+        );
+        assert_eq!(changes.len(), 3);
+        let StackChange::Return(frames) = &changes[2] else {
+            panic!("expected a return, got {:?}", changes[2]);
+        };
+        assert_eq!(
+            frames.iter().map(|frame| frame.entry).collect::<Vec<_>>(),
+            [10, 20]
+        );
+        assert_eq!(stack.depth(), 0);
+    }
+}
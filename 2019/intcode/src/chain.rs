@@ -0,0 +1,469 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use debugger::{Machine, Pane, Stepped, TextView};
+
+use crate::{Executed, Instruction, IntcodeError, IntcodeMachine, Opcode, Pipeline, State};
+
+/// Which machines of a [`Chain`] a step executes an instruction of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    /// The machines take turns, skipping the ones that halted or wait for input
+    RoundRobin,
+    /// Only the focused machine
+    Focused,
+}
+
+/// An instruction executed by one of the machines of a [`Chain`]
+#[derive(Debug)]
+pub struct Routed {
+    /// Machine that executed it
+    pub machine: usize,
+    pub executed: Executed,
+    /// Machine whose turn it was before the step
+    turn: usize,
+    /// Machine the output of the instruction was queued for, and the index among the inputs of
+    /// that machine it was queued as
+    sent_to: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Routed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {}", self.machine, self.executed)
+    }
+}
+
+/// Intcode machines wired together like the stages of a [`Pipeline`], so that every output of a
+/// machine is queued as input of the next one
+///
+/// The debugger shows one machine in full, which is the focused one, and a small pane for each
+/// of the others. Breakpoints fire on whichever machine takes the next step, and watchpoints on
+/// writes and reads of any of them.
+#[derive(Debug)]
+pub struct Chain {
+    machines: Vec<IntcodeMachine>,
+    feedback: bool,
+    focus: usize,
+    mode: StepMode,
+    /// Machine that takes the next step when they take turns
+    turn: usize,
+}
+
+impl Chain {
+    /// Wires up the stages of a pipeline, which needs at least one, to take turns stepping
+    #[must_use]
+    pub fn new(pipeline: Pipeline) -> Self {
+        let feedback = pipeline.feedback();
+
+        Self {
+            machines: pipeline
+                .into_stages()
+                .into_iter()
+                .map(IntcodeMachine::new)
+                .collect(),
+            feedback,
+            focus: 0,
+            mode: StepMode::RoundRobin,
+            turn: 0,
+        }
+    }
+
+    fn focused(&self) -> &IntcodeMachine {
+        &self.machines[self.focus]
+    }
+
+    fn next_opcode(&self, index: usize) -> Option<Opcode> {
+        let executor = &self.machines[index].interpreter.executor;
+
+        Instruction::fetch(&executor.memory, executor.pc)
+            .ok()
+            .map(|instruction| instruction.opcode)
+    }
+
+    fn halted(&self, index: usize) -> bool {
+        self.next_opcode(index) == Some(Opcode::Halt)
+    }
+
+    /// Whether the machine at `index` needs input that has not been sent to it yet
+    fn waiting(&self, index: usize) -> bool {
+        self.next_opcode(index) == Some(Opcode::In)
+            && self.machines[index]
+                .interpreter
+                .executor
+                .queued_input()
+                .is_empty()
+    }
+
+    /// Machine that takes the next step, or `None` if they take turns and all of them halted or
+    /// wait for input
+    fn next_machine(&self) -> Option<usize> {
+        let len = self.machines.len();
+
+        match self.mode {
+            StepMode::Focused => Some(self.focus),
+            StepMode::RoundRobin => (0..len)
+                .map(|i| (self.turn + i) % len)
+                .find(|i| !self.halted(*i) && !self.waiting(*i)),
+        }
+    }
+
+    /// Machine the output of the machine at `index` is queued for
+    fn next_stage(&self, index: usize) -> Option<usize> {
+        if index + 1 < self.machines.len() {
+            Some(index + 1)
+        } else {
+            self.feedback.then_some(0)
+        }
+    }
+}
+
+impl Machine for Chain {
+    type Record = Routed;
+    type Error = IntcodeError;
+
+    const MNEMONICS: &'static [&'static str] = IntcodeMachine::MNEMONICS;
+
+    /// Executes an instruction of the next machine and queues what it outputs for the next stage
+    ///
+    /// Values sent to a stage that has halted are dropped, like in a [`Pipeline`]. While the
+    /// machines take turns, the chain only halts once all of them have, and only waits for input
+    /// once none of them can go on.
+    fn step(&mut self) -> Result<Stepped<Routed>, IntcodeError> {
+        let len = self.machines.len();
+
+        let Some(machine) = self.next_machine() else {
+            let state = if (0..len).any(|i| self.waiting(i)) {
+                State::NeedsInput
+            } else {
+                State::Halted
+            };
+
+            return Ok((Some(state), None));
+        };
+
+        let (state, executed) = self.machines[machine].step()?;
+        let Some(executed) = executed else {
+            return Ok((state, None));
+        };
+
+        let turn = self.turn;
+        if self.mode == StepMode::RoundRobin {
+            self.turn = (machine + 1) % len;
+        }
+
+        let mut sent_to = None;
+        if let Some(State::Output(value)) = state {
+            if let Some(next) = self.next_stage(machine).filter(|next| !self.halted(*next)) {
+                let executor = &mut self.machines[next].interpreter.executor;
+                sent_to = Some((next, executor.inputs_received()));
+                executor.push_input(value);
+            }
+        }
+
+        let routed = Routed {
+            machine,
+            executed,
+            turn,
+            sent_to,
+        };
+
+        Ok((state, Some(routed)))
+    }
+
+    /// Takes back the output the instruction sent, even if more input was queued after it
+    fn undo(&mut self, routed: &Routed) {
+        if let Some((next, index)) = routed.sent_to {
+            self.machines[next]
+                .interpreter
+                .executor
+                .unqueue_input(index);
+        }
+
+        self.machines[routed.machine].undo(&routed.executed);
+        self.turn = routed.turn;
+    }
+
+    fn pc(&self) -> usize {
+        self.focused().pc()
+    }
+
+    fn registers(&self) -> Vec<(&'static str, i64)> {
+        self.focused().registers()
+    }
+
+    fn memory_len(&self) -> usize {
+        self.focused().memory_len()
+    }
+
+    fn cell(&self, addr: usize) -> i64 {
+        self.focused().cell(addr)
+    }
+
+    fn disassemble(&self, addr: usize) -> Option<(String, usize)> {
+        self.focused().disassemble(addr)
+    }
+
+    fn opcode(&self) -> Option<&'static str> {
+        self.focused().opcode()
+    }
+
+    fn next_instruction(&self) -> (usize, Option<&'static str>) {
+        let machine = &self.machines[self.next_machine().unwrap_or(self.focus)];
+
+        (machine.pc(), machine.opcode())
+    }
+
+    fn outputs(&self) -> &[i64] {
+        self.focused().outputs()
+    }
+
+    fn listing(&self, view: TextView) -> Option<String> {
+        self.focused().listing(view)
+    }
+
+    fn inputs(&self) -> Option<(Vec<i64>, &[i64])> {
+        self.focused().inputs()
+    }
+
+    fn push_input(&mut self, value: i64) -> bool {
+        self.machines[self.focus].push_input(value)
+    }
+
+    fn reads(routed: &Routed) -> Vec<usize> {
+        IntcodeMachine::reads(&routed.executed)
+    }
+
+    fn write(routed: &Routed) -> Option<usize> {
+        IntcodeMachine::write(&routed.executed)
+    }
+
+    fn next_accesses(&self) -> (Vec<usize>, Option<usize>) {
+        self.focused().next_accesses()
+    }
+
+    fn rel(&self) -> Option<usize> {
+        self.focused().rel()
+    }
+
+    fn call_depth(&self) -> usize {
+        self.focused().call_depth()
+    }
+
+    fn backtrace(&self) -> Vec<String> {
+        self.focused().backtrace()
+    }
+
+    fn executions(&self) -> Option<&HashMap<usize, u64>> {
+        self.focused().executions()
+    }
+
+    /// Profiles every machine, so switching focus keeps showing counts
+    fn enable_profiling(&mut self) -> bool {
+        self.machines.iter_mut().all(Machine::enable_profiling)
+    }
+
+    /// Checks the focused machine when only it steps, as the state of machines taking turns
+    /// does not repeat the way loop detection looks for
+    fn fingerprint(&self) -> Option<u64> {
+        match self.mode {
+            StepMode::Focused => self.focused().fingerprint(),
+            StepMode::RoundRobin => None,
+        }
+    }
+
+    fn panes(&self) -> Vec<Pane> {
+        let next = self.next_machine();
+
+        self.machines
+            .iter()
+            .enumerate()
+            .map(|(i, machine)| {
+                let executor = &machine.interpreter.executor;
+                let status = if self.halted(i) {
+                    "halted"
+                } else if self.waiting(i) {
+                    "waiting for input"
+                } else {
+                    "running"
+                };
+                let output = machine
+                    .outputs()
+                    .last()
+                    .map_or_else(|| "-".to_string(), ToString::to_string);
+
+                Pane {
+                    title: if next == Some(i) {
+                        format!("Machine {i} (next)")
+                    } else {
+                        format!("Machine {i}")
+                    },
+                    text: format!(
+                        "PC: {:08x}\nRel: {}\nOut: {output}\n{status}",
+                        executor.pc, executor.rel
+                    ),
+                    focused: i == self.focus,
+                }
+            })
+            .collect()
+    }
+
+    fn focus(&mut self, index: usize) -> bool {
+        if index >= self.machines.len() {
+            return false;
+        }
+
+        self.focus = index;
+        true
+    }
+
+    fn toggle_step_mode(&mut self) -> Option<&'static str> {
+        let (mode, description) = match self.mode {
+            StepMode::RoundRobin => (StepMode::Focused, "Stepping the focused machine only"),
+            StepMode::Focused => (StepMode::RoundRobin, "Stepping the machines in turn"),
+        };
+        self.mode = mode;
+
+        Some(description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+
+    /// Reads a value, outputs it and halts
+    const RELAY: &str = "3,0,4,0,99";
+
+    /// Two relays, the first of which has `input` queued
+    fn relays(input: &[i64], feedback: bool) -> Chain {
+        let mut first = Interpreter::parse(RELAY).unwrap();
+        for value in input {
+            first.push_input(*value);
+        }
+        let second = Interpreter::parse(RELAY).unwrap();
+
+        let pipeline = Pipeline::new(vec![first, second]);
+        Chain::new(if feedback {
+            pipeline.with_feedback()
+        } else {
+            pipeline
+        })
+    }
+
+    fn queued(chain: &Chain, index: usize) -> Vec<i64> {
+        let queue = chain.machines[index].interpreter.executor.queued_input();
+        queue.iter().copied().collect()
+    }
+
+    /// Steps until the chain stops, returning every instruction executed
+    fn run(chain: &mut Chain) -> (State, Vec<Routed>) {
+        let mut steps = vec![];
+
+        loop {
+            let (state, routed) = chain.step().unwrap();
+            match routed {
+                Some(routed) => steps.push(routed),
+                None => return (state.unwrap(), steps),
+            }
+        }
+    }
+
+    #[test]
+    fn steps_take_turns_and_route_outputs() {
+        let mut chain = relays(&[7], false);
+
+        let (state, steps) = run(&mut chain);
+        assert_eq!(state, State::Halted);
+
+        let machines = steps
+            .iter()
+            .map(|routed| routed.machine)
+            .collect::<Vec<_>>();
+        assert_eq!(machines, [0, 0, 1, 1]);
+
+        let sent_to = steps
+            .iter()
+            .filter_map(|routed| routed.sent_to)
+            .collect::<Vec<_>>();
+        assert_eq!(sent_to, [(1, 0)]);
+        assert_eq!(chain.machines[1].outputs(), [7]);
+    }
+
+    #[test]
+    fn waits_for_input_once_no_machine_can_go_on() {
+        let mut chain = relays(&[], true);
+
+        let (state, steps) = run(&mut chain);
+        assert_eq!(state, State::NeedsInput);
+        assert!(steps.is_empty());
+
+        // input typed in goes to the focused machine
+        assert!(chain.push_input(3));
+        let (state, _) = run(&mut chain);
+        assert_eq!(state, State::Halted);
+        assert_eq!(chain.machines[1].outputs(), [3]);
+    }
+
+    #[test]
+    fn focused_machine_steps_alone() {
+        let mut chain = relays(&[7], false);
+        assert!(!chain.focus(2));
+        assert!(chain.focus(1));
+        chain.toggle_step_mode();
+
+        // the second machine has no input yet, so it waits without the first one stepping
+        assert_eq!(chain.step().unwrap().0, Some(State::NeedsInput));
+        assert_eq!(chain.machines[0].pc(), 0);
+        assert_eq!(chain.pc(), 0);
+
+        assert!(chain.focus(0));
+        assert_eq!(chain.step().unwrap().0, None);
+        assert_eq!(chain.step().unwrap().0, Some(State::Output(7)));
+        assert_eq!(queued(&chain, 1), [7]);
+        assert_eq!(chain.machines[1].pc(), 0);
+        assert_eq!(chain.pc(), 4);
+    }
+
+    #[test]
+    fn undo_takes_back_the_routed_value() {
+        let mut chain = relays(&[7], false);
+        chain.step().unwrap();
+        let (_, routed) = chain.step().unwrap();
+        let routed = routed.unwrap();
+        assert_eq!(routed.sent_to, Some((1, 0)));
+
+        // a value typed in after the routed one stays queued
+        chain.focus(1);
+        chain.push_input(8);
+        assert_eq!(queued(&chain, 1), [7, 8]);
+
+        chain.undo(&routed);
+        assert_eq!(queued(&chain, 1), [8]);
+        assert_eq!(chain.machines[0].pc(), 2);
+        assert!(chain.machines[0].outputs().is_empty());
+    }
+
+    #[test]
+    fn undoing_every_step_restores_the_machines() {
+        let mut chain = relays(&[7, 9], true);
+        chain.focus(1);
+        chain.push_input(8);
+
+        let (state, steps) = run(&mut chain);
+        assert_eq!(state, State::Halted);
+        assert_eq!(chain.machines[1].outputs(), [8]);
+
+        for routed in steps.iter().rev() {
+            chain.undo(routed);
+        }
+
+        assert_eq!(queued(&chain, 0), [7, 9]);
+        assert_eq!(queued(&chain, 1), [8]);
+        for machine in &chain.machines {
+            assert_eq!(machine.pc(), 0);
+            assert!(machine.outputs().is_empty());
+        }
+        assert_eq!(chain.turn, 0);
+    }
+}
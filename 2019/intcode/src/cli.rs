@@ -0,0 +1,401 @@
+use std::{fs, io, process::ExitCode, time::Duration};
+
+use intcode::{
+    parse_program, Arithmetic, Chain, Interpreter, Limiter, Limits, Outcome, Pipeline, Recorder,
+    Recording, RunEnd, State, Transpiler,
+};
+
+use debugger::DebuggerResult;
+
+pub const USAGE: &str = "\
+Usage: intcode <file>                   debug a program in the terminal interface
+       intcode chain <file> <phases> [--feedback] [--input <values>]
+                                        debug copies of a program chained like amplifiers,
+                                        each given one of the comma-separated phases
+       intcode asm <file>               assemble a program and print it
+       intcode run <file> [options]     run a program without the interface
+       intcode play <file>              run a text-based program interactively
+       intcode replay <recording>       replay a recorded run and check it behaves the same
+       intcode diff <recording> <recording>
+                                        find where two recorded runs diverge
+       intcode transpile <file> [--name <name>]
+                                        print a program compiled to a Rust function
+       intcode dap                      serve the Debug Adapter Protocol over stdio
+
+Options for run:
+    --input <values>        comma-separated values to queue for `In` instructions
+    --input-file <file>     file of comma- or whitespace-separated values to queue
+    --patch <addr>=<value>  write to memory before running, may be repeated
+    --arithmetic <policy>   wrapping (default), checked to fault on overflow, or big for
+                            exact big integer results
+    --budget <count>        stop after executing this many instructions
+    --timeout <seconds>     stop after running for this long
+    --detect-loops          stop when the program is stuck in a loop without I/O
+    --trace                 print every executed instruction to stderr
+    --profile               print a profile of the run to stderr when it ends
+    --record <file>         record the inputs and outputs of the run to a file
+    --record-steps          also record every step, so runs can be diffed step by step
+
+Exit codes for run:
+    0   the program halted
+    2   the program is waiting for more input
+    3   the instruction budget or the time ran out
+    4   the program faulted
+    5   the program is stuck in a loop
+
+Exit codes for replay and diff:
+    0   the runs are the same
+    6   the runs diverge";
+
+const EXIT_WAITING: u8 = 2;
+const EXIT_LIMIT: u8 = 3;
+const EXIT_FAULT: u8 = 4;
+const EXIT_LOOP: u8 = 5;
+const EXIT_DIVERGED: u8 = 6;
+
+/// Number of addresses and loops listed in a profile
+const PROFILE_TOP: usize = 20;
+
+/// Options of the `run` subcommand
+#[derive(Debug, Default)]
+struct RunArgs {
+    file: String,
+    input: Vec<i64>,
+    patches: Vec<(usize, i64)>,
+    limits: Limits,
+    arithmetic: Arithmetic,
+    trace: bool,
+    profile: bool,
+    record: Option<String>,
+    record_steps: bool,
+}
+
+impl RunArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut args = args.iter();
+        let mut run_args = Self {
+            file: args
+                .next()
+                .ok_or("Input file expected as argument")?
+                .clone(),
+            ..Self::default()
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for `{arg}`"))
+            };
+
+            match arg.as_str() {
+                "--input" => run_args.input.extend(parse_values(value()?)?),
+                "--input-file" => {
+                    let path = value()?;
+                    let contents = fs::read_to_string(path)
+                        .map_err(|e| format!("Could not read `{path}`: {e}"))?;
+                    run_args.input.extend(parse_values(&contents)?);
+                }
+                "--patch" => {
+                    for patch in value()?.split(',') {
+                        run_args.patches.push(parse_patch(patch)?);
+                    }
+                }
+                "--arithmetic" => run_args.arithmetic = value()?.parse()?,
+                "--budget" => {
+                    let budget = value()?;
+                    run_args.limits.instructions = Some(
+                        budget
+                            .parse()
+                            .map_err(|e| format!("Invalid budget `{budget}`: {e}"))?,
+                    );
+                }
+                "--timeout" => {
+                    let timeout = value()?;
+                    run_args.limits.time = Some(
+                        timeout
+                            .parse()
+                            .ok()
+                            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                            .ok_or_else(|| format!("Invalid timeout `{timeout}`"))?,
+                    );
+                }
+                "--detect-loops" => run_args.limits.detect_loops = true,
+                "--trace" => run_args.trace = true,
+                "--profile" => run_args.profile = true,
+                "--record" => run_args.record = Some(value()?.clone()),
+                "--record-steps" => run_args.record_steps = true,
+                _ => return Err(format!("Unknown option `{arg}`")),
+            }
+        }
+
+        Ok(run_args)
+    }
+}
+
+fn parse_values(s: &str) -> Result<Vec<i64>, String> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse::<i64>()
+                .map_err(|e| format!("Invalid input value `{value}`: {e}"))
+        })
+        .collect()
+}
+
+fn parse_patch(s: &str) -> Result<(usize, i64), String> {
+    let (addr, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("Patch `{s}` is not of the form <addr>=<value>"))?;
+
+    let addr = addr
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid patch address `{addr}`: {e}"))?;
+    let value = value
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid patch value `{value}`: {e}"))?;
+
+    Ok((addr, value))
+}
+
+/// Runs a program headlessly, printing every output on its own line
+pub fn run(args: &[String]) -> DebuggerResult<ExitCode> {
+    let args = match RunArgs::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let mut interpreter =
+        Interpreter::parse(&fs::read_to_string(&args.file)?)?.with_arithmetic(args.arithmetic);
+    for (addr, value) in args.patches {
+        if interpreter.set_memory(addr, value).is_none() {
+            eprintln!("Patching {addr:#x} went over the memory limit");
+            return Ok(ExitCode::from(EXIT_FAULT));
+        }
+    }
+    for value in args.input {
+        interpreter.push_input(value);
+    }
+    if args.profile {
+        interpreter = interpreter.with_profiling();
+    }
+
+    let mut recorder = args
+        .record
+        .as_ref()
+        .map(|_| Recorder::new(&interpreter, args.record_steps));
+
+    let mut limiter = Limiter::new(args.limits);
+    let (code, end) = loop {
+        let pc = interpreter.executor.pc;
+        match limiter.check(&interpreter.executor) {
+            None | Some(Outcome::State(_)) => {}
+            Some(Outcome::BudgetExhausted) => {
+                eprintln!(
+                    "Budget of {} instructions ran out at {pc:#x}",
+                    limiter.executed()
+                );
+                break (ExitCode::from(EXIT_LIMIT), RunEnd::Stopped);
+            }
+            Some(Outcome::TimedOut) => {
+                eprintln!(
+                    "Timed out at {pc:#x} after {} instructions",
+                    limiter.executed()
+                );
+                break (ExitCode::from(EXIT_LIMIT), RunEnd::Stopped);
+            }
+            Some(Outcome::Looping { pc, period }) => {
+                eprintln!("Stuck in a loop of {period} instructions at {pc:#x}");
+                break (ExitCode::from(EXIT_LOOP), RunEnd::Stopped);
+            }
+        }
+
+        let step = match &mut recorder {
+            Some(recorder) => recorder.step(&mut interpreter),
+            None if args.trace => interpreter.trace_step(),
+            None => interpreter.step().map(|state| (state, None)),
+        };
+        let step = step.map(|(state, record)| {
+            if let Some(record) = record.filter(|_| args.trace) {
+                eprintln!("{record}");
+            }
+            limiter.count(state);
+            state
+        });
+
+        match step {
+            Ok(None) => {}
+            Ok(Some(State::Output(value))) => println!("{value}"),
+            Ok(Some(State::NeedsInput)) => {
+                eprintln!("Waiting for input at {:#x}", interpreter.executor.pc);
+                break (ExitCode::from(EXIT_WAITING), RunEnd::NeedsInput);
+            }
+            Ok(Some(State::Halted)) => break (ExitCode::SUCCESS, RunEnd::Halted),
+            Err(e) => {
+                eprintln!("{e}");
+                break (ExitCode::from(EXIT_FAULT), RunEnd::Fault(e.to_string()));
+            }
+        }
+    };
+
+    if let Some(profile) = interpreter.profile() {
+        // only executed addresses that static disassembly misses are hints, since every hint
+        // starts a new block
+        let reached = interpreter.disassemble([]);
+        let hints = profile
+            .executions
+            .keys()
+            .copied()
+            .filter(|addr| !reached.is_code(*addr));
+
+        let disassembly = interpreter.disassemble(hints);
+        eprintln!("{}", profile.report(&disassembly, PROFILE_TOP));
+    }
+
+    if let (Some(recorder), Some(file)) = (recorder, &args.record) {
+        fs::write(file, recorder.finish(&interpreter, end).to_bytes())?;
+    }
+
+    Ok(code)
+}
+
+/// Replays a recorded run and compares it to the recording
+pub fn replay(args: &[String]) -> DebuggerResult<ExitCode> {
+    let [file] = args else {
+        eprintln!("Recording expected as argument\n\n{USAGE}");
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let recording = Recording::from_bytes(&fs::read(file)?)?;
+    let replay = recording.replay();
+    println!(
+        "Replayed {} instructions with {} inputs and {} outputs, {}",
+        replay.executed,
+        replay.inputs.len(),
+        replay.outputs.len(),
+        replay.end
+    );
+
+    match recording.diff(&replay) {
+        Some(divergence) => {
+            print!("{divergence}");
+            Ok(ExitCode::from(EXIT_DIVERGED))
+        }
+        None => Ok(ExitCode::SUCCESS),
+    }
+}
+
+/// Finds the first point at which two recorded runs differ
+pub fn diff(args: &[String]) -> DebuggerResult<ExitCode> {
+    let [left, right] = args else {
+        eprintln!("Two recordings expected as arguments\n\n{USAGE}");
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let left = Recording::from_bytes(&fs::read(left)?)?;
+    let right = Recording::from_bytes(&fs::read(right)?)?;
+
+    match left.diff(&right) {
+        Some(divergence) => {
+            print!("{divergence}");
+            Ok(ExitCode::from(EXIT_DIVERGED))
+        }
+        None => {
+            println!("Runs are the same");
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Prints the source of a Rust function that runs a program, see [`Transpiler`]
+pub fn transpile(args: &[String]) -> DebuggerResult<ExitCode> {
+    let (file, name) = match args {
+        [file] => (file, "run"),
+        [file, flag, name] if flag == "--name" => (file, name.as_str()),
+        _ => {
+            eprintln!("Input file expected as argument\n\n{USAGE}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let program = parse_program(&fs::read_to_string(file)?)?;
+    print!("{}", Transpiler::new(&program).with_name(name).transpile());
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Debugs copies of a program in the terminal interface, wired together so that each one gets
+/// the output of the one before, see [`Chain`]
+pub async fn chain(args: &[String]) -> DebuggerResult<ExitCode> {
+    let [file, phases, options @ ..] = args else {
+        eprintln!("Input file and phases expected as arguments\n\n{USAGE}");
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let phases = parse_values(phases)?;
+    if phases.is_empty() {
+        return Err("At least one phase expected")?;
+    }
+
+    let mut feedback = false;
+    let mut input = vec![];
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--feedback" => feedback = true,
+            "--input" => {
+                let values = options.next().ok_or("Missing value for `--input`")?;
+                input.extend(parse_values(values)?);
+            }
+            _ => return Err(format!("Unknown option `{option}`"))?,
+        }
+    }
+
+    let program = parse_program(&fs::read_to_string(file)?)?;
+    let stages = phases
+        .iter()
+        .enumerate()
+        .map(|(i, phase)| {
+            let mut stage = Interpreter::new(program.clone());
+            stage.push_input(*phase);
+            if i == 0 {
+                for value in &input {
+                    stage.push_input(*value);
+                }
+            }
+
+            stage
+        })
+        .collect();
+
+    let pipeline = Pipeline::new(stages);
+    let pipeline = if feedback {
+        pipeline.with_feedback()
+    } else {
+        pipeline
+    };
+    debugger::debug(Chain::new(pipeline)).await?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Plays a program that talks in ASCII at the terminal, sending every line typed to it until it
+/// halts or the input ends
+pub fn play(args: &[String]) -> DebuggerResult<ExitCode> {
+    let Some(file) = args.first() else {
+        eprintln!("Input file expected as argument\n\n{USAGE}");
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let mut interpreter = Interpreter::parse(&fs::read_to_string(file)?)?;
+
+    match interpreter.play(io::stdin().lock(), io::stdout())? {
+        State::Halted => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::from(EXIT_WAITING)),
+    }
+}
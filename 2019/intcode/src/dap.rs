@@ -0,0 +1,897 @@
+//! Debug Adapter Protocol server, for debugging programs from an editor
+//!
+//! Messages are read from stdin and written to stdout, each preceded by a `Content-Length`
+//! header. There is a single thread, and stack frames are the calls inferred by [`CallStack`].
+//! Programs have no source, so breakpoints are set by address: the first line of a source
+//! breakpoint stands for address 0 and so on, stack frames report their line the same way, and
+//! instruction breakpoints take an address as their reference. Expressions evaluated in the REPL
+//! are queued as input, either as integers or as a line of ASCII text.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, BufRead, Read, Write},
+    process::ExitCode,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use machine::parse_address;
+use serde_json::{json, Value};
+
+use crate::{
+    callstack::CallStack, executor::State, instruction::Instruction, interpreter::Interpreter,
+};
+
+/// Instructions executed between checks for new messages while running
+const STEPS_PER_POLL: usize = 10_000;
+
+/// Only thread of every program
+const THREAD_ID: i64 = 1;
+
+/// Cells shown before and after the address a memory window is centred on
+const WINDOW_BEFORE: usize = 8;
+const WINDOW_AFTER: usize = 24;
+
+/// Variable references are a frame id times this plus one of the scopes below
+const SCOPES_PER_FRAME: i64 = 4;
+const SCOPE_REGISTERS: i64 = 1;
+const SCOPE_FRAME: i64 = 2;
+const SCOPE_CODE: i64 = 3;
+
+/// How a run that has been started goes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Run {
+    /// Until a breakpoint
+    Continue,
+    /// Until a breakpoint or until the call stack is no deeper than this
+    Until(usize),
+}
+
+/// Why a run stopped, as reported in a `stopped` event
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Stop {
+    Entry,
+    Step,
+    Breakpoint,
+    Pause,
+    Input,
+    Exception(String),
+    Halted,
+}
+
+/// Program being debugged
+#[derive(Debug)]
+struct Target {
+    interpreter: Interpreter,
+    call_stack: CallStack,
+    stop_on_entry: bool,
+    run: Option<Run>,
+    halted: bool,
+    fault: Option<String>,
+}
+
+/// Sends messages to the client
+#[derive(Debug)]
+struct Client<W> {
+    out: W,
+    seq: i64,
+}
+
+/// Addresses to stop at, kept apart by the request that set them since each request replaces
+/// only its own
+#[derive(Debug)]
+struct Breakpoints {
+    source: BTreeSet<usize>,
+    instruction: BTreeSet<usize>,
+}
+
+impl Breakpoints {
+    fn contains(&self, addr: usize) -> bool {
+        self.source.contains(&addr) || self.instruction.contains(&addr)
+    }
+}
+
+#[derive(Debug)]
+struct Session<W> {
+    client: Client<W>,
+    target: Option<Target>,
+    breakpoints: Breakpoints,
+    /// Line the client numbers first, which stands for address 0
+    first_line: usize,
+    configured: bool,
+}
+
+/// Reads messages from `input` on another thread, so a running program can be paused
+fn spawn_reader(input: impl Read + Send + 'static) -> Receiver<Value> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut input = io::BufReader::new(input);
+        while let Some(message) = read_message(&mut input) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+/// Reads a single message, or `None` once the input ends or stops making sense
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut len = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let mut body = vec![0; len?];
+    input.read_exact(&mut body).ok()?;
+
+    serde_json::from_slice(&body).ok()
+}
+
+/// Values to queue from text, which holds integers separated by commas or whitespace or else a
+/// line of ASCII text
+fn parse_input(text: &str) -> Vec<i64> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(str::parse::<i64>)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|_| text.bytes().chain([b'\n']).map(i64::from).collect())
+}
+
+impl<W: Write> Client<W> {
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        let body = message.to_string();
+        write!(self.out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.out.flush()
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        self.send(response)
+    }
+
+    /// Tells the client the program stopped, or that the session is over if it halted
+    fn stopped(&mut self, stop: Stop) -> io::Result<()> {
+        let (reason, description) = match stop {
+            Stop::Halted => {
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                return self.event("terminated", json!({}));
+            }
+            Stop::Entry => ("entry", None),
+            Stop::Step => ("step", None),
+            Stop::Breakpoint => ("breakpoint", None),
+            Stop::Pause => ("pause", None),
+            Stop::Input => ("pause", Some("Waiting for input".to_string())),
+            Stop::Exception(e) => ("exception", Some(e)),
+        };
+
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(description) = description {
+            body["description"] = json!(description);
+            body["text"] = body["description"].clone();
+        }
+
+        self.event("stopped", body)
+    }
+}
+
+impl Target {
+    /// Executes a single instruction, sending what it output, and returns why the run has to
+    /// stop if it does
+    fn step(&mut self, client: &mut Client<impl Write>) -> io::Result<Option<Stop>> {
+        if self.halted {
+            return Ok(Some(Stop::Halted));
+        }
+        if let Some(e) = &self.fault {
+            return Ok(Some(Stop::Exception(e.clone())));
+        }
+
+        let (state, record) = match self.interpreter.trace_step() {
+            Ok(step) => step,
+            Err(e) => {
+                self.fault = Some(e.to_string());
+                return Ok(Some(Stop::Exception(e.to_string())));
+            }
+        };
+
+        if let Some(record) = &record {
+            self.call_stack.observe(
+                record,
+                self.interpreter.executor.pc,
+                &self.interpreter.executor.memory,
+            );
+        }
+
+        match state {
+            Some(State::Output(value)) => {
+                let body = json!({ "category": "stdout", "output": format!("{value}\n") });
+                client.event("output", body)?;
+                Ok(None)
+            }
+            Some(State::NeedsInput) => Ok(Some(Stop::Input)),
+            Some(State::Halted) => {
+                self.halted = true;
+                Ok(Some(Stop::Halted))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a run stepping over or out of a call has got back out of it
+    fn returned(&self) -> bool {
+        match self.run {
+            Some(Run::Until(depth)) => self.call_stack.depth() <= depth,
+            _ => false,
+        }
+    }
+
+    /// Executes up to `steps` instructions of the run that is going, stopping at breakpoints
+    fn run_for(
+        &mut self,
+        client: &mut Client<impl Write>,
+        breakpoints: &Breakpoints,
+        steps: usize,
+    ) -> io::Result<()> {
+        for _ in 0..steps {
+            if self.run.is_none() {
+                break;
+            }
+
+            let stop = if breakpoints.contains(self.interpreter.executor.pc) {
+                Some(Stop::Breakpoint)
+            } else {
+                self.step(client)?
+            };
+
+            if let Some(stop) = stop.or_else(|| self.returned().then_some(Stop::Step)) {
+                self.run = None;
+                client.stopped(stop)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts a run, executing the instruction at the program counter even if it has a
+    /// breakpoint, since that is where the last run stopped
+    fn start(&mut self, client: &mut Client<impl Write>, run: Run) -> io::Result<()> {
+        self.run = Some(run);
+
+        if let Some(stop) = self
+            .step(client)?
+            .or_else(|| self.returned().then_some(Stop::Step))
+        {
+            self.run = None;
+            client.stopped(stop)?;
+        }
+
+        Ok(())
+    }
+
+    /// Program counter, relative base and function of every frame, innermost first
+    fn frames(&self) -> Vec<(usize, isize, Option<usize>)> {
+        let executor = &self.interpreter.executor;
+        let rels = [executor.rel]
+            .into_iter()
+            .chain(self.call_stack.frames().iter().rev().map(|frame| frame.rel));
+
+        self.call_stack
+            .backtrace(executor.pc)
+            .into_iter()
+            .zip(rels)
+            .map(|(entry, rel)| (entry.pc, rel, entry.function))
+            .collect()
+    }
+}
+
+impl<W: Write> Session<W> {
+    const fn new(out: W) -> Self {
+        Self {
+            client: Client { out, seq: 0 },
+            target: None,
+            breakpoints: Breakpoints {
+                source: BTreeSet::new(),
+                instruction: BTreeSet::new(),
+            },
+            first_line: 1,
+            configured: false,
+        }
+    }
+
+    fn running(&self) -> bool {
+        self.target
+            .as_ref()
+            .is_some_and(|target| target.run.is_some())
+    }
+
+    /// Begins executing once the program has been launched and the client is done configuring
+    fn begin(&mut self) -> io::Result<()> {
+        let Some(target) = self.target.as_mut().filter(|_| self.configured) else {
+            return Ok(());
+        };
+
+        if target.stop_on_entry {
+            self.client.stopped(Stop::Entry)
+        } else {
+            target.run = Some(Run::Continue);
+            Ok(())
+        }
+    }
+
+    /// Handles a request, returning `false` once the client disconnects
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+
+        match command {
+            "initialize" => {
+                self.first_line = usize::from(arguments["linesStartAt1"].as_bool() != Some(false));
+
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsInstructionBreakpoints": true,
+                });
+                self.client.respond(request, Ok(capabilities))?;
+                self.client.event("initialized", json!({}))?;
+            }
+            "launch" => {
+                let result = self.launch(arguments);
+                let launched = result.is_ok();
+                self.client.respond(request, result)?;
+                if launched {
+                    self.begin()?;
+                }
+            }
+            "configurationDone" => {
+                self.configured = true;
+                self.client.respond(request, Ok(json!({})))?;
+                self.begin()?;
+            }
+            "setBreakpoints" => {
+                let first_line = self.first_line;
+                let address = |breakpoint: &Value| {
+                    usize::try_from(breakpoint["line"].as_u64()?)
+                        .ok()?
+                        .checked_sub(first_line)
+                };
+                let body = set_breakpoints(&mut self.breakpoints.source, arguments, address);
+                self.client.respond(request, Ok(body))?;
+            }
+            "setInstructionBreakpoints" => {
+                let body =
+                    set_breakpoints(&mut self.breakpoints.instruction, arguments, |breakpoint| {
+                        let reference = parse_address(breakpoint["instructionReference"].as_str()?);
+                        let offset = breakpoint["offset"].as_i64().unwrap_or_default();
+                        let addr = i64::try_from(reference.ok()?).ok()?.checked_add(offset)?;
+                        usize::try_from(addr).ok()
+                    });
+                self.client.respond(request, Ok(body))?;
+            }
+            "threads" => {
+                let threads = json!({ "threads": [{ "id": THREAD_ID, "name": "intcode" }] });
+                self.client.respond(request, Ok(threads))?;
+            }
+            "continue" | "next" | "stepIn" | "stepOut" => self.resume(request, command)?,
+            "pause" => {
+                self.client.respond(request, Ok(json!({})))?;
+                if let Some(target) = self.target.as_mut().filter(|target| target.run.is_some()) {
+                    target.run = None;
+                    self.client.stopped(Stop::Pause)?;
+                }
+            }
+            "stackTrace" => {
+                let result = self.stack_trace();
+                self.client.respond(request, result)?;
+            }
+            "scopes" => {
+                let frame = arguments["frameId"].as_i64().unwrap_or_default();
+                let scope = |name: &str, kind: i64| {
+                    json!({
+                        "name": name,
+                        "variablesReference": frame * SCOPES_PER_FRAME + kind,
+                        "expensive": false,
+                    })
+                };
+                let scopes = json!({
+                    "scopes": [
+                        scope("Registers", SCOPE_REGISTERS),
+                        scope("Frame", SCOPE_FRAME),
+                        scope("Code", SCOPE_CODE),
+                    ]
+                });
+                self.client.respond(request, Ok(scopes))?;
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_i64().unwrap_or_default();
+                let result = self.variables(reference);
+                self.client.respond(request, result)?;
+            }
+            "evaluate" => {
+                let result = self.evaluate(arguments);
+                self.client.respond(request, result)?;
+            }
+            "disconnect" | "terminate" => {
+                self.client.respond(request, Ok(json!({})))?;
+                return Ok(false);
+            }
+            _ => {
+                let message = format!("Unsupported request `{command}`");
+                self.client.respond(request, Err(message))?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let file = arguments["program"]
+            .as_str()
+            .ok_or("Missing `program` to launch")?;
+        let text = fs::read_to_string(file).map_err(|e| format!("Could not read `{file}`: {e}"))?;
+        let mut interpreter = Interpreter::parse(&text).map_err(|e| e.to_string())?;
+
+        let input = match &arguments["input"] {
+            Value::Null => vec![],
+            Value::String(text) => parse_input(text),
+            Value::Array(values) => values
+                .iter()
+                .map(|value| {
+                    value
+                        .as_i64()
+                        .ok_or(format!("Invalid input value `{value}`"))
+                })
+                .collect::<Result<_, _>>()?,
+            value => return Err(format!("Invalid input `{value}`")),
+        };
+        for value in input {
+            interpreter.push_input(value);
+        }
+
+        self.target = Some(Target {
+            interpreter,
+            call_stack: CallStack::default(),
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or_default(),
+            run: None,
+            halted: false,
+            fault: None,
+        });
+
+        Ok(json!({}))
+    }
+
+    /// Continues or steps
+    ///
+    /// `next` steps over calls, so it keeps running if the instruction made one, and `stepOut`
+    /// runs until the innermost call returns.
+    fn resume(&mut self, request: &Value, command: &str) -> io::Result<()> {
+        let Some(target) = &mut self.target else {
+            return self
+                .client
+                .respond(request, Err("No program launched".to_string()));
+        };
+
+        let depth = target.call_stack.depth();
+        let run = match command {
+            "continue" => Run::Continue,
+            "stepOut" if depth == 0 => {
+                return self
+                    .client
+                    .respond(request, Err("Not inside a call".to_string()));
+            }
+            "stepOut" => Run::Until(depth - 1),
+            _ => Run::Until(depth),
+        };
+
+        let body = match command {
+            "continue" => json!({ "allThreadsContinued": true }),
+            _ => json!({}),
+        };
+        self.client.respond(request, Ok(body))?;
+
+        target.start(&mut self.client, run)?;
+
+        // stepping in stops after one instruction even if it made a call
+        if command == "stepIn" && target.run.is_some() {
+            target.run = None;
+            self.client.stopped(Stop::Step)?;
+        }
+
+        Ok(())
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let target = self.target.as_ref().ok_or("No program launched")?;
+
+        let frames = target
+            .frames()
+            .into_iter()
+            .enumerate()
+            .map(|(id, (pc, _, function))| {
+                let name = match function {
+                    Some(function) => format!("fn_{function:08x} at {pc:08x}"),
+                    None => format!("<start> at {pc:08x}"),
+                };
+
+                json!({
+                    "id": id,
+                    "name": name,
+                    "line": pc + self.first_line,
+                    "column": 0,
+                    "instructionPointerReference": format!("{pc:#x}"),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+    }
+
+    fn variables(&self, reference: i64) -> Result<Value, String> {
+        let target = self.target.as_ref().ok_or("No program launched")?;
+        let frames = target.frames();
+        let (pc, rel, _) = usize::try_from(reference / SCOPES_PER_FRAME)
+            .ok()
+            .and_then(|frame| frames.get(frame).copied())
+            .ok_or_else(|| format!("No variables `{reference}`"))?;
+
+        let executor = &target.interpreter.executor;
+        let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+        let window = |center: usize, name: &dyn Fn(usize) -> String| {
+            (center.saturating_sub(WINDOW_BEFORE)..center + WINDOW_AFTER)
+                .map(|addr| variable(name(addr), executor.memory.get(addr).to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let variables = match reference % SCOPES_PER_FRAME {
+            SCOPE_REGISTERS => {
+                let instruction = Instruction::fetch(&executor.memory, pc)
+                    .map_or_else(|e| e.to_string(), |instruction| instruction.to_string());
+
+                vec![
+                    variable("pc".to_string(), format!("{pc:#x} ({pc})")),
+                    variable("rel".to_string(), format!("{rel:#x} ({rel})")),
+                    variable("instruction".to_string(), instruction.replace('\t', " ")),
+                    variable(
+                        "input".to_string(),
+                        format!("{:?}", executor.queued_input()),
+                    ),
+                    variable(
+                        "output".to_string(),
+                        format!("{:?}", executor.output_history()),
+                    ),
+                ]
+            }
+            SCOPE_FRAME => match usize::try_from(rel) {
+                #[allow(clippy::cast_possible_wrap)]
+                Ok(base) => window(base, &|addr| {
+                    format!("rel{:+} [{addr:#x}]", addr as isize - rel)
+                }),
+                Err(_) => vec![],
+            },
+            SCOPE_CODE => window(pc, &|addr| format!("[{addr:#x}]")),
+            _ => return Err(format!("No variables `{reference}`")),
+        };
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Queues the expression as input
+    fn evaluate(&mut self, arguments: &Value) -> Result<Value, String> {
+        let target = self.target.as_mut().ok_or("No program launched")?;
+        let expression = arguments["expression"].as_str().unwrap_or_default();
+
+        let values = parse_input(expression);
+        for value in &values {
+            target.interpreter.push_input(*value);
+        }
+
+        Ok(json!({
+            "result": format!("Queued {values:?}"),
+            "variablesReference": 0,
+        }))
+    }
+}
+
+/// Replaces a set of breakpoints with the addresses of the breakpoints in the arguments
+fn set_breakpoints(
+    breakpoints: &mut BTreeSet<usize>,
+    arguments: &Value,
+    address: impl Fn(&Value) -> Option<usize>,
+) -> Value {
+    breakpoints.clear();
+
+    let results = arguments["breakpoints"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|breakpoint| match address(breakpoint) {
+            Some(addr) => {
+                breakpoints.insert(addr);
+                json!({ "verified": true, "instructionReference": format!("{addr:#x}") })
+            }
+            None => json!({ "verified": false, "message": "Not an address" }),
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "breakpoints": results })
+}
+
+/// Serves a single debugging session over stdin and stdout
+pub fn serve_dap() -> io::Result<ExitCode> {
+    serve(io::stdin(), io::stdout().lock())?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Serves a single debugging session, until the client disconnects or `input` ends
+fn serve(input: impl Read + Send + 'static, out: impl Write) -> io::Result<()> {
+    let messages = spawn_reader(input);
+    let mut session = Session::new(out);
+
+    loop {
+        let message = if session.running() {
+            match messages.try_recv() {
+                Ok(message) => Some(message),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        } else {
+            match messages.recv() {
+                Ok(message) => Some(message),
+                Err(_) => break,
+            }
+        };
+
+        if let Some(message) = message {
+            if message["type"] == "request" && !session.handle(&message)? {
+                break;
+            }
+        }
+
+        if let Some(target) = &mut session.target {
+            target.run_for(&mut session.client, &session.breakpoints, STEPS_PER_POLL)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::Cursor, iter, process};
+
+    use super::*;
+
+    /// Adds 0 and 0, outputs 1 and 2 and halts, with the outputs at addresses 4 and 6
+    const PROGRAM: &str = "1101,0,0,20,104,1,104,2,99";
+
+    /// Writes the program to a file of its own for the test called `name` to launch
+    fn program_file(name: &str) -> String {
+        let path = env::temp_dir().join(format!("intcode-dap-{}-{name}.txt", process::id()));
+        fs::write(&path, PROGRAM).unwrap();
+
+        path.to_string_lossy().into_owned()
+    }
+
+    fn request(command: &str, arguments: Value) -> Value {
+        json!({ "type": "request", "command": command, "arguments": arguments })
+    }
+
+    /// Serves a session that gets the requests, framed like a client would send them, and
+    /// returns every message sent back
+    fn session(requests: &[Value]) -> Vec<Value> {
+        let mut input = vec![];
+        for (seq, request) in requests.iter().enumerate() {
+            let mut request = request.clone();
+            request["seq"] = json!(seq + 1);
+
+            let body = request.to_string();
+            write!(input, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        }
+
+        let mut out = vec![];
+        serve(Cursor::new(input), &mut out).unwrap();
+
+        let mut out = out.as_slice();
+        iter::from_fn(|| read_message(&mut out)).collect()
+    }
+
+    fn responses<'a>(messages: &'a [Value], command: &'a str) -> impl Iterator<Item = &'a Value> {
+        messages
+            .iter()
+            .filter(move |message| message["type"] == "response" && message["command"] == command)
+    }
+
+    fn events<'a>(messages: &'a [Value], event: &'a str) -> impl Iterator<Item = &'a Value> {
+        messages
+            .iter()
+            .filter(move |message| message["type"] == "event" && message["event"] == event)
+    }
+
+    fn stop_reasons(messages: &[Value]) -> Vec<&str> {
+        events(messages, "stopped")
+            .map(|event| event["body"]["reason"].as_str().unwrap())
+            .collect()
+    }
+
+    /// Innermost frame of every stack trace, as its line and instruction pointer
+    fn top_frames(messages: &[Value]) -> Vec<(u64, &str)> {
+        responses(messages, "stackTrace")
+            .map(|response| {
+                let frame = &response["body"]["stackFrames"][0];
+                (
+                    frame["line"].as_u64().unwrap(),
+                    frame["instructionPointerReference"].as_str().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn outputs(messages: &[Value]) -> Vec<&str> {
+        events(messages, "output")
+            .map(|event| event["body"]["output"].as_str().unwrap())
+            .collect()
+    }
+
+    fn source_breakpoints(lines: &[u64]) -> Value {
+        let breakpoints = lines
+            .iter()
+            .map(|line| json!({ "line": line }))
+            .collect::<Vec<_>>();
+
+        request(
+            "setBreakpoints",
+            json!({ "source": { "path": "program.txt" }, "breakpoints": breakpoints }),
+        )
+    }
+
+    fn instruction_breakpoints(references: &[&str]) -> Value {
+        let breakpoints = references
+            .iter()
+            .map(|reference| json!({ "instructionReference": reference }))
+            .collect::<Vec<_>>();
+
+        request(
+            "setInstructionBreakpoints",
+            json!({ "breakpoints": breakpoints }),
+        )
+    }
+
+    #[test]
+    fn breakpoints_of_both_kinds() {
+        let program = program_file("both-kinds");
+        let source = source_breakpoints(&[5]);
+        let instruction = instruction_breakpoints(&["0x6"]);
+
+        // editors send both requests on launch, in either order
+        for (first, second) in [(&source, &instruction), (&instruction, &source)] {
+            let messages = session(&[
+                request("initialize", json!({ "adapterID": "intcode" })),
+                request("launch", json!({ "program": program })),
+                first.clone(),
+                second.clone(),
+                request("configurationDone", json!({})),
+                request("stackTrace", json!({ "threadId": THREAD_ID })),
+                request("continue", json!({ "threadId": THREAD_ID })),
+                request("stackTrace", json!({ "threadId": THREAD_ID })),
+                request("continue", json!({ "threadId": THREAD_ID })),
+                request("disconnect", json!({})),
+            ]);
+
+            assert!(messages
+                .iter()
+                .filter(|message| message["type"] == "response")
+                .all(|response| response["success"] == true));
+            assert_eq!(stop_reasons(&messages), ["breakpoint", "breakpoint"]);
+            assert_eq!(top_frames(&messages), [(5, "0x4"), (7, "0x6")]);
+            assert_eq!(outputs(&messages), ["1\n", "2\n"]);
+            assert_eq!(events(&messages, "terminated").count(), 1);
+        }
+    }
+
+    #[test]
+    fn breakpoints_replace_only_their_own_kind() {
+        let program = program_file("replace");
+        let messages = session(&[
+            request("initialize", json!({})),
+            request("launch", json!({ "program": program })),
+            source_breakpoints(&[5]),
+            instruction_breakpoints(&["0x6"]),
+            source_breakpoints(&[]),
+            request("configurationDone", json!({})),
+            request("stackTrace", json!({ "threadId": THREAD_ID })),
+            request("continue", json!({ "threadId": THREAD_ID })),
+            request("disconnect", json!({})),
+        ]);
+
+        assert_eq!(stop_reasons(&messages), ["breakpoint"]);
+        assert_eq!(top_frames(&messages), [(7, "0x6")]);
+        assert_eq!(events(&messages, "terminated").count(), 1);
+    }
+
+    #[test]
+    fn lines_starting_at_0() {
+        let program = program_file("lines-at-0");
+        let messages = session(&[
+            request("initialize", json!({ "linesStartAt1": false })),
+            request("launch", json!({ "program": program })),
+            source_breakpoints(&[6]),
+            request("configurationDone", json!({})),
+            request("stackTrace", json!({ "threadId": THREAD_ID })),
+            request("disconnect", json!({})),
+        ]);
+
+        assert_eq!(top_frames(&messages), [(6, "0x6")]);
+    }
+
+    #[test]
+    fn stepping_from_entry() {
+        let program = program_file("entry");
+        let messages = session(&[
+            request("initialize", json!({})),
+            request("launch", json!({ "program": program, "stopOnEntry": true })),
+            request("configurationDone", json!({})),
+            request("stackTrace", json!({ "threadId": THREAD_ID })),
+            request("next", json!({ "threadId": THREAD_ID })),
+            request("stackTrace", json!({ "threadId": THREAD_ID })),
+            request("disconnect", json!({})),
+        ]);
+
+        assert_eq!(stop_reasons(&messages), ["entry", "step"]);
+        assert_eq!(top_frames(&messages), [(1, "0x0"), (5, "0x4")]);
+        assert!(outputs(&messages).is_empty());
+    }
+
+    #[test]
+    fn launch_without_program() {
+        let messages = session(&[
+            request("initialize", json!({})),
+            request("launch", json!({})),
+            request("disconnect", json!({})),
+        ]);
+
+        let launch = responses(&messages, "launch").next().unwrap();
+        assert_eq!(launch["success"], false);
+        assert_eq!(launch["message"], "Missing `program` to launch");
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{
+    error::IntcodeError,
+    memory::Memory,
+    opcode::{ModeOpt, Opcode},
+};
+
+/// Opcode and parameter modes of a single instruction, worked out arithmetically from the value at
+/// its address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded {
+    pub opcode: Opcode,
+    pub modes: [ModeOpt; 3],
+}
+
+impl Decoded {
+    pub fn new(op: i64, addr: usize) -> Result<Self, IntcodeError> {
+        if !(0..100_000).contains(&op) {
+            return Err(IntcodeError::InvalidOpcode { addr, value: op });
+        }
+
+        let opcode = u8::try_from(op % 100)
+            .ok()
+            .and_then(|code| Opcode::try_from(code).ok())
+            .ok_or(IntcodeError::InvalidOpcode { addr, value: op })?;
+
+        let mode = |place: i64| {
+            u32::try_from(op / place % 10)
+                .ok()
+                .and_then(|digit| ModeOpt::try_from(digit).ok())
+                .ok_or(IntcodeError::InvalidMode { addr, value: op })
+        };
+        let modes = [mode(100)?, mode(1_000)?, mode(10_000)?];
+
+        Ok(Self { opcode, modes })
+    }
+}
+
+/// Decoded instructions keyed by address
+///
+/// Entries are filled in the first time an address is executed and must be invalidated whenever
+/// the cell at that address is written to, so that self-modifying programs are decoded again.
+#[derive(Debug, Default, Clone)]
+pub struct DecodeCache {
+    entries: Vec<Option<Decoded>>,
+    /// Whether every instruction is decoded afresh, to measure what the cache saves
+    disabled: bool,
+}
+
+impl DecodeCache {
+    /// Decodes the instruction at `addr`. Only instructions in dense memory are cached.
+    pub fn get(&mut self, memory: &Memory, addr: usize) -> Result<Decoded, IntcodeError> {
+        if let Some(Some(decoded)) = self.entries.get(addr) {
+            return Ok(*decoded);
+        }
+
+        if addr >= memory.len() {
+            return Err(IntcodeError::EndOfMemory { pc: addr });
+        }
+
+        let decoded = Decoded::new(memory[addr], addr)?;
+        if self.disabled || addr >= memory.dense().len() {
+            return Ok(decoded);
+        }
+
+        if addr >= self.entries.len() {
+            self.entries.resize(addr + 1, None);
+        }
+        self.entries[addr] = Some(decoded);
+
+        Ok(decoded)
+    }
+
+    pub fn invalidate(&mut self, addr: usize) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            *entry = None;
+        }
+    }
+
+    /// Stops caching, so every instruction is decoded each time it is executed
+    pub fn disable(&mut self) {
+        self.disabled = true;
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,429 @@
+//! Control-flow-aware disassembly
+//!
+//! Instead of sweeping memory linearly, the [`Disassembler`] starts at address 0 and follows every
+//! path control can take from there. Cells that are never reached are shown as data, so data mixed
+//! in with code no longer throws the listing out of sync. The listing is valid input for
+//! [`assemble`](crate::asm::assemble).
+
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    instruction::{format_param, Instruction},
+    opcode::{ModeOpt, Opcode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Unknown,
+    Start,
+    Operand,
+}
+
+/// Run of instructions that is only ever entered at its first instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    /// Address after the last instruction of the block
+    pub end: usize,
+    /// Addresses control can continue at after the last instruction
+    pub successors: Vec<usize>,
+    /// Whether the block ends in a jump whose target is read from memory at runtime
+    pub computed_jump: bool,
+}
+
+#[derive(Debug)]
+pub struct Disassembler<'a> {
+    program: &'a [i64],
+    hints: BTreeSet<usize>,
+    pc: Option<usize>,
+}
+
+impl<'a> Disassembler<'a> {
+    #[must_use]
+    pub fn new(program: &'a [i64]) -> Self {
+        Self {
+            program,
+            hints: BTreeSet::new(),
+            pc: None,
+        }
+    }
+
+    /// Addresses known to hold code, for example from an execution trace. They are used as extra
+    /// entry points, which is the only way to reach code behind computed jumps.
+    #[must_use]
+    pub fn with_hints(mut self, hints: impl IntoIterator<Item = usize>) -> Self {
+        self.hints.extend(hints);
+        self
+    }
+
+    /// Marks the program counter in the listing. It is also used as an entry point.
+    #[must_use]
+    pub const fn with_pc(mut self, pc: usize) -> Self {
+        self.pc = Some(pc);
+        self
+    }
+
+    #[must_use]
+    pub fn disassemble(&self) -> Disassembly {
+        let program = self.program;
+        let mut cells = vec![Cell::Unknown; program.len()];
+        let mut instructions = BTreeMap::new();
+        let mut flow = BTreeMap::new();
+
+        let mut leaders = BTreeSet::from([0]);
+        leaders.extend(self.hints.iter().copied());
+        leaders.extend(self.pc);
+
+        let mut work = leaders.iter().rev().copied().collect::<Vec<_>>();
+
+        while let Some(addr) = work.pop() {
+            if cells.get(addr) != Some(&Cell::Unknown) {
+                continue;
+            }
+
+            let Ok(ins) = Instruction::new(program, addr) else {
+                continue;
+            };
+
+            // keep the instruction that was reached first if two of them overlap
+            let next = addr + ins.opcode.len();
+            if cells[addr + 1..next].iter().any(|c| *c != Cell::Unknown) {
+                continue;
+            }
+
+            cells[addr] = Cell::Start;
+            cells[addr + 1..next].fill(Cell::Operand);
+
+            let (successors, computed_jump) = successors(&ins, next);
+            if matches!(ins.opcode, Opcode::Jt | Opcode::Jf) {
+                leaders.extend(successors.iter().copied());
+            }
+
+            work.extend(successors.iter().rev().copied());
+            flow.insert(addr, (successors, computed_jump));
+            instructions.insert(addr, ins);
+        }
+
+        let mut blocks: Vec<Block> = vec![];
+        let mut terminated = true;
+        for (&addr, ins) in &instructions {
+            let continues = !terminated
+                && !leaders.contains(&addr)
+                && blocks.last().is_some_and(|block| block.end == addr);
+
+            if !continues {
+                blocks.push(Block {
+                    start: addr,
+                    end: addr,
+                    successors: vec![],
+                    computed_jump: false,
+                });
+            }
+
+            let block = blocks.last_mut().expect("A block was just pushed");
+            let (successors, computed_jump) = &flow[&addr];
+            block.end = addr + ins.opcode.len();
+            block.successors.clone_from(successors);
+            block.computed_jump = *computed_jump;
+
+            terminated = matches!(ins.opcode, Opcode::Jt | Opcode::Jf | Opcode::Halt);
+        }
+
+        let labels = blocks
+            .iter()
+            .map(|block| (block.start, format!("loc_{:04x}", block.start)))
+            .collect();
+
+        let mut data = vec![];
+        for (addr, cell) in cells.iter().enumerate() {
+            if *cell == Cell::Unknown {
+                match data.last_mut() {
+                    Some((start, values)) if *start + Vec::len(values) == addr => {
+                        values.push(program[addr]);
+                    }
+                    _ => data.push((addr, vec![program[addr]])),
+                }
+            }
+        }
+
+        Disassembly {
+            blocks,
+            instructions,
+            data,
+            labels,
+            pc: self.pc,
+        }
+    }
+}
+
+/// Where control can go after an instruction, and whether it can also go somewhere that is only
+/// known at runtime
+fn successors(ins: &Instruction, next: usize) -> (Vec<usize>, bool) {
+    match ins.opcode {
+        Opcode::Halt => (vec![], false),
+        Opcode::Jt | Opcode::Jf => {
+            // a condition in immediate mode either always or never jumps
+            let taken = (ins.modes[0] == ModeOpt::Immediate)
+                .then(|| (ins.parameters[1] != 0) == (ins.opcode == Opcode::Jt));
+
+            let mut successors = vec![];
+            let mut computed_jump = false;
+
+            if taken != Some(false) {
+                if ins.modes[1] == ModeOpt::Immediate {
+                    successors.extend(usize::try_from(ins.parameters[2]).ok());
+                } else {
+                    computed_jump = true;
+                }
+            }
+
+            if taken != Some(true) {
+                successors.push(next);
+            }
+
+            (successors, computed_jump)
+        }
+        _ => (vec![next], false),
+    }
+}
+
+/// Result of disassembling a program, split into basic blocks and data
+#[derive(Debug)]
+pub struct Disassembly {
+    pub blocks: Vec<Block>,
+    instructions: BTreeMap<usize, Instruction>,
+    data: Vec<(usize, Vec<i64>)>,
+    labels: BTreeMap<usize, String>,
+    pc: Option<usize>,
+}
+
+impl Disassembly {
+    /// Whether an instruction was found to start at the address
+    #[must_use]
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.instructions.contains_key(&addr)
+    }
+
+    #[must_use]
+    pub fn instruction(&self, addr: usize) -> Option<&Instruction> {
+        self.instructions.get(&addr)
+    }
+
+    /// Every instruction found, in order of address
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, &Instruction)> {
+        self.instructions.iter().map(|(addr, ins)| (*addr, ins))
+    }
+
+    #[must_use]
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    fn write_instruction(
+        &self,
+        f: &mut fmt::Formatter,
+        addr: usize,
+        ins: &Instruction,
+    ) -> fmt::Result {
+        let pc_indicator = if self.pc == Some(addr) { "> " } else { "  " };
+        write!(f, "{pc_indicator}{addr:08x}:\t{:?}", ins.opcode)?;
+
+        // mode digits for parameters the instruction does not have are kept in the word
+        let mut canonical = ins.opcode as i64;
+        for (place, mode) in [100, 1_000, 10_000]
+            .iter()
+            .zip(&ins.modes[..ins.opcode.len() - 1])
+        {
+            canonical += place * *mode as i64;
+        }
+        if ins.parameters[0] != canonical {
+            write!(f, "({})", ins.parameters[0])?;
+        }
+
+        let mut params = vec![];
+        for (i, p) in ins.parameters[1..].iter().enumerate() {
+            let label = usize::try_from(*p)
+                .ok()
+                .and_then(|target| self.labels.get(&target));
+
+            params.push(match label {
+                Some(label)
+                    if i == 1
+                        && matches!(ins.opcode, Opcode::Jt | Opcode::Jf)
+                        && ins.modes[1] == ModeOpt::Immediate =>
+                {
+                    format_param(ins.modes[i], label)
+                }
+                _ => format_param(ins.modes[i], p),
+            });
+        }
+
+        if params.is_empty() {
+            writeln!(f)
+        } else {
+            writeln!(f, "\t{}", params.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut blocks = self.blocks.iter().peekable();
+        let mut data = self.data.iter().peekable();
+
+        loop {
+            let next_block = blocks.peek().map(|block| block.start);
+            let next_data = data.peek().map(|(start, _)| *start);
+
+            let block_first = match (next_block, next_data) {
+                (Some(block_start), Some(data_start)) => block_start < data_start,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            if block_first {
+                let block = blocks.next().expect("Block was peeked");
+                writeln!(f, "{}:", self.labels[&block.start])?;
+
+                for (addr, ins) in self.instructions.range(block.start..block.end) {
+                    self.write_instruction(f, *addr, ins)?;
+                }
+
+                if block.computed_jump {
+                    writeln!(f, "\t; computed jump")?;
+                }
+            } else {
+                let (start, values) = data.next().expect("Data was peeked");
+                writeln!(f, "data_{start:04x}:")?;
+
+                for (i, chunk) in values.chunks(8).enumerate() {
+                    let addr = start + i * 8;
+                    let pc_indicator = match self.pc {
+                        Some(pc) if (addr..addr + chunk.len()).contains(&pc) => "> ",
+                        _ => "  ",
+                    };
+
+                    let values = chunk.iter().map(ToString::to_string).collect::<Vec<_>>();
+                    writeln!(f, "{pc_indicator}{addr:08x}:\t.data\t{}", values.join(", "))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_program};
+
+    fn disassemble(program: &str) -> (Vec<i64>, Disassembly) {
+        let program = parse_program(program).unwrap();
+        let disassembly = Disassembler::new(&program).disassemble();
+
+        (program, disassembly)
+    }
+
+    fn block(start: usize, end: usize, successors: &[usize], computed_jump: bool) -> Block {
+        Block {
+            start,
+            end,
+            successors: successors.to_vec(),
+            computed_jump,
+        }
+    }
+
+    #[test]
+    fn splits_blocks_at_jumps_and_their_targets() {
+        // counts [13] down from 3, outputting it every time
+        let (_, disassembly) = disassemble("4,13,1001,13,-1,13,1005,13,0,104,-1,99,0,3");
+
+        assert_eq!(
+            disassembly.blocks,
+            [block(0, 9, &[0, 9], false), block(9, 12, &[], false),]
+        );
+        assert_eq!(disassembly.label(0), Some("loc_0000"));
+        assert_eq!(disassembly.label(9), Some("loc_0009"));
+        assert!(disassembly.is_code(2));
+        assert!(!disassembly.is_code(3));
+    }
+
+    #[test]
+    fn follows_constant_conditions() {
+        // always jumps over the data at 3, and never jumps back to 0
+        let (_, disassembly) = disassemble("1105,1,4,7,1106,1,0,99");
+
+        assert_eq!(
+            disassembly.blocks,
+            [
+                block(0, 3, &[4], false),
+                block(4, 7, &[7], false),
+                block(7, 8, &[], false),
+            ]
+        );
+        assert!(!disassembly.is_code(3));
+    }
+
+    #[test]
+    fn marks_computed_jumps() {
+        // jumps to the address in [8], which only a hint can reach
+        let (program, disassembly) = disassemble("5,7,8,99,104,1,99,1,4");
+        assert_eq!(
+            disassembly.blocks,
+            [block(0, 3, &[3], true), block(3, 4, &[], false)]
+        );
+        assert!(disassembly.to_string().contains("; computed jump"));
+        assert!(!disassembly.is_code(4));
+
+        let disassembly = Disassembler::new(&program).with_hints([4]).disassemble();
+        assert!(disassembly.is_code(4));
+    }
+
+    #[test]
+    fn lists_data_runs() {
+        let (_, disassembly) = disassemble("1105,1,12,1,2,3,4,5,6,7,8,9,99");
+        let listing = disassembly.to_string();
+
+        assert!(listing.contains("data_0003:\n"), "{listing}");
+        assert!(
+            listing.contains("00000003:\t.data\t1, 2, 3, 4, 5, 6, 7, 8\n"),
+            "{listing}"
+        );
+        assert!(listing.contains("0000000b:\t.data\t9\n"), "{listing}");
+    }
+
+    #[test]
+    fn labels_only_immediate_jump_targets() {
+        // the first jump goes to the address stored at 3, which is also where a block starts
+        let (_, disassembly) = disassemble("5,9,3,1105,1,7,99,99,0,0");
+        let listing = disassembly.to_string();
+
+        assert!(listing.contains("Jt\t[9], [3]\n"), "{listing}");
+        assert!(listing.contains("Jt\t1, loc_0007\n"), "{listing}");
+    }
+
+    #[test]
+    fn listing_reassembles() {
+        let programs = [
+            "4,13,1001,13,-1,13,1005,13,0,104,-1,99,0,3",
+            "1105,1,4,7,1106,1,0,99",
+            "5,7,8,99,104,1,99,1,4",
+            "1105,1,12,1,2,3,4,5,6,7,8,9,99",
+            "5,9,3,1105,1,7,99,99,0,0",
+        ];
+
+        for program in programs {
+            let (program, disassembly) = disassemble(program);
+            let listing = Disassembler::new(&program)
+                .with_pc(4)
+                .disassemble()
+                .to_string();
+
+            assert_eq!(assemble(&disassembly.to_string()).unwrap(), program);
+            assert_eq!(assemble(&listing).unwrap(), program, "{listing}");
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use core::fmt;
+use std::{error::Error, io, num::ParseIntError};
+
+use crate::opcode::Opcode;
+
+/// Everything that can go wrong while loading or running an intcode program
+#[derive(Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub enum IntcodeError {
+    /// The value at `addr` does not contain a known opcode
+    InvalidOpcode { addr: usize, value: i64 },
+    /// One of the parameter mode digits of the instruction at `addr` is not 0, 1 or 2
+    InvalidMode { addr: usize, value: i64 },
+    /// A parameter of the instruction at `pc` resolved to an address below zero
+    NegativeAddress { pc: usize, addr: i64 },
+    /// Adjusting the relative base at `pc` took it out of range
+    RelativeBaseOverflow {
+        pc: usize,
+        rel: isize,
+        adjustment: i64,
+    },
+    /// The instruction at `pc` produced output after the output channel was closed by a halt
+    OutputAfterHalt { pc: usize },
+    /// The receiving end of the output channel was dropped before the instruction at `pc` ran
+    OutputClosed { pc: usize },
+    /// The program needed input at `pc` but the input channel was closed
+    InputClosed { pc: usize },
+    /// The program counter, or a parameter of the instruction at `pc`, ran off the end of memory
+    EndOfMemory { pc: usize },
+    /// The instruction at `pc` computed `a + b` or `a * b`, depending on `opcode`, and the result
+    /// did not fit in 64 bits under checked arithmetic
+    Overflow {
+        pc: usize,
+        opcode: Opcode,
+        a: i64,
+        b: i64,
+    },
+    /// The instruction at `pc` used the big integer at `addr` as an address, output or relative
+    /// base adjustment, which all have to fit in 64 bits
+    ValueTooLarge { pc: usize, addr: usize },
+    /// The instruction at `pc` wrote to `addr`, which would have allocated more memory cells than
+    /// `limit`
+    MemoryLimit {
+        pc: usize,
+        addr: usize,
+        limit: usize,
+    },
+
+    /// The program file could not be read
+    Io(io::Error),
+    /// The program text contained something other than comma-separated integers
+    Parse(ParseIntError),
+    /// The program text did not contain any lines
+    EmptyProgram,
+    /// Assembly source could not be assembled
+    Asm { line: usize, message: String },
+    /// A recording could not be decoded
+    Recording(String),
+    /// The stages of a pipeline at the indices in `waiting` need input that none of the others
+    /// will send, and every other stage has halted
+    Deadlock { waiting: Vec<usize> },
+}
+
+impl IntcodeError {
+    /// Address of the instruction that caused the error, if it happened while running a program
+    #[must_use]
+    pub const fn pc(&self) -> Option<usize> {
+        match self {
+            Self::InvalidOpcode { addr: pc, .. }
+            | Self::InvalidMode { addr: pc, .. }
+            | Self::NegativeAddress { pc, .. }
+            | Self::RelativeBaseOverflow { pc, .. }
+            | Self::OutputAfterHalt { pc }
+            | Self::OutputClosed { pc }
+            | Self::InputClosed { pc }
+            | Self::EndOfMemory { pc }
+            | Self::Overflow { pc, .. }
+            | Self::ValueTooLarge { pc, .. }
+            | Self::MemoryLimit { pc, .. } => Some(*pc),
+            Self::Io(_)
+            | Self::Parse(_)
+            | Self::EmptyProgram
+            | Self::Asm { .. }
+            | Self::Recording(_)
+            | Self::Deadlock { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidOpcode { addr, value } => {
+                write!(f, "Invalid opcode in instruction {value} at {addr:#x}")
+            }
+            Self::InvalidMode { addr, value } => {
+                write!(f, "Invalid parameter mode in instruction {value} at {addr:#x}")
+            }
+            Self::NegativeAddress { pc, addr } => {
+                write!(f, "Instruction at {pc:#x} accessed negative address {addr}")
+            }
+            Self::RelativeBaseOverflow {
+                pc,
+                rel,
+                adjustment,
+            } => write!(
+                f,
+                "Instruction at {pc:#x} overflowed the relative base {rel} by adjusting it by {adjustment}"
+            ),
+            Self::OutputAfterHalt { pc } => {
+                write!(f, "Instruction at {pc:#x} output after the program halted")
+            }
+            Self::OutputClosed { pc } => {
+                write!(f, "Instruction at {pc:#x} output after the output channel was closed")
+            }
+            Self::InputClosed { pc } => {
+                write!(f, "Instruction at {pc:#x} needed input but the input channel was closed")
+            }
+            Self::EndOfMemory { pc } => write!(f, "Program ran off the end of memory at {pc:#x}"),
+            Self::Overflow { pc, opcode, a, b } => {
+                let operator = if *opcode == Opcode::Mul { '*' } else { '+' };
+                write!(f, "Instruction at {pc:#x} overflowed computing {a} {operator} {b}")
+            }
+            Self::ValueTooLarge { pc, addr } => write!(
+                f,
+                "Instruction at {pc:#x} used the value at {addr:#x}, which does not fit in 64 bits"
+            ),
+            Self::MemoryLimit { pc, addr, limit } => write!(
+                f,
+                "Instruction at {pc:#x} wrote to {addr:#x}, which would use more than {limit} cells of memory"
+            ),
+            Self::Io(e) => write!(f, "Could not read program: {e}"),
+            Self::Parse(e) => write!(f, "Could not parse program: {e}"),
+            Self::EmptyProgram => write!(f, "Program is empty"),
+            Self::Asm { line, message } => write!(f, "Line {line}: {message}"),
+            Self::Recording(message) => write!(f, "Invalid recording: {message}"),
+            Self::Deadlock { waiting } => {
+                write!(f, "Pipeline stages {waiting:?} are waiting for input that will never come")
+            }
+        }
+    }
+}
+
+impl Error for IntcodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for IntcodeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ParseIntError> for IntcodeError {
+    fn from(e: ParseIntError) -> Self {
+        Self::Parse(e)
+    }
+}
@@ -1,228 +1,542 @@
 use core::fmt;
-use std::{
-    error::Error,
-    fs,
-    ops::{Deref, DerefMut, Index, IndexMut},
-};
+use std::{collections::VecDeque, fs};
 
+use num_bigint::{BigInt, Sign};
 use tokio::sync::mpsc;
 
+pub use machine::State;
+
 use crate::{
+    arithmetic::{self, Arithmetic},
+    decode::DecodeCache,
+    disasm::{Disassembler, Disassembly},
+    error::IntcodeError,
     instruction::Instruction,
+    limits::{Limiter, Outcome},
+    memory::Memory,
     opcode::{ModeOpt, Opcode},
+    profile::Profile,
+    trace::{MemoryWrite, StepRecord},
 };
 
-pub struct Memory(Vec<i64>);
-
-#[allow(dead_code)]
-impl Memory {
-    fn get_mut(&mut self, addr: usize) -> Result<&mut i64, Box<dyn Error + Send + Sync>> {
-        if addr < self.0.len() {
-            return Ok(self
-                .0
-                .get_mut(addr)
-                .ok_or("Could not access memory address 0")?);
-        }
-
-        self.0.resize(addr + 1, 0);
-        Ok(self
-            .0
-            .get_mut(addr)
-            .ok_or("Could not access memory address 2")?)
-    }
-
-    fn len(&self) -> usize {
-        self.0.len()
-    }
+/// Parses the text of a comma-separated program
+pub fn parse_program(text: &str) -> Result<Vec<i64>, IntcodeError> {
+    Ok(text
+        .lines()
+        .next()
+        .ok_or(IntcodeError::EmptyProgram)?
+        .split(',')
+        .map(str::parse::<i64>)
+        .collect::<Result<Vec<_>, _>>()?)
 }
 
-impl Index<usize> for Memory {
-    type Output = i64;
-    fn index(&self, i: usize) -> &i64 {
-        if i < self.0.len() {
-            return &self.0[i];
-        }
-
-        &0
-    }
+/// Compiled version of a program, which runs an executor like [`Executor::run`]
+///
+/// See [`Transpiler`](crate::Transpiler) for how to create one.
+pub type Compiled = fn(&mut Executor) -> Result<State, IntcodeError>;
+
+/// Compiled code new executors run, when the crate is built with a transpiled program
+#[cfg(feature = "transpiled")]
+const DEFAULT_COMPILED: Option<Compiled> = Some(crate::transpiled::run);
+#[cfg(not(feature = "transpiled"))]
+const DEFAULT_COMPILED: Option<Compiled> = None;
+
+/// Everything that decides what a program does next, with memory reduced to a hash
+///
+/// Two equal fingerprints mean the program is in the same state, unless the memory hashes
+/// collide. The number of values read and written is included, so a program that did any I/O
+/// between two points in time never has the same fingerprint at both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    pc: usize,
+    rel: isize,
+    memory: u64,
+    inputs: usize,
+    outputs: usize,
 }
 
-impl IndexMut<usize> for Memory {
-    fn index_mut(&mut self, i: usize) -> &mut i64 {
-        if i >= self.0.len() {
-            self.0.resize(i + 1, 0);
-        }
-
-        &mut self.0[i]
-    }
-}
-
-impl Deref for Memory {
-    type Target = Vec<i64>;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl DerefMut for Memory {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-impl fmt::Display for Memory {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for line in self.0.chunks(8) {
-            let mut out_line = vec![];
-            for val in line {
-                out_line.push(format!("{val:8}\t"));
-            }
-            writeln!(f, "{}", out_line.join("\t"))?;
-        }
-
-        Ok(())
-    }
+/// Complete state of a program at some point in its execution
+///
+/// A snapshot can be restored into an executor to rewind it, or used to create any number of
+/// independent machines that continue from the same state.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    memory: Memory,
+    pc: usize,
+    rel: isize,
+    cache: DecodeCache,
+
+    input: VecDeque<i64>,
+    input_history: Vec<i64>,
+    output_history: Vec<i64>,
 }
 
 pub struct Executor {
     pub memory: Memory,
     pub pc: usize,
     pub rel: isize,
+    cache: DecodeCache,
 
+    input: VecDeque<i64>,
     input_rx: mpsc::Receiver<i64>,
     input_history: Vec<i64>,
 
     output_tx: Option<mpsc::UnboundedSender<i64>>,
     output_history: Vec<i64>,
+
+    profile: Option<Box<Profile>>,
+    compiled: Option<Compiled>,
+    arithmetic: Arithmetic,
 }
 
 impl Executor {
-    fn parse_input(file_string: &str) -> Result<Vec<i64>, Box<dyn Error + Send + Sync>> {
-        Ok(file_string
-            .lines()
-            .next()
-            .ok_or("Error reading line from input file")?
-            .split(',')
-            .map(str::parse::<i64>)
-            .collect::<Result<Vec<_>, _>>()?)
-    }
-
     pub fn from_file(
         file: &str,
         input_rx: mpsc::Receiver<i64>,
         output_tx: mpsc::UnboundedSender<i64>,
-    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+    ) -> Result<Self, IntcodeError> {
         let contents = fs::read_to_string(file)?;
 
-        Ok(Self {
-            memory: Memory(Self::parse_input(&contents)?),
+        Ok(Self::new(parse_program(&contents)?, input_rx, output_tx))
+    }
+
+    pub fn new(
+        program: Vec<i64>,
+        input_rx: mpsc::Receiver<i64>,
+        output_tx: mpsc::UnboundedSender<i64>,
+    ) -> Self {
+        Self {
+            memory: Memory::new(program),
             pc: 0,
             rel: 0,
+            cache: DecodeCache::default(),
 
+            input: VecDeque::new(),
             input_rx,
             input_history: vec![],
 
             output_tx: Some(output_tx),
             output_history: vec![],
-        })
+
+            profile: None,
+            compiled: DEFAULT_COMPILED,
+            arithmetic: Arithmetic::default(),
+        }
     }
 
     pub fn output_history(&self) -> &[i64] {
         &self.output_history
     }
 
-    /// # Panics
+    /// Values consumed by `In` instructions so far
+    pub fn input_history(&self) -> &[i64] {
+        &self.input_history
+    }
+
+    /// Values queued for upcoming `In` instructions
+    pub const fn queued_input(&self) -> &VecDeque<i64> {
+        &self.input
+    }
+
+    /// Queues a value for the next `In` instruction
+    pub fn push_input(&mut self, val: i64) {
+        self.input.push_back(val);
+    }
+
+    /// Number of values read or queued so far, which is the index the next queued value will have
+    /// among every input of the run
+    pub fn inputs_received(&self) -> usize {
+        self.input_history.len() + self.input.len()
+    }
+
+    /// Takes back the value that was queued as input `index` of the run, if it has not been read
+    /// yet
+    pub fn unqueue_input(&mut self, index: usize) -> Option<i64> {
+        let queued = index.checked_sub(self.input_history.len())?;
+        self.input.remove(queued)
+    }
+
+    /// Captures the current state, including input that has been sent over the input channel but
+    /// not consumed yet
+    pub fn snapshot(&mut self) -> Snapshot {
+        while let Ok(message) = self.input_rx.try_recv() {
+            self.input.push_back(message);
+        }
+
+        Snapshot {
+            memory: self.memory.clone(),
+            pc: self.pc,
+            rel: self.rel,
+            cache: self.cache.clone(),
+
+            input: self.input.clone(),
+            input_history: self.input_history.clone(),
+            output_history: self.output_history.clone(),
+        }
+    }
+
+    /// Replaces the current state with a snapshot. The input and output channels are kept.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.memory = snapshot.memory;
+        self.pc = snapshot.pc;
+        self.rel = snapshot.rel;
+        self.cache = snapshot.cache;
+
+        self.input = snapshot.input;
+        self.input_history = snapshot.input_history;
+        self.output_history = snapshot.output_history;
+    }
+
+    pub const fn fingerprint(&self) -> Fingerprint {
+        Fingerprint {
+            pc: self.pc,
+            rel: self.rel,
+            memory: self.memory.hash(),
+            inputs: self.input_history.len(),
+            outputs: self.output_history.len(),
+        }
+    }
+
+    /// Runs until the program needs input, produces an output or halts, or until the limiter
+    /// stops it
     ///
-    /// Panics if the input channel is closed but the program expected input
-    pub async fn exec_one(&mut self) -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
-        enum Access {
-            Fetch,
-            Store,
+    /// The limiter can be passed to any number of calls to limit a run as a whole.
+    pub fn run_limited(&mut self, limiter: &mut Limiter) -> Result<Outcome, IntcodeError> {
+        loop {
+            if let Some(outcome) = limiter.check(self) {
+                return Ok(outcome);
+            }
+
+            let state = self.step()?;
+            limiter.count(state);
+
+            if let Some(state) = state {
+                return Ok(Outcome::State(state));
+            }
         }
+    }
 
-        let ins = Instruction::new(&self.memory.0, self.pc)?;
-        let params = &ins.parameters[1..];
+    /// Disassembles memory by following control flow from address 0 and the program counter
+    ///
+    /// `hints` are further addresses known to hold code, such as the program counters seen while
+    /// running. They let the disassembly continue past jumps with computed targets.
+    pub fn disassemble(&self, hints: impl IntoIterator<Item = usize>) -> Disassembly {
+        Disassembler::new(self.memory.dense())
+            .with_hints(hints)
+            .with_pc(self.pc)
+            .disassemble()
+    }
 
-        let get_param_value = |i: usize, a: Access| -> Result<i64, Box<dyn Error + Send + Sync>> {
-            Ok(
-                match (a, ins.modes.get(i).ok_or("Index doesn't exist for mode")?) {
-                    (Access::Fetch, ModeOpt::Position) => self.memory[usize::try_from(params[i])?],
-                    (Access::Store, ModeOpt::Position) | (_, ModeOpt::Immediate) => params[i],
-                    (Access::Fetch, ModeOpt::Relative) => {
-                        self.memory[usize::try_from(self.rel + isize::try_from(params[i])?)?]
-                    }
-                    (Access::Store, ModeOpt::Relative) => {
-                        i64::try_from(self.rel + isize::try_from(params[i])?)?
-                    }
-                },
+    /// Opcode of the instruction the program counter points at
+    pub fn next_opcode(&mut self) -> Result<Opcode, IntcodeError> {
+        Ok(self.cache.get(&self.memory, self.pc)?.opcode)
+    }
+
+    /// Addresses the instruction at the program counter will read its parameters from and write
+    /// its result to
+    pub fn next_accesses(&self) -> Result<(Vec<usize>, Option<usize>), IntcodeError> {
+        let instruction = Instruction::fetch(&self.memory, self.pc)?;
+        Ok(self.accesses(&instruction))
+    }
+
+    /// Sets the number of memory cells the program can allocate
+    ///
+    /// Writes that would go over the limit fail with [`IntcodeError::MemoryLimit`].
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        self.memory.set_limit(limit);
+    }
+
+    /// Writes to memory, dropping any cached decoding of the instruction at that address
+    pub fn set_memory(&mut self, addr: usize, value: i64) -> Result<(), IntcodeError> {
+        self.memory
+            .set(addr, value)
+            .ok_or(IntcodeError::MemoryLimit {
+                pc: self.pc,
+                addr,
+                limit: self.memory.limit(),
+            })?;
+        self.cache.invalidate(addr);
+
+        Ok(())
+    }
+
+    /// Writes a value of any size, like [`Executor::set_memory`] if it fits in 64 bits
+    fn set_big_memory(&mut self, addr: usize, value: BigInt) -> Result<(), IntcodeError> {
+        self.memory
+            .set_big(addr, value)
+            .ok_or(IntcodeError::MemoryLimit {
+                pc: self.pc,
+                addr,
+                limit: self.memory.limit(),
+            })?;
+        self.cache.invalidate(addr);
+
+        Ok(())
+    }
+
+    /// Starts counting executed instructions and memory accesses, which slows execution down
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_default();
+    }
+
+    /// Counts gathered since profiling was enabled
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    /// Stops profiling and returns the counts gathered so far
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take().map(|profile| *profile)
+    }
+
+    /// Decodes every instruction each time it is executed instead of once per address, which is
+    /// only useful to measure what decoding costs
+    pub fn disable_decode_cache(&mut self) {
+        self.cache.disable();
+    }
+
+    /// Makes [`Executor::run`] and [`Executor::exec`] run compiled code, or the interpreter for
+    /// `None`
+    pub fn set_compiled(&mut self, compiled: Option<Compiled>) {
+        self.compiled = compiled;
+    }
+
+    pub const fn compiled(&self) -> Option<Compiled> {
+        self.compiled
+    }
+
+    /// Sets how `Add` and `Mul` deal with results that do not fit in 64 bits
+    ///
+    /// Compiled code is only run with [`Arithmetic::Wrapping`], and the program is interpreted
+    /// under the other policies.
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    pub const fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    /// Takes the next queued value for an `In` instruction and records it as consumed
+    pub fn consume_input(&mut self) -> Option<i64> {
+        let value = self.input.pop_front()?;
+        self.input_history.push(value);
+
+        Some(value)
+    }
+
+    /// Records a value produced by an `Out` instruction
+    pub fn record_output(&mut self, value: i64) {
+        self.output_history.push(value);
+    }
+
+    /// Address a relative mode parameter of the instruction at the program counter refers to
+    pub fn relative_address(&self, offset: i64) -> Result<usize, IntcodeError> {
+        resolve_address(self.pc, self.rel, ModeOpt::Relative, offset)
+    }
+
+    /// Address a jump of the instruction at the program counter goes to
+    pub fn jump_target(&self, target: i64) -> Result<usize, IntcodeError> {
+        usize::try_from(target).map_err(|_| IntcodeError::NegativeAddress {
+            pc: self.pc,
+            addr: target,
+        })
+    }
+
+    /// Moves the relative base like the `Rel` instruction at the program counter
+    pub fn adjust_relative_base(&mut self, adjustment: i64) -> Result<(), IntcodeError> {
+        self.rel = isize::try_from(adjustment)
+            .ok()
+            .and_then(|adjustment| self.rel.checked_add(adjustment))
+            .ok_or(IntcodeError::RelativeBaseOverflow {
+                pc: self.pc,
+                rel: self.rel,
+                adjustment,
+            })?;
+
+        Ok(())
+    }
+
+    /// Executes a single instruction without blocking
+    ///
+    /// Returns `None` for instructions that only touch memory or the program counter. An `In`
+    /// with nothing queued leaves the program counter where it is and returns
+    /// [`State::NeedsInput`], so it is executed again once input has been pushed.
+    pub fn step(&mut self) -> Result<Option<State>, IntcodeError> {
+        if self.profile.is_some() {
+            return self.profiled_step();
+        }
+
+        self.execute()
+    }
+
+    /// Executes a single instruction like [`Executor::step`] and counts it in the profile
+    fn profiled_step(&mut self) -> Result<Option<State>, IntcodeError> {
+        let pc = self.pc;
+        let instruction = Instruction::fetch(&self.memory, pc)?;
+        let (reads, write) = self.accesses(&instruction);
+
+        let state = self.execute()?;
+        if state != Some(State::NeedsInput) {
+            if let Some(profile) = &mut self.profile {
+                profile.record(pc, &instruction, &reads, write, self.pc);
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Addresses the instruction at the program counter reads its parameters from and writes its
+    /// result to
+    fn accesses(&self, instruction: &Instruction) -> (Vec<usize>, Option<usize>) {
+        let (reads, write) = instruction.opcode.accesses();
+
+        let address = |i: usize| {
+            resolve_address(
+                self.pc,
+                self.rel,
+                instruction.modes[i],
+                instruction.parameters[i + 1],
             )
+            .ok()
+        };
+        let reads = reads
+            .iter()
+            .filter(|i| instruction.modes[**i] != ModeOpt::Immediate)
+            .filter_map(|i| address(*i))
+            .collect();
+
+        (reads, write.and_then(address))
+    }
+
+    fn execute(&mut self) -> Result<Option<State>, IntcodeError> {
+        let pc = self.pc;
+        let ins = self.cache.get(&self.memory, pc)?;
+        if (pc + ins.opcode.len()) > self.memory.len() {
+            return Err(IntcodeError::EndOfMemory { pc });
+        }
+
+        let mut params = [0; 3];
+        for (i, param) in params.iter_mut().enumerate().take(ins.opcode.len() - 1) {
+            *param = self.memory[pc + 1 + i];
+        }
+
+        // address a parameter refers to, which is also where results are stored
+        let address = |i: usize| resolve_address(pc, self.rel, ins.modes[i], params[i]);
+
+        let get_param_value = |i: usize| -> Result<i64, IntcodeError> {
+            Ok(match ins.modes[i] {
+                ModeOpt::Immediate => params[i],
+                ModeOpt::Position | ModeOpt::Relative => {
+                    let addr = address(i)?;
+                    if self.memory.get_big(addr).is_some() {
+                        return Err(IntcodeError::ValueTooLarge { pc, addr });
+                    }
+
+                    self.memory[addr]
+                }
+            })
+        };
+
+        // value of a parameter that may be a big integer, for big integer arithmetic
+        let get_big_param_value = |i: usize| -> Result<BigInt, IntcodeError> {
+            Ok(match ins.modes[i] {
+                ModeOpt::Immediate => BigInt::from(params[i]),
+                ModeOpt::Position | ModeOpt::Relative => {
+                    let addr = address(i)?;
+                    self.memory
+                        .get_big(addr)
+                        .cloned()
+                        .unwrap_or_else(|| BigInt::from(self.memory[addr]))
+                }
+            })
+        };
+
+        let is_zero = |i: usize| -> Result<bool, IntcodeError> {
+            if self.arithmetic == Arithmetic::Big {
+                return Ok(get_big_param_value(i)?.sign() == Sign::NoSign);
+            }
+
+            Ok(get_param_value(i)? == 0)
         };
 
+        let jump_target = |i: usize| self.jump_target(get_param_value(i)?);
+
         match ins.opcode {
-            Opcode::Add => {
-                let location = usize::try_from(get_param_value(2, Access::Store)?)?;
-                self.memory[location] =
-                    get_param_value(0, Access::Fetch)? + get_param_value(1, Access::Fetch)?;
+            Opcode::Add | Opcode::Mul if self.arithmetic == Arithmetic::Big => {
+                let location = address(2)?;
+                let val =
+                    arithmetic::exact(ins.opcode, get_big_param_value(0)?, get_big_param_value(1)?);
+                self.set_big_memory(location, val)?;
             }
-            Opcode::Mul => {
-                let location = usize::try_from(get_param_value(2, Access::Store)?)?;
-                self.memory[location] =
-                    get_param_value(0, Access::Fetch)? * get_param_value(1, Access::Fetch)?;
+            Opcode::Add | Opcode::Mul => {
+                let location = address(2)?;
+                let (a, b) = (get_param_value(0)?, get_param_value(1)?);
+                let val =
+                    self.arithmetic
+                        .apply(ins.opcode, a, b)
+                        .ok_or(IntcodeError::Overflow {
+                            pc,
+                            opcode: ins.opcode,
+                            a,
+                            b,
+                        })?;
+                self.set_memory(location, val)?;
             }
             Opcode::In => {
-                if let Some(message) = self.input_rx.recv().await {
-                    let location = usize::try_from(get_param_value(0, Access::Store)?)?;
-                    self.memory[location] = message;
-                    self.input_history.push(message);
-                } else {
-                    // program already halted
-                    self.output_tx = None;
-                    return Ok(Some(0));
-                }
+                let Some(message) = self.input.pop_front() else {
+                    return Ok(Some(State::NeedsInput));
+                };
+
+                let location = address(0)?;
+                self.set_memory(location, message)?;
+                self.input_history.push(message);
             }
             Opcode::Out => {
-                let message = get_param_value(0, Access::Fetch)?;
-                self.output_tx
-                    .as_mut()
-                    .expect("Tried to output when program already halted")
-                    .send(message)
-                    .expect("Tried to output when program already halted");
+                let message = get_param_value(0)?;
                 self.output_history.push(message);
+                self.pc += ins.opcode.len();
+
+                return Ok(Some(State::Output(message)));
             }
             Opcode::Jt => {
-                if get_param_value(0, Access::Fetch)? != 0 {
-                    self.pc = usize::try_from(get_param_value(1, Access::Fetch)?)?;
+                if !is_zero(0)? {
+                    self.pc = jump_target(1)?;
                     return Ok(None);
                 }
             }
             Opcode::Jf => {
-                if get_param_value(0, Access::Fetch)? == 0 {
-                    self.pc = usize::try_from(get_param_value(1, Access::Fetch)?)?;
+                if is_zero(0)? {
+                    self.pc = jump_target(1)?;
                     return Ok(None);
                 }
             }
+            Opcode::Lt | Opcode::Eq if self.arithmetic == Arithmetic::Big => {
+                let (a, b) = (get_big_param_value(0)?, get_big_param_value(1)?);
+                let val = i64::from(if ins.opcode == Opcode::Lt {
+                    a < b
+                } else {
+                    a == b
+                });
+                let location = address(2)?;
+                self.set_memory(location, val)?;
+            }
             Opcode::Lt => {
-                let val = i64::from(
-                    get_param_value(0, Access::Fetch)? < get_param_value(1, Access::Fetch)?,
-                );
-                let location = usize::try_from(get_param_value(2, Access::Store)?)?;
-                self.memory[location] = val;
+                let val = i64::from(get_param_value(0)? < get_param_value(1)?);
+                let location = address(2)?;
+                self.set_memory(location, val)?;
             }
             Opcode::Eq => {
-                let val = i64::from(
-                    get_param_value(0, Access::Fetch)? == get_param_value(1, Access::Fetch)?,
-                );
-                let location = usize::try_from(get_param_value(2, Access::Store)?)?;
-                self.memory[location] = val;
+                let val = i64::from(get_param_value(0)? == get_param_value(1)?);
+                let location = address(2)?;
+                self.set_memory(location, val)?;
             }
             Opcode::Rel => {
-                self.rel += isize::try_from(get_param_value(0, Access::Fetch)?)?;
+                let adjustment = get_param_value(0)?;
+                self.adjust_relative_base(adjustment)?;
             }
             Opcode::Halt => {
-                // manually drops the output senders
-                self.output_tx = None;
-
-                return Ok(Some(0));
+                return Ok(Some(State::Halted));
             }
         }
 
@@ -231,16 +545,159 @@ impl Executor {
         Ok(None)
     }
 
-    pub async fn exec(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    /// Executes a single instruction like [`Executor::step`] and records what it did
+    ///
+    /// No record is returned when the step did not execute anything because the program needs
+    /// input.
+    pub fn trace_step(&mut self) -> Result<(Option<State>, Option<StepRecord>), IntcodeError> {
+        let pc = self.pc;
+        let rel = self.rel;
+        let instruction = Instruction::fetch(&self.memory, pc)?;
+        let (reads, write) = self.accesses(&instruction);
+
+        let reads = reads
+            .into_iter()
+            .map(|addr| (addr, self.memory[addr]))
+            .collect();
+        let write = write.map(|addr| (addr, self.memory.value(addr)));
+
+        let state = self.step()?;
+        if state == Some(State::NeedsInput) {
+            return Ok((state, None));
+        }
+
+        Ok((
+            state,
+            Some(StepRecord {
+                pc,
+                rel,
+                reads,
+                write: write.map(|(addr, old)| MemoryWrite {
+                    addr,
+                    old,
+                    new: self.memory[addr],
+                }),
+                input: (instruction.opcode == Opcode::In)
+                    .then(|| self.input_history.last().copied())
+                    .flatten(),
+                output: match state {
+                    Some(State::Output(value)) => Some(value),
+                    _ => None,
+                },
+                instruction,
+            }),
+        ))
+    }
+
+    /// Reverts the step described by a record from [`Executor::trace_step`]
+    ///
+    /// Steps have to be undone in the reverse of the order they were executed in, starting with
+    /// the most recent one.
+    pub fn undo(&mut self, record: &StepRecord) {
+        if let Some(write) = &record.write {
+            // the cell was allocated when it was written, so this can not go over the limit
+            let _ = self.memory.set_value(write.addr, write.old.clone());
+            self.cache.invalidate(write.addr);
+        }
+
+        if let Some(input) = record.input {
+            self.input_history.pop();
+            self.input.push_front(input);
+        }
+
+        if record.output.is_some() {
+            self.output_history.pop();
+        }
+
+        self.pc = record.pc;
+        self.rel = record.rel;
+    }
+
+    /// Runs until the program needs input, produces an output or halts
+    ///
+    /// Compiled code is run if it has been set, unless the run is being profiled or arithmetic is
+    /// not wrapping.
+    pub fn run(&mut self) -> Result<State, IntcodeError> {
+        let compiled = self
+            .compiled
+            .filter(|_| self.profile.is_none() && self.arithmetic == Arithmetic::Wrapping);
+        if let Some(compiled) = compiled {
+            return compiled(self);
+        }
+
+        while self.pc < self.memory.len() {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+        Err(IntcodeError::EndOfMemory { pc: self.pc })
+    }
+
+    pub async fn exec_one(&mut self) -> Result<Option<i64>, IntcodeError> {
+        loop {
+            let pc = self.pc;
+
+            let state = match self.compiled {
+                Some(_) => Some(self.run()?),
+                None => self.step()?,
+            };
+
+            match state {
+                None => return Ok(None),
+                Some(State::NeedsInput) => {
+                    let message = self
+                        .input_rx
+                        .recv()
+                        .await
+                        .ok_or(IntcodeError::InputClosed { pc })?;
+                    self.input.push_back(message);
+                }
+                Some(State::Output(message)) => {
+                    self.output_tx
+                        .as_mut()
+                        .ok_or(IntcodeError::OutputAfterHalt { pc })?
+                        .send(message)
+                        .map_err(|_| IntcodeError::OutputClosed { pc })?;
+                    return Ok(None);
+                }
+                Some(State::Halted) => {
+                    // manually drops the output senders
+                    self.output_tx = None;
+
+                    return Ok(Some(0));
+                }
+            }
+        }
+    }
+
+    pub async fn exec(&mut self) -> Result<(), IntcodeError> {
         while self.pc < self.memory.len() {
             if let Some(_output) = self.exec_one().await? {
                 return Ok(());
             }
         }
-        Err("Program did not halt")?
+        Err(IntcodeError::EndOfMemory { pc: self.pc })
     }
 }
 
+/// Address a parameter refers to, which for parameters in immediate mode is the parameter itself
+fn resolve_address(
+    pc: usize,
+    rel: isize,
+    mode: ModeOpt,
+    param: i64,
+) -> Result<usize, IntcodeError> {
+    let addr = match mode {
+        ModeOpt::Position | ModeOpt::Immediate => param,
+        ModeOpt::Relative => i64::try_from(rel)
+            .ok()
+            .and_then(|rel| rel.checked_add(param))
+            .ok_or(IntcodeError::NegativeAddress { pc, addr: param })?,
+    };
+
+    usize::try_from(addr).map_err(|_| IntcodeError::NegativeAddress { pc, addr })
+}
+
 impl fmt::Debug for Executor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // writeln!(f, "input:\t{:?}", self.input.history)?;
@@ -248,7 +705,7 @@ impl fmt::Debug for Executor {
         writeln!(f, "pc:\t{:?}", self.pc)?;
 
         writeln!(f, "\nprogram: ")?;
-        for chunk in self.memory.chunks(16) {
+        for chunk in self.memory.dense().chunks(16) {
             writeln!(f, "\t{chunk:?}")?;
         }
 
@@ -259,8 +716,8 @@ impl fmt::Debug for Executor {
 impl fmt::Display for Executor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut addr = 0;
-        while addr < self.memory.len() {
-            let Ok(ins) = Instruction::new(&self.memory, addr) else {
+        while addr < self.memory.dense().len() {
+            let Ok(ins) = Instruction::new(self.memory.dense(), addr) else {
                 writeln!(
                     f,
                     "\tInvalid instruction: {} at: {:#x}",
@@ -289,3 +746,109 @@ impl fmt::Display for Executor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{assemble, Interpreter, State};
+
+    /// Outputs the running total of the values it reads
+    const TOTAL: &str = "
+        loop:   In      [x]
+                Add     [x], [total], [total]
+                Out     [total]
+                Jt      1, loop
+        x:      .data   0
+        total:  .data   0
+        ";
+
+    fn total() -> Interpreter {
+        Interpreter::new(assemble(TOTAL).unwrap())
+    }
+
+    /// Runs the instruction at `op` twice, adding `delta` to its instruction word in between
+    fn patched(delta: i64) -> String {
+        format!(
+            "
+            op:     Add     [x], 3, [x]
+                    Add     [op], {delta}, [op]
+                    Add     [n], -1, [n]
+                    Jt      [n], op
+                    Out     [x]
+                    Halt
+            x:      .data   2
+            n:      .data   2
+            "
+        )
+    }
+
+    #[test]
+    fn decode_cache_sees_self_modifying_code() {
+        // the opcode changes to `Mul`, or the first parameter to immediate mode, which adds the
+        // address of `x` instead of its value
+        for (delta, output) in [(1, 15), (100, 21)] {
+            let program = assemble(&patched(delta)).unwrap();
+
+            let mut cached = Interpreter::new(program.clone());
+            let mut uncached = Interpreter::new(program);
+            uncached.executor.disable_decode_cache();
+
+            for interpreter in [&mut cached, &mut uncached] {
+                assert_eq!(interpreter.run().unwrap(), State::Output(output), "{delta}");
+                assert_eq!(interpreter.run().unwrap(), State::Halted, "{delta}");
+            }
+            assert_eq!(
+                cached.executor.memory.dense(),
+                uncached.executor.memory.dense()
+            );
+        }
+    }
+
+    #[test]
+    fn fork_runs_independently() {
+        let mut interpreter = total();
+        interpreter.push_input(1);
+        assert_eq!(interpreter.run().unwrap(), State::Output(1));
+        interpreter.push_input(5);
+
+        let mut fork = interpreter.fork();
+        fork.push_input(10);
+
+        // both read the value that was queued when the fork was made, but only the fork has more
+        assert_eq!(interpreter.run().unwrap(), State::Output(6));
+        assert_eq!(interpreter.run().unwrap(), State::NeedsInput);
+        assert_eq!(fork.run().unwrap(), State::Output(6));
+        assert_eq!(fork.run().unwrap(), State::Output(16));
+
+        assert_eq!(interpreter.executor.pc, 0);
+        assert_eq!(fork.executor.pc, 8);
+        assert_eq!(interpreter.executor.memory.get(12), 6);
+        assert_eq!(fork.executor.memory.get(12), 16);
+        assert_eq!(interpreter.output_history(), [1, 6]);
+        assert_eq!(fork.output_history(), [1, 6, 16]);
+        assert_eq!(fork.input_history(), [1, 5, 10]);
+    }
+
+    #[tokio::test]
+    async fn snapshot_keeps_input_sent_over_the_channel() {
+        let mut interpreter = total();
+        interpreter.push_input(1);
+        interpreter.input(2).await.unwrap();
+        interpreter.input(3).await.unwrap();
+
+        let snapshot = interpreter.snapshot();
+        let mut restored = Interpreter::from_snapshot(snapshot);
+        for machine in [&mut interpreter, &mut restored] {
+            assert_eq!(machine.run().unwrap(), State::Output(1));
+            assert_eq!(machine.run().unwrap(), State::Output(3));
+            assert_eq!(machine.run().unwrap(), State::Output(6));
+            assert_eq!(machine.run().unwrap(), State::NeedsInput);
+        }
+
+        // a fork drains the channel the same way
+        let mut interpreter = total();
+        interpreter.input(4).await.unwrap();
+        let mut fork = interpreter.fork();
+        assert_eq!(fork.run().unwrap(), State::Output(4));
+        assert_eq!(interpreter.run().unwrap(), State::Output(4));
+    }
+}
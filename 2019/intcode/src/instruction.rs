@@ -1,43 +1,40 @@
-use crate::opcode::{ModeOpt, Opcode};
+use crate::{
+    decode::Decoded,
+    error::IntcodeError,
+    memory::Memory,
+    opcode::{ModeOpt, Opcode},
+};
 use core::fmt;
-use std::error::Error;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Instruction {
     pub opcode: Opcode,
-    pub modes: Vec<ModeOpt>,
+    pub modes: [ModeOpt; 3],
     pub parameters: Vec<i64>,
 }
 
 impl Instruction {
-    pub fn new(program: &[i64], addr: usize) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let op = program
-            .get(addr)
-            .ok_or("Address {addr} does not exist in the program.")?;
+    pub fn new(program: &[i64], addr: usize) -> Result<Self, IntcodeError> {
+        Self::read(|addr| program[addr], program.len(), addr)
+    }
 
-        let chars = format!("{op:05}").chars().collect::<Vec<_>>();
+    /// Reads the instruction at `addr` from memory, which may be past the program image
+    pub fn fetch(memory: &Memory, addr: usize) -> Result<Self, IntcodeError> {
+        Self::read(|addr| memory[addr], memory.len(), addr)
+    }
 
-        let opcode: Opcode = chars[chars.len() - 2..chars.len()]
-            .iter()
-            .collect::<String>()
-            .parse::<u8>()?
-            .try_into()?;
+    fn read(cell: impl Fn(usize) -> i64, len: usize, addr: usize) -> Result<Self, IntcodeError> {
+        if addr >= len {
+            return Err(IntcodeError::EndOfMemory { pc: addr });
+        }
 
-        let modes = chars[0..3]
-            .iter()
-            .rev()
-            .map(|c| c.to_digit(10))
-            .collect::<Option<Vec<_>>>()
-            .ok_or("Error converting modes to digits")?
-            .into_iter()
-            .map(TryInto::try_into)
-            .collect::<Result<Vec<_>, _>>()?;
+        let Decoded { opcode, modes } = Decoded::new(cell(addr), addr)?;
 
-        if (addr + opcode.len()) > program.len() {
-            return Err("Program does not contain enough data for the instruction.")?;
+        if (addr + opcode.len()) > len {
+            return Err(IntcodeError::EndOfMemory { pc: addr });
         }
 
-        let parameters = program[addr..addr + opcode.len()].to_vec();
+        let parameters = (addr..addr + opcode.len()).map(cell).collect();
 
         Ok(Self {
             opcode,
@@ -47,6 +44,15 @@ impl Instruction {
     }
 }
 
+/// Formats a parameter the way it is written in the disassembly
+pub fn format_param(mode: ModeOpt, param: impl fmt::Display) -> String {
+    match mode {
+        ModeOpt::Position => format!("[{param}]"),
+        ModeOpt::Immediate => param.to_string(),
+        ModeOpt::Relative => format!("[{param} + rel]"),
+    }
+}
+
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self.opcode)?;
@@ -58,18 +64,7 @@ impl fmt::Display for Instruction {
         let mut params = vec![];
 
         for (i, p) in self.parameters[1..].iter().enumerate() {
-            let brackets = match self.modes.get(i) {
-                Some(ModeOpt::Position | ModeOpt::Relative) => ("[", "]"),
-                _ => ("", ""),
-            };
-
-            let rel = if self.modes[i] == ModeOpt::Relative {
-                " + rel"
-            } else {
-                ""
-            };
-
-            params.push(format!("{}{}{}{}", brackets.0, p, rel, brackets.1));
+            params.push(format_param(self.modes[i], p));
         }
 
         write!(f, "\t{}", params.join(", "))?;
@@ -1,6 +1,14 @@
-use crate::executor::Executor;
+use crate::{
+    arithmetic::Arithmetic,
+    disasm::Disassembly,
+    error::IntcodeError,
+    executor::{parse_program, Compiled, Executor, Snapshot, State},
+    io::{InputProvider, OutputSink},
+    limits::{Limiter, Outcome},
+    profile::Profile,
+    trace::StepRecord,
+};
 use core::fmt;
-use std::error::Error;
 use tokio::sync::mpsc;
 
 pub struct Interpreter {
@@ -11,34 +19,93 @@ pub struct Interpreter {
 
 #[allow(clippy::missing_errors_doc)]
 impl Interpreter {
-    pub async fn from_file(
-        file: &str,
-        input: Vec<i64>,
-    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+    /// Creates an interpreter for a program that is already in memory, with no input queued
+    #[must_use]
+    pub fn new(program: Vec<i64>) -> Self {
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        let (input_tx, input_rx) = mpsc::channel(32);
+
+        Self {
+            executor: Executor::new(program, input_rx, output_tx),
+            output_rx,
+            input_tx: Some(input_tx),
+        }
+    }
+
+    /// Creates an interpreter that continues from a previously captured state
+    #[must_use]
+    pub fn from_snapshot(snapshot: Snapshot) -> Self {
+        let mut interpreter = Self::new(vec![]);
+        interpreter.executor.restore(snapshot);
+
+        interpreter
+    }
+
+    /// Creates an interpreter from the text of a comma-separated program
+    pub fn parse(program: &str) -> Result<Self, IntcodeError> {
+        Ok(Self::new(parse_program(program)?))
+    }
+
+    pub async fn from_file(file: &str, input: Vec<i64>) -> Result<Self, IntcodeError> {
         let (output_tx, output_rx) = mpsc::unbounded_channel();
         let (input_tx, input_rx) = mpsc::channel(32);
 
-        for i in input.clone() {
-            input_tx.send(i).await?;
+        let mut executor = Executor::from_file(file, input_rx, output_tx)?;
+        for i in input {
+            executor.push_input(i);
         }
 
         Ok(Self {
-            executor: Executor::from_file(file, input_rx, output_tx)?,
+            executor,
             output_rx,
             input_tx: Some(input_tx),
         })
     }
 
-    /// # Panics
-    ///
-    /// Panics if the input channel is closed but the program expected input
-    pub async fn input(&mut self, val: i64) {
+    pub async fn input(&mut self, val: i64) -> Result<(), IntcodeError> {
+        let pc = self.executor.pc;
+
         self.input_tx
             .as_mut()
-            .expect("Input channel doesn't exist")
+            .ok_or(IntcodeError::InputClosed { pc })?
             .send(val)
             .await
-            .expect("Input channel closed");
+            .map_err(|_| IntcodeError::InputClosed { pc })
+    }
+
+    /// Queues a value for the next `In` instruction without going through the input channel
+    pub fn push_input(&mut self, val: i64) {
+        self.executor.push_input(val);
+    }
+
+    /// See [`Executor::disassemble`]
+    pub fn disassemble(&self, hints: impl IntoIterator<Item = usize>) -> Disassembly {
+        self.executor.disassemble(hints)
+    }
+
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.executor.snapshot()
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.executor.restore(snapshot);
+    }
+
+    /// Creates an independent copy of the machine in its current state, with its own channels
+    #[must_use]
+    pub fn fork(&mut self) -> Self {
+        let mut fork = Self::from_snapshot(self.snapshot());
+        fork.executor.set_compiled(self.executor.compiled());
+        fork.executor.set_arithmetic(self.executor.arithmetic());
+
+        fork
+    }
+
+    /// Runs compiled code instead of interpreting the program, see [`Transpiler`](crate::Transpiler)
+    #[must_use]
+    pub fn with_compiled(mut self, compiled: Compiled) -> Self {
+        self.executor.set_compiled(Some(compiled));
+        self
     }
 
     pub async fn output(&mut self) -> Option<i64> {
@@ -50,26 +117,111 @@ impl Interpreter {
         self.executor.output_history()
     }
 
+    #[must_use]
+    pub fn input_history(&self) -> &[i64] {
+        self.executor.input_history()
+    }
+
+    /// Writes to memory, returning `None` if that would go over the memory limit
     pub fn set_memory(&mut self, address: usize, value: i64) -> Option<()> {
-        *self.executor.memory.get_mut(address)? = value;
+        self.executor.set_memory(address, value).ok()
+    }
+
+    /// Sets how `Add` and `Mul` deal with results that do not fit in 64 bits, which defaults to
+    /// wrapping around
+    #[must_use]
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.executor.set_arithmetic(arithmetic);
+        self
+    }
 
-        Some(())
+    /// Sets the number of memory cells the program can allocate, which defaults to
+    /// [`DEFAULT_MEMORY_LIMIT`](crate::DEFAULT_MEMORY_LIMIT)
+    #[must_use]
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.executor.set_memory_limit(limit);
+        self
+    }
+
+    /// Counts every instruction executed from now on, see [`Executor::enable_profiling`]
+    #[must_use]
+    pub fn with_profiling(mut self) -> Self {
+        self.executor.enable_profiling();
+        self
     }
 
-    pub async fn exec_one(&mut self) -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+    #[must_use]
+    pub fn profile(&self) -> Option<&Profile> {
+        self.executor.profile()
+    }
+
+    pub fn step(&mut self) -> Result<Option<State>, IntcodeError> {
+        self.executor.step()
+    }
+
+    /// See [`Executor::trace_step`]
+    pub fn trace_step(&mut self) -> Result<(Option<State>, Option<StepRecord>), IntcodeError> {
+        self.executor.trace_step()
+    }
+
+    /// See [`Executor::undo`]
+    pub fn undo(&mut self, record: &StepRecord) {
+        self.executor.undo(record);
+    }
+
+    /// Runs until the program needs input, produces an output or halts
+    ///
+    /// Values are only read from the queue filled by [`Interpreter::push_input`] and outputs are
+    /// only recorded in the output history, never sent over the output channel.
+    pub fn run(&mut self) -> Result<State, IntcodeError> {
+        self.executor.run()
+    }
+
+    /// Runs until the program halts, taking input from and handing output to `io`
+    ///
+    /// A provider and a sink that are separate values are passed as a pair. Values queued with
+    /// [`Interpreter::push_input`] are read before `io` is asked for any. Returns
+    /// [`State::NeedsInput`] if `io` has no input yet, and the run can be picked up again with more
+    /// input later. Outputs are recorded in the output history too.
+    pub fn run_with(
+        &mut self,
+        io: &mut (impl InputProvider + OutputSink),
+    ) -> Result<State, IntcodeError> {
+        loop {
+            match self.executor.run()? {
+                State::NeedsInput => match io.next_input(&self.executor) {
+                    Some(value) => self.executor.push_input(value),
+                    None => return Ok(State::NeedsInput),
+                },
+                State::Output(value) => io.output(value),
+                State::Halted => return Ok(State::Halted),
+            }
+        }
+    }
+
+    /// See [`Executor::run_limited`]
+    pub fn run_limited(&mut self, limiter: &mut Limiter) -> Result<Outcome, IntcodeError> {
+        self.executor.run_limited(limiter)
+    }
+
+    pub async fn exec_one(&mut self) -> Result<Option<i64>, IntcodeError> {
         self.executor.exec_one().await
     }
 
-    pub async fn exec(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    pub async fn exec(&mut self) -> Result<(), IntcodeError> {
         self.executor.exec().await
     }
 
     pub fn exec_spawn(
         mut self,
-    ) -> Result<(mpsc::Sender<i64>, mpsc::UnboundedReceiver<i64>), Box<dyn Error + Send + Sync>>
-    {
+    ) -> Result<(mpsc::Sender<i64>, mpsc::UnboundedReceiver<i64>), IntcodeError> {
+        let input_tx = self.input_tx.take().ok_or(IntcodeError::InputClosed {
+            pc: self.executor.pc,
+        })?;
+        let output_rx = self.output_rx;
+
         tokio::spawn(async move { self.executor.exec().await });
-        Ok((self.input_tx.ok_or("Input already closed")?, self.output_rx))
+        Ok((input_tx, output_rx))
     }
 }
 
@@ -0,0 +1,307 @@
+//! Where programs run by [`Interpreter::run_with`] get their input from and put their output
+//!
+//! One value can be both an [`InputProvider`] and an [`OutputSink`], for programs whose input
+//! depends on their output. Otherwise a provider and a sink are paired up in a tuple.
+//!
+//! [`Interpreter::run_with`]: crate::Interpreter::run_with
+
+use std::collections::VecDeque;
+
+use tokio::sync::mpsc;
+
+use crate::{ascii::ascii, executor::Executor};
+
+/// Source of the values `In` instructions read
+pub trait InputProvider {
+    /// Value for the `In` instruction at the program counter, or `None` if there is none yet
+    fn next_input(&mut self, executor: &Executor) -> Option<i64>;
+}
+
+/// Destination of the values `Out` instructions produce
+pub trait OutputSink {
+    fn output(&mut self, value: i64);
+}
+
+impl<I: InputProvider, O> InputProvider for (I, O) {
+    fn next_input(&mut self, executor: &Executor) -> Option<i64> {
+        self.0.next_input(executor)
+    }
+}
+
+impl<I, O: OutputSink> OutputSink for (I, O) {
+    fn output(&mut self, value: i64) {
+        self.1.output(value);
+    }
+}
+
+/// Fixed values, read in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListInput(VecDeque<i64>);
+
+impl ListInput {
+    #[must_use]
+    pub fn new(values: impl IntoIterator<Item = i64>) -> Self {
+        Self(values.into_iter().collect())
+    }
+
+    pub fn push(&mut self, value: i64) {
+        self.0.push_back(value);
+    }
+}
+
+impl InputProvider for ListInput {
+    fn next_input(&mut self, _executor: &Executor) -> Option<i64> {
+        self.0.pop_front()
+    }
+}
+
+/// Values worked out by a closure from the state of the machine when it needs them
+#[derive(Debug, Clone)]
+pub struct StateInput<F>(pub F);
+
+impl<F: FnMut(&Executor) -> Option<i64>> InputProvider for StateInput<F> {
+    fn next_input(&mut self, executor: &Executor) -> Option<i64> {
+        (self.0)(executor)
+    }
+}
+
+/// Text, read as ASCII codes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsciiInput(VecDeque<u8>);
+
+impl AsciiInput {
+    #[must_use]
+    pub fn new(text: &str) -> Self {
+        Self(text.bytes().collect())
+    }
+
+    /// Queues a line of text, followed by a newline
+    pub fn push_line(&mut self, line: &str) {
+        self.0.extend(line.bytes().chain([b'\n']));
+    }
+}
+
+impl InputProvider for AsciiInput {
+    fn next_input(&mut self, _executor: &Executor) -> Option<i64> {
+        self.0.pop_front().map(i64::from)
+    }
+}
+
+/// Values sent over a channel
+///
+/// Only values that have already been sent are read, so a run never blocks on the channel and
+/// stops to wait for input instead.
+#[derive(Debug)]
+pub struct ChannelInput(pub mpsc::Receiver<i64>);
+
+impl InputProvider for ChannelInput {
+    fn next_input(&mut self, _executor: &Executor) -> Option<i64> {
+        self.0.try_recv().ok()
+    }
+}
+
+/// Every output, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Collector(Vec<i64>);
+
+impl Collector {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn values(&self) -> &[i64] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn into_values(self) -> Vec<i64> {
+        self.0
+    }
+}
+
+impl OutputSink for Collector {
+    fn output(&mut self, value: i64) {
+        self.0.push(value);
+    }
+}
+
+/// Outputs grouped into tuples of `N`, which are passed to a closure once complete
+#[derive(Debug, Clone)]
+pub struct Tuples<const N: usize, F> {
+    callback: F,
+    tuple: [i64; N],
+    len: usize,
+}
+
+impl<const N: usize, F: FnMut([i64; N])> Tuples<N, F> {
+    #[must_use]
+    pub const fn new(callback: F) -> Self {
+        Self {
+            callback,
+            tuple: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Outputs of the tuple that is not complete yet
+    #[must_use]
+    pub fn pending(&self) -> &[i64] {
+        &self.tuple[..self.len]
+    }
+}
+
+impl<const N: usize, F: FnMut([i64; N])> OutputSink for Tuples<N, F> {
+    fn output(&mut self, value: i64) {
+        self.tuple[self.len] = value;
+        self.len += 1;
+
+        if self.len == N {
+            self.len = 0;
+            (self.callback)(self.tuple);
+        }
+    }
+}
+
+/// Outputs that are ASCII codes, split into lines
+///
+/// Outputs that are not ASCII codes are written into the line as numbers, like
+/// [`Interpreter::read_line`](crate::Interpreter::read_line) does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsciiLines {
+    lines: Vec<String>,
+    partial: String,
+}
+
+impl AsciiLines {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lines completed by a newline, without it
+    #[must_use]
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Takes the completed lines, leaving the partial one
+    pub fn take_lines(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.lines)
+    }
+
+    /// Text output since the last newline, which is usually a prompt
+    #[must_use]
+    pub fn partial(&self) -> &str {
+        &self.partial
+    }
+}
+
+impl OutputSink for AsciiLines {
+    fn output(&mut self, value: i64) {
+        match ascii(value) {
+            Some('\n') => self.lines.push(std::mem::take(&mut self.partial)),
+            Some(c) => self.partial.push(c),
+            None => self.partial.push_str(&value.to_string()),
+        }
+    }
+}
+
+/// Every output passed to a closure
+#[derive(Debug, Clone)]
+pub struct Callback<F>(pub F);
+
+impl<F: FnMut(i64)> OutputSink for Callback<F> {
+    fn output(&mut self, value: i64) {
+        (self.0)(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Interpreter, State};
+
+    /// Outputs twice every value it reads, until it reads 0
+    const DOUBLE: &str = "
+        loop:   In      [x]
+                Jf      [x], end
+                Mul     [x], 2, [x]
+                Out     [x]
+                Jt      1, loop
+        end:    Halt
+        x:      .data   0
+        ";
+
+    /// Answers every output with one more than it, until the answer would be over 20
+    #[derive(Default)]
+    struct Successor {
+        outputs: Vec<i64>,
+        asked_at: Vec<usize>,
+    }
+
+    impl InputProvider for Successor {
+        fn next_input(&mut self, executor: &Executor) -> Option<i64> {
+            self.asked_at.push(executor.pc);
+
+            let next = self.outputs.last().map_or(1, |output| output + 1);
+            Some(if next > 20 { 0 } else { next })
+        }
+    }
+
+    impl OutputSink for Successor {
+        fn output(&mut self, value: i64) {
+            self.outputs.push(value);
+        }
+    }
+
+    #[test]
+    fn runs_with_a_custom_provider_and_sink() {
+        let mut interpreter = Interpreter::new(assemble(DOUBLE).unwrap());
+        let mut io = Successor::default();
+
+        assert_eq!(interpreter.run_with(&mut io).unwrap(), State::Halted);
+        assert_eq!(io.outputs, [2, 6, 14, 30]);
+        assert_eq!(io.asked_at, [0; 5]);
+        assert_eq!(interpreter.input_history(), [1, 3, 7, 15, 0]);
+        assert_eq!(interpreter.output_history(), io.outputs);
+    }
+
+    #[test]
+    fn stops_when_the_provider_runs_out() {
+        let mut interpreter = Interpreter::new(assemble(DOUBLE).unwrap());
+        interpreter.push_input(5);
+        let mut io = (ListInput::new([1, 2]), Collector::new());
+
+        // values queued on the machine are read before the provider is asked
+        assert_eq!(interpreter.run_with(&mut io).unwrap(), State::NeedsInput);
+        assert_eq!(io.1.values(), [10, 2, 4]);
+        assert_eq!(interpreter.run_with(&mut io).unwrap(), State::NeedsInput);
+
+        io.0.push(3);
+        io.0.push(0);
+        assert_eq!(interpreter.run_with(&mut io).unwrap(), State::Halted);
+        assert_eq!(io.1.into_values(), [10, 2, 4, 6]);
+    }
+
+    #[test]
+    fn groups_outputs() {
+        let mut tuples = vec![];
+        let mut lines = AsciiLines::new();
+        {
+            let mut sink = Tuples::new(|tuple: [i64; 3]| tuples.push(tuple));
+            for value in 1..=7 {
+                sink.output(value);
+            }
+            assert_eq!(sink.pending(), [7]);
+        }
+        assert_eq!(tuples, [[1, 2, 3], [4, 5, 6]]);
+
+        for value in [104, 105, 10, 62, 1000] {
+            lines.output(value);
+        }
+        assert_eq!(lines.lines(), ["hi"]);
+        assert_eq!(lines.partial(), ">1000");
+    }
+}
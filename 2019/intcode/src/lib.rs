@@ -1,6 +1,65 @@
+mod arithmetic;
+mod ascii;
+mod asm;
+mod callstack;
+#[cfg(feature = "debugger")]
+mod chain;
+mod dap;
+mod decode;
+mod disasm;
+mod error;
 mod executor;
 mod instruction;
 mod interpreter;
+mod io;
+mod limits;
+#[cfg(feature = "debugger")]
+mod machine;
+mod memory;
+mod network;
 mod opcode;
+mod pipeline;
+mod profile;
+mod recording;
+mod trace;
+mod transpile;
 
+#[cfg(feature = "transpiled")]
+extern crate self as intcode;
+
+/// Program transpiled by `intcode transpile`, from the file named by `INTCODE_TRANSPILED` when
+/// the crate is built
+#[cfg(feature = "transpiled")]
+mod transpiled {
+    include!(env!("INTCODE_TRANSPILED"));
+}
+
+pub use crate::arithmetic::Arithmetic;
+pub use crate::ascii::AsciiOutput;
+pub use crate::asm::assemble;
+pub use crate::callstack::{BacktraceEntry, CallStack, Frame, StackChange};
+#[cfg(feature = "debugger")]
+pub use crate::chain::{Chain, Routed, StepMode};
+pub use crate::dap::serve_dap;
+pub use crate::disasm::{Block, Disassembler, Disassembly};
+pub use crate::error::IntcodeError;
+pub use crate::executor::{parse_program, Compiled, Executor, Fingerprint, Snapshot, State};
+pub use crate::instruction::Instruction;
 pub use crate::interpreter::Interpreter;
+pub use crate::io::{
+    AsciiInput, AsciiLines, Callback, ChannelInput, Collector, InputProvider, ListInput,
+    OutputSink, StateInput, Tuples,
+};
+pub use crate::limits::{Limiter, Limits, Outcome};
+#[cfg(feature = "debugger")]
+pub use crate::machine::{Executed, IntcodeMachine};
+pub use crate::memory::{CellValue, Memory, DEFAULT_MEMORY_LIMIT};
+pub use crate::network::{Handler, Nat, Network, NetworkOutcome, Packet, Reply};
+pub use crate::opcode::{ModeOpt, Opcode};
+pub use crate::pipeline::Pipeline;
+pub use crate::profile::{HotLoop, Profile, Report};
+pub use crate::recording::{
+    Divergence, DivergencePoint, RecordedStep, Recorder, Recording, RunEnd,
+};
+pub use crate::trace::{MemoryWrite, StepRecord};
+pub use crate::transpile::Transpiler;
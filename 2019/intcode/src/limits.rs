@@ -0,0 +1,274 @@
+//! Stopping runs that take too long or can never finish
+
+use std::time::{Duration, Instant};
+
+use machine::LoopDetector;
+
+use crate::executor::{Executor, Fingerprint, State};
+
+/// How often the clock is read when a time limit is set
+const CLOCK_INTERVAL: u64 = 1 << 10;
+
+/// Limits for a run of a program. Nothing is limited by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Number of instructions that can be executed
+    pub instructions: Option<u64>,
+    /// Time the run can take
+    pub time: Option<Duration>,
+    /// Whether to stop when the program is caught in a loop it can never leave
+    pub detect_loops: bool,
+}
+
+impl Limits {
+    #[must_use]
+    pub const fn with_instructions(mut self, instructions: u64) -> Self {
+        self.instructions = Some(instructions);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    #[must_use]
+    pub const fn with_loop_detection(mut self) -> Self {
+        self.detect_loops = true;
+        self
+    }
+}
+
+/// How a run with [`Limits`] ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program stopped by itself
+    State(State),
+    /// The instruction budget ran out
+    BudgetExhausted,
+    /// The time limit was reached
+    TimedOut,
+    /// The program returned to a state it was in before without doing any I/O since, so it would
+    /// repeat the same `period` instructions forever
+    Looping { pc: usize, period: u64 },
+}
+
+/// Keeps track of a run against its [`Limits`], across any number of calls that execute
+/// instructions
+#[derive(Debug)]
+pub struct Limiter {
+    limits: Limits,
+    start: Instant,
+    executed: u64,
+    loops: LoopDetector<Fingerprint>,
+    /// Number of instructions executed when the loop detector last saw the machine, so that a
+    /// machine that waits for input is not taken for one that loops
+    loops_checked: Option<u64>,
+}
+
+impl Limiter {
+    /// Starts the clock on a run
+    #[must_use]
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            start: Instant::now(),
+            executed: 0,
+            loops: LoopDetector::new(),
+            loops_checked: None,
+        }
+    }
+
+    /// Number of instructions that have been executed
+    #[must_use]
+    pub const fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Checks the limits before the executor runs its next instruction, returning why the run has
+    /// to stop if it does
+    ///
+    /// The step that follows has to be passed to [`Limiter::count`].
+    pub fn check(&mut self, executor: &Executor) -> Option<Outcome> {
+        if self
+            .limits
+            .instructions
+            .is_some_and(|budget| self.executed >= budget)
+        {
+            return Some(Outcome::BudgetExhausted);
+        }
+
+        if let Some(time) = self.limits.time {
+            if self.executed.is_multiple_of(CLOCK_INTERVAL) && self.start.elapsed() >= time {
+                return Some(Outcome::TimedOut);
+            }
+        }
+
+        if self.limits.detect_loops && self.loops_checked != Some(self.executed) {
+            self.loops_checked = Some(self.executed);
+
+            if let Some(period) = self.loops.check(executor.fingerprint()) {
+                return Some(Outcome::Looping {
+                    pc: executor.pc,
+                    period,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Counts the step taken after the last check, which did not execute anything if the program
+    /// stopped for input
+    pub fn count(&mut self, state: Option<State>) {
+        if state != Some(State::NeedsInput) {
+            self.executed += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Interpreter};
+
+    /// Jumps to itself forever
+    const SPIN: &str = "1105,1,0";
+
+    /// Reads a value and outputs it, until it reads 0
+    const ECHO: &str = "3,9,4,9,1005,9,0,99,0,0";
+
+    fn run(program: &str, limits: Limits) -> (Outcome, u64) {
+        let mut interpreter = Interpreter::parse(program).unwrap();
+        let mut limiter = Limiter::new(limits);
+
+        let outcome = interpreter.run_limited(&mut limiter).unwrap();
+        (outcome, limiter.executed())
+    }
+
+    #[test]
+    fn budget_counts_executed_instructions() {
+        let limits = Limits::default().with_instructions(5);
+        assert_eq!(run(SPIN, limits), (Outcome::BudgetExhausted, 5));
+
+        // halting with the last of the budget is not running out of it
+        let limits = Limits::default().with_instructions(2);
+        assert_eq!(
+            run("1101,2,3,0,99", limits),
+            (Outcome::State(State::Halted), 2)
+        );
+    }
+
+    #[test]
+    fn waiting_for_input_does_not_use_up_the_budget() {
+        let mut interpreter = Interpreter::parse(ECHO).unwrap();
+        let mut limiter = Limiter::new(Limits::default().with_instructions(4));
+
+        for _ in 0..10 {
+            assert_eq!(
+                interpreter.run_limited(&mut limiter).unwrap(),
+                Outcome::State(State::NeedsInput)
+            );
+        }
+        assert_eq!(limiter.executed(), 0);
+
+        interpreter.push_input(7);
+        assert_eq!(
+            interpreter.run_limited(&mut limiter).unwrap(),
+            Outcome::State(State::Output(7))
+        );
+        assert_eq!(limiter.executed(), 2);
+    }
+
+    #[test]
+    fn times_out() {
+        let limits = Limits::default().with_time(Duration::ZERO);
+        assert_eq!(run(SPIN, limits), (Outcome::TimedOut, 0));
+
+        // the clock is only read every so often, on a count of executed instructions
+        let limits = Limits::default().with_time(Duration::from_millis(20));
+        let (outcome, executed) = run(SPIN, limits);
+        assert_eq!(outcome, Outcome::TimedOut);
+        assert!(executed.is_multiple_of(CLOCK_INTERVAL), "{executed}");
+    }
+
+    #[test]
+    fn detects_loops() {
+        let limits = Limits::default().with_loop_detection();
+        assert_eq!(run(SPIN, limits).0, Outcome::Looping { pc: 0, period: 1 });
+
+        // counts down to 0 and then spins between the two jumps
+        let program = assemble(
+            "
+            loop:   Add     [count], -1, [count]
+                    Jt      [count], loop
+            spin:   Jt      1, back
+            back:   Jt      1, spin
+            count:  .data   100
+            ",
+        )
+        .unwrap();
+        let mut interpreter = Interpreter::new(program);
+        let outcome = interpreter.run_limited(&mut Limiter::new(limits)).unwrap();
+        assert!(
+            matches!(
+                outcome,
+                Outcome::Looping {
+                    pc: 7 | 10,
+                    period: 2
+                }
+            ),
+            "{outcome:?}"
+        );
+    }
+
+    #[test]
+    fn changing_code_is_not_a_loop() {
+        // counts up the immediate operand of its own comparison until it reaches 1000
+        let program = assemble(
+            "
+            loop:   Add     [step + 1], 1, [step + 1]
+            step:   Lt      0, 1000, [flag]
+                    Jt      [flag], loop
+                    Halt
+            flag:   .data   0
+            ",
+        )
+        .unwrap();
+
+        let mut interpreter = Interpreter::new(program);
+        let mut limiter = Limiter::new(Limits::default().with_loop_detection());
+        assert_eq!(
+            interpreter.run_limited(&mut limiter).unwrap(),
+            Outcome::State(State::Halted)
+        );
+        assert_eq!(limiter.executed(), 3 * 1000 + 1);
+    }
+
+    #[test]
+    fn waiting_for_input_is_not_a_loop() {
+        let mut interpreter = Interpreter::parse(ECHO).unwrap();
+        let mut limiter = Limiter::new(Limits::default().with_loop_detection());
+
+        for value in [1, 2, 3, 0] {
+            for _ in 0..3 {
+                assert_eq!(
+                    interpreter.run_limited(&mut limiter).unwrap(),
+                    Outcome::State(State::NeedsInput)
+                );
+            }
+
+            interpreter.push_input(value);
+            assert_eq!(
+                interpreter.run_limited(&mut limiter).unwrap(),
+                Outcome::State(State::Output(value))
+            );
+        }
+
+        assert_eq!(
+            interpreter.run_limited(&mut limiter).unwrap(),
+            Outcome::State(State::Halted)
+        );
+    }
+}
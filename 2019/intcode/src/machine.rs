@@ -0,0 +1,188 @@
+use core::fmt;
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use debugger::{Machine, Stepped, TextView};
+
+use crate::{CallStack, Instruction, IntcodeError, Interpreter, StackChange, StepRecord};
+
+/// An executed intcode instruction, with what it did to the inferred call stack
+#[derive(Debug)]
+pub struct Executed {
+    pub record: StepRecord,
+    pub stack: Option<StackChange>,
+}
+
+impl fmt::Display for Executed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.record)
+    }
+}
+
+/// Intcode interpreter, with what the debugger infers from the instructions it executes
+#[derive(Debug)]
+pub struct IntcodeMachine {
+    pub interpreter: Interpreter,
+    /// Addresses of every instruction executed so far, used as disassembly hints
+    visited: BTreeSet<usize>,
+    /// Calls inferred from the instructions executed so far
+    call_stack: CallStack,
+}
+
+impl IntcodeMachine {
+    #[must_use]
+    pub fn new(interpreter: Interpreter) -> Self {
+        Self {
+            interpreter,
+            visited: BTreeSet::new(),
+            call_stack: CallStack::default(),
+        }
+    }
+}
+
+impl Machine for IntcodeMachine {
+    type Record = Executed;
+    type Error = IntcodeError;
+
+    const MNEMONICS: &'static [&'static str] = &[
+        "add", "mul", "in", "out", "jt", "jf", "lt", "eq", "rel", "halt",
+    ];
+
+    fn step(&mut self) -> Result<Stepped<Executed>, IntcodeError> {
+        let pc = self.interpreter.executor.pc;
+        let (state, record) = self.interpreter.trace_step()?;
+
+        let executed = record.map(|record| {
+            self.visited.insert(pc);
+            let stack = self.call_stack.observe(
+                &record,
+                self.interpreter.executor.pc,
+                &self.interpreter.executor.memory,
+            );
+
+            Executed { record, stack }
+        });
+
+        Ok((state, executed))
+    }
+
+    fn undo(&mut self, executed: &Executed) {
+        self.interpreter.undo(&executed.record);
+        if let Some(change) = &executed.stack {
+            self.call_stack.revert(change.clone());
+        }
+    }
+
+    fn pc(&self) -> usize {
+        self.interpreter.executor.pc
+    }
+
+    fn registers(&self) -> Vec<(&'static str, i64)> {
+        vec![("Rel", self.interpreter.executor.rel as i64)]
+    }
+
+    fn memory_len(&self) -> usize {
+        self.interpreter.executor.memory.len()
+    }
+
+    fn cell(&self, addr: usize) -> i64 {
+        self.interpreter.executor.memory.get(addr)
+    }
+
+    fn disassemble(&self, addr: usize) -> Option<(String, usize)> {
+        let instruction = Instruction::fetch(&self.interpreter.executor.memory, addr).ok()?;
+
+        Some((instruction.to_string(), instruction.opcode.len()))
+    }
+
+    fn opcode(&self) -> Option<&'static str> {
+        let executor = &self.interpreter.executor;
+        let instruction = Instruction::fetch(&executor.memory, executor.pc).ok()?;
+
+        Some(instruction.opcode.mnemonic())
+    }
+
+    fn outputs(&self) -> &[i64] {
+        self.interpreter.output_history()
+    }
+
+    fn listing(&self, view: TextView) -> Option<String> {
+        Some(match view {
+            TextView::Linear => self.interpreter.to_string(),
+            TextView::ControlFlow => self
+                .interpreter
+                .disassemble(self.visited.iter().copied())
+                .to_string(),
+        })
+    }
+
+    fn inputs(&self) -> Option<(Vec<i64>, &[i64])> {
+        let queued = self.interpreter.executor.queued_input();
+
+        Some((
+            queued.iter().copied().collect(),
+            self.interpreter.input_history(),
+        ))
+    }
+
+    fn push_input(&mut self, value: i64) -> bool {
+        self.interpreter.push_input(value);
+        true
+    }
+
+    fn reads(executed: &Executed) -> Vec<usize> {
+        executed
+            .record
+            .reads
+            .iter()
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    fn write(executed: &Executed) -> Option<usize> {
+        executed.record.write.as_ref().map(|write| write.addr)
+    }
+
+    fn next_accesses(&self) -> (Vec<usize>, Option<usize>) {
+        self.interpreter
+            .executor
+            .next_accesses()
+            .unwrap_or_default()
+    }
+
+    fn rel(&self) -> Option<usize> {
+        usize::try_from(self.interpreter.executor.rel).ok()
+    }
+
+    fn call_depth(&self) -> usize {
+        self.call_stack.depth()
+    }
+
+    fn backtrace(&self) -> Vec<String> {
+        self.call_stack
+            .backtrace(self.interpreter.executor.pc)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn executions(&self) -> Option<&HashMap<usize, u64>> {
+        self.interpreter
+            .profile()
+            .map(|profile| &profile.executions)
+    }
+
+    fn enable_profiling(&mut self) -> bool {
+        self.interpreter.executor.enable_profiling();
+        true
+    }
+
+    fn fingerprint(&self) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        self.interpreter.executor.fingerprint().hash(&mut hasher);
+
+        Some(hasher.finish())
+    }
+}
@@ -1,23 +1,53 @@
-use std::{env, process::exit};
+use std::{env, fs, process::ExitCode};
 
 use debugger::DebuggerResult;
+use intcode::{IntcodeMachine, Interpreter};
 
-mod debugger;
-mod utils;
+mod cli;
 
 #[tokio::main]
-async fn main() -> DebuggerResult<()> {
+async fn main() -> DebuggerResult<ExitCode> {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
-        println!("Input file expected as argument");
-        exit(1);
+        println!("Input file expected as argument\n\n{}", cli::USAGE);
+        return Ok(ExitCode::FAILURE);
     }
 
-    // let mut interpreter = intcode::Interpreter::from_file(&args[1], vec![]).await?;
-    // interpreter.exec().await?;
-    // println!("{:?}", interpreter.output_history());
-    // Ok(())
+    match args[1].as_str() {
+        "asm" => {
+            let Some(file) = args.get(2) else {
+                println!("Assembly file expected as argument");
+                return Ok(ExitCode::FAILURE);
+            };
 
-    debugger::start(&args[1], vec![]).await
+            let program = intcode::assemble(&fs::read_to_string(file)?)?;
+            println!(
+                "{}",
+                program
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+
+            Ok(ExitCode::SUCCESS)
+        }
+        "chain" => cli::chain(&args[2..]).await,
+        "run" => cli::run(&args[2..]),
+        "play" => cli::play(&args[2..]),
+        "replay" => cli::replay(&args[2..]),
+        "diff" => cli::diff(&args[2..]),
+        "transpile" => cli::transpile(&args[2..]),
+        "dap" => Ok(intcode::serve_dap()?),
+        "-h" | "--help" => {
+            println!("{}", cli::USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        file => {
+            let interpreter = Interpreter::from_file(file, vec![]).await?;
+            debugger::debug(IntcodeMachine::new(interpreter)).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
 }
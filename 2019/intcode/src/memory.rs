@@ -0,0 +1,356 @@
+use core::fmt;
+use std::{
+    collections::BTreeMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Index,
+};
+
+use num_bigint::BigInt;
+
+use crate::arithmetic::low_bits;
+
+/// Number of cells in a page of sparse memory
+const PAGE_SIZE: usize = 1 << 12;
+
+/// How far past the end of dense memory a write can land and still grow it instead of allocating
+/// a sparse page
+const DENSE_GROWTH: usize = 1 << 16;
+
+/// Default limit on the number of cells a program can allocate, which is 1 GiB
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 27;
+
+/// Program memory, which reads as 0 everywhere that has not been written
+///
+/// Cells from address 0 up to a little past the furthest write near the program image are kept
+/// in one contiguous vector. Writes further out go to pages that are only allocated when
+/// something other than 0 is first written to them, so a single far write does not allocate
+/// everything in between.
+///
+/// Values that do not fit in 64 bits, which only big integer arithmetic produces, are kept on the
+/// side, while their cell holds the low 64 bits.
+#[derive(Debug, Clone)]
+pub struct Memory {
+    dense: Vec<i64>,
+    pages: BTreeMap<usize, Box<[i64]>>,
+    /// One past the highest address with storage that was written to or was part of the program
+    len: usize,
+    limit: usize,
+    /// Combined [`cell_hash`] of every cell
+    hash: u64,
+    /// Exact values of the cells that hold a big integer
+    big: BTreeMap<usize, BigInt>,
+}
+
+/// Exact value of a memory cell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellValue {
+    Int(i64),
+    /// Value that does not fit in 64 bits, which only big integer arithmetic produces
+    Big(BigInt),
+}
+
+impl fmt::Display for CellValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Big(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Hash of a single cell, where cells holding 0 hash to 0 so unwritten memory does not count
+fn cell_hash(addr: usize, value: i64) -> u64 {
+    if value == 0 {
+        return 0;
+    }
+
+    // splitmix64 finalizer
+    let mut x = (addr as u64).rotate_left(32) ^ (value as u64);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Hash of the full value of a cell holding a big integer, on top of the [`cell_hash`] of its
+/// low 64 bits
+fn big_hash(addr: usize, value: &BigInt) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (addr, value).hash(&mut hasher);
+
+    hasher.finish()
+}
+
+impl Memory {
+    #[must_use]
+    pub fn new(program: Vec<i64>) -> Self {
+        let hash = program
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (addr, value)| hash ^ cell_hash(addr, *value));
+
+        Self {
+            len: program.len(),
+            dense: program,
+            pages: BTreeMap::new(),
+            limit: DEFAULT_MEMORY_LIMIT,
+            hash,
+            big: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the number of cells the program can allocate before writes fail
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// One past the highest address that has been written to or was part of the program
+    ///
+    /// Writing 0 to a cell far past the program that has no storage yet leaves it unallocated,
+    /// and does not count as a write here either.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of cells backed by storage
+    pub fn allocated(&self) -> usize {
+        self.dense.len() + self.pages.len() * PAGE_SIZE
+    }
+
+    /// Contiguous cells starting at address 0, which hold the program image
+    pub fn dense(&self) -> &[i64] {
+        &self.dense
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        if addr < self.dense.len() {
+            return self.dense[addr];
+        }
+
+        self.pages
+            .get(&(addr / PAGE_SIZE))
+            .map_or(0, |page| page[addr % PAGE_SIZE])
+    }
+
+    /// Writes a value, or returns `None` without writing if that would allocate more cells than
+    /// the limit allows
+    pub fn set(&mut self, addr: usize, value: i64) -> Option<()> {
+        if !self.big.is_empty() {
+            if let Some(old) = self.big.remove(&addr) {
+                self.hash ^= big_hash(addr, &old);
+            }
+        }
+
+        // unallocated cells already read as 0, so writing 0 to them changes nothing
+        let far = addr >= self.dense.len() + DENSE_GROWTH;
+        if value == 0 && far && !self.pages.contains_key(&(addr / PAGE_SIZE)) {
+            return Some(());
+        }
+
+        let cell = self.cell(addr)?;
+        let old = *cell;
+        *cell = value;
+        self.hash ^= cell_hash(addr, old) ^ cell_hash(addr, value);
+
+        Some(())
+    }
+
+    /// Exact value of a cell, if it holds a big integer
+    pub fn get_big(&self, addr: usize) -> Option<&BigInt> {
+        if self.big.is_empty() {
+            return None;
+        }
+
+        self.big.get(&addr)
+    }
+
+    /// Writes a value of any size, like [`Memory::set`] if it fits in 64 bits
+    pub fn set_big(&mut self, addr: usize, value: BigInt) -> Option<()> {
+        if let Ok(value) = i64::try_from(&value) {
+            return self.set(addr, value);
+        }
+
+        self.set(addr, low_bits(&value))?;
+        self.hash ^= big_hash(addr, &value);
+        self.big.insert(addr, value);
+
+        Some(())
+    }
+
+    /// Exact value of a cell, big integers included
+    pub fn value(&self, addr: usize) -> CellValue {
+        match self.get_big(addr) {
+            Some(value) => CellValue::Big(value.clone()),
+            None => CellValue::Int(self.get(addr)),
+        }
+    }
+
+    /// Writes an exact value, like [`Memory::set`] or [`Memory::set_big`]
+    pub fn set_value(&mut self, addr: usize, value: CellValue) -> Option<()> {
+        match value {
+            CellValue::Int(value) => self.set(addr, value),
+            CellValue::Big(value) => self.set_big(addr, value),
+        }
+    }
+
+    /// Hash of the contents of memory, which is kept up to date by every write
+    ///
+    /// Big integers count with their full value.
+    pub const fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Storage for a cell, allocating it if needed
+    fn cell(&mut self, addr: usize) -> Option<&mut i64> {
+        if addr < self.dense.len() {
+            return Some(&mut self.dense[addr]);
+        }
+
+        if addr < self.dense.len() + DENSE_GROWTH {
+            self.grow_dense(addr + 1)?;
+            self.len = self.len.max(addr + 1);
+
+            return Some(&mut self.dense[addr]);
+        }
+
+        let page = addr / PAGE_SIZE;
+        if !self.pages.contains_key(&page) {
+            if self.allocated() + PAGE_SIZE > self.limit {
+                return None;
+            }
+
+            self.pages
+                .insert(page, vec![0; PAGE_SIZE].into_boxed_slice());
+        }
+
+        self.len = self.len.max(addr + 1);
+        self.pages
+            .get_mut(&page)
+            .map(|page| &mut page[addr % PAGE_SIZE])
+    }
+
+    /// Extends dense memory to `len` cells, moving in the contents of any sparse pages it now
+    /// covers
+    fn grow_dense(&mut self, len: usize) -> Option<()> {
+        if self.allocated() + (len - self.dense.len()) > self.limit {
+            return None;
+        }
+
+        let start = self.dense.len();
+        self.dense.resize(len, 0);
+
+        let covered = self
+            .pages
+            .range(start / PAGE_SIZE..=(len - 1) / PAGE_SIZE)
+            .map(|(page, _)| *page)
+            .collect::<Vec<_>>();
+
+        for page in covered {
+            let page_start = page * PAGE_SIZE;
+            let cells = &self.pages[&page];
+
+            for addr in start.max(page_start)..len.min(page_start + PAGE_SIZE) {
+                self.dense[addr] = cells[addr - page_start];
+            }
+
+            // partly covered pages stay around for the cells past the end of dense memory
+            if page_start + PAGE_SIZE <= len {
+                self.pages.remove(&page);
+            }
+        }
+
+        Some(())
+    }
+
+    /// Addresses and values of every cell that is not 0, in order of address
+    ///
+    /// Only allocated storage is visited, so this takes time in proportion to the memory in use
+    /// rather than to [`Memory::len`].
+    pub fn nonzero(&self) -> impl Iterator<Item = (usize, i64)> + '_ {
+        let dense = self.dense.iter().copied().enumerate();
+        let pages = self.pages.iter().flat_map(|(page, cells)| {
+            cells
+                .iter()
+                .enumerate()
+                .map(move |(i, value)| (page * PAGE_SIZE + i, *value))
+                .filter(|(addr, _)| *addr >= self.dense.len())
+        });
+
+        dense.chain(pages).filter(|(_, value)| *value != 0)
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+    fn index(&self, i: usize) -> &i64 {
+        if i < self.dense.len() {
+            return &self.dense[i];
+        }
+
+        self.pages
+            .get(&(i / PAGE_SIZE))
+            .map_or(&0, |page| &page[i % PAGE_SIZE])
+    }
+}
+
+impl fmt::Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_rows = |f: &mut fmt::Formatter, cells: &[i64]| -> fmt::Result {
+            for line in cells.chunks(8) {
+                let mut out_line = vec![];
+                for val in line {
+                    out_line.push(format!("{val:8}\t"));
+                }
+                writeln!(f, "{}", out_line.join("\t"))?;
+            }
+
+            Ok(())
+        };
+
+        write_rows(f, &self.dense)?;
+
+        for (page, cells) in &self.pages {
+            let start = (page * PAGE_SIZE).max(self.dense.len());
+            let end = ((page + 1) * PAGE_SIZE).min(self.len);
+
+            if start < end {
+                writeln!(f, "... {start:#x}")?;
+                write_rows(f, &cells[start - page * PAGE_SIZE..end - page * PAGE_SIZE])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_writes_of_0_change_nothing() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        let hash = memory.hash();
+
+        memory.set(1 << 40, 0).unwrap();
+        assert_eq!(memory.len(), 3);
+        assert_eq!(memory.allocated(), 3);
+        assert_eq!(memory.hash(), hash);
+
+        memory.set(1 << 40, 7).unwrap();
+        assert_eq!(memory.len(), (1 << 40) + 1);
+        assert_eq!(memory.nonzero().last(), Some((1 << 40, 7)));
+
+        // once the page is allocated, writing 0 back is an ordinary write
+        memory.set(1 << 40, 0).unwrap();
+        assert_eq!(memory.len(), (1 << 40) + 1);
+        assert_eq!(memory.hash(), hash);
+    }
+}
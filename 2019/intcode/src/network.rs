@@ -0,0 +1,325 @@
+//! Machines that talk to each other by sending addressed packets
+//!
+//! Every machine is booted with its address as its first input. It then sends a packet by
+//! producing three outputs, the destination address followed by `x` and `y`, and receives one by
+//! reading `x` and `y` from its input. A machine that reads input while nothing has been sent to
+//! it reads `-1`.
+//!
+//! The network runs in rounds. In each round, every machine that has not halted takes a turn in
+//! order of address, so runs are reproducible. A turn delivers everything sent to the machine
+//! since its last turn and then runs it until it needs more input.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{error::IntcodeError, executor::State, interpreter::Interpreter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// What a [`Handler`] wants the network to do after it has seen an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    Continue,
+    /// Send a packet
+    Send(Packet),
+    /// Stop running the network, with a result for whoever is running it
+    Stop(i64),
+}
+
+/// Something other than a machine that listens on an address
+pub trait Handler {
+    /// Called with every packet sent to the address of the handler
+    fn receive(&mut self, packet: Packet) -> Reply;
+
+    /// Called when every machine is waiting for a packet and no packets are on their way
+    fn idle(&mut self) -> Reply {
+        Reply::Continue
+    }
+}
+
+impl<F: FnMut(Packet) -> Reply> Handler for F {
+    fn receive(&mut self, packet: Packet) -> Reply {
+        self(packet)
+    }
+}
+
+/// Remembers the last packet sent to it, and sends it on to address 0 whenever the network is
+/// idle
+///
+/// Before each packet is sent on, `stop` is asked whether the network should stop instead, in
+/// which case it stops with the `y` of the packet.
+#[derive(Debug)]
+pub struct Nat<F> {
+    last: Option<Packet>,
+    stop: F,
+}
+
+impl<F: FnMut(Packet) -> bool> Nat<F> {
+    #[must_use]
+    pub const fn new(stop: F) -> Self {
+        Self { last: None, stop }
+    }
+}
+
+impl<F: FnMut(Packet) -> bool> Handler for Nat<F> {
+    fn receive(&mut self, packet: Packet) -> Reply {
+        self.last = Some(packet);
+        Reply::Continue
+    }
+
+    fn idle(&mut self) -> Reply {
+        let Some(packet) = self.last else {
+            return Reply::Continue;
+        };
+
+        if (self.stop)(packet) {
+            return Reply::Stop(packet.y);
+        }
+
+        Reply::Send(Packet { dest: 0, ..packet })
+    }
+}
+
+/// Why [`Network::run`] returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkOutcome {
+    /// A handler stopped the network
+    Stopped(i64),
+    /// Every machine is waiting for a packet, and no handler sent one when told about it
+    Idle,
+    /// Every machine has halted
+    Halted,
+}
+
+struct Node {
+    interpreter: Interpreter,
+    queue: VecDeque<Packet>,
+    /// Outputs of a packet that has only partly been produced
+    pending: Vec<i64>,
+    halted: bool,
+}
+
+pub struct Network {
+    nodes: Vec<Node>,
+    handlers: BTreeMap<i64, Box<dyn Handler>>,
+    /// Packets sent to addresses with neither a machine nor a handler
+    pub undeliverable: Vec<Packet>,
+}
+
+#[allow(clippy::missing_errors_doc)]
+impl Network {
+    /// Boots `size` copies of a program, with addresses from 0 up to `size`
+    #[must_use]
+    pub fn new(program: &[i64], size: usize) -> Self {
+        let nodes = (0..size)
+            .map(|addr| {
+                let mut interpreter = Interpreter::new(program.to_vec());
+                interpreter.push_input(i64::try_from(addr).expect("Address fits in i64"));
+
+                Node {
+                    interpreter,
+                    queue: VecDeque::new(),
+                    pending: vec![],
+                    halted: false,
+                }
+            })
+            .collect();
+
+        Self {
+            nodes,
+            handlers: BTreeMap::new(),
+            undeliverable: vec![],
+        }
+    }
+
+    /// Makes a handler listen on an address, which takes precedence over any machine there
+    #[must_use]
+    pub fn with_handler(mut self, addr: i64, handler: impl Handler + 'static) -> Self {
+        self.handlers.insert(addr, Box::new(handler));
+        self
+    }
+
+    /// Machine at an address, to inspect its state
+    #[must_use]
+    pub fn machine(&self, addr: usize) -> Option<&Interpreter> {
+        self.nodes.get(addr).map(|node| &node.interpreter)
+    }
+
+    /// Sends a packet as if a machine had sent it, returning the result if a handler stopped the
+    /// network
+    ///
+    /// Packets that handlers send in reply are delivered in the order they were sent, before this
+    /// returns.
+    pub fn send(&mut self, packet: Packet) -> Option<i64> {
+        let mut packets = VecDeque::from([packet]);
+
+        while let Some(packet) = packets.pop_front() {
+            if let Some(handler) = self.handlers.get_mut(&packet.dest) {
+                match handler.receive(packet) {
+                    Reply::Continue => {}
+                    Reply::Send(packet) => packets.push_back(packet),
+                    Reply::Stop(result) => return Some(result),
+                }
+                continue;
+            }
+
+            match usize::try_from(packet.dest)
+                .ok()
+                .and_then(|addr| self.nodes.get_mut(addr))
+            {
+                Some(node) => node.queue.push_back(packet),
+                None => self.undeliverable.push(packet),
+            }
+        }
+
+        None
+    }
+
+    /// Runs rounds until a handler stops the network, or it goes idle or halts
+    pub fn run(&mut self) -> Result<NetworkOutcome, IntcodeError> {
+        loop {
+            let mut idle = true;
+
+            for addr in 0..self.nodes.len() {
+                let node = &mut self.nodes[addr];
+                if node.halted {
+                    continue;
+                }
+
+                if node.queue.is_empty() {
+                    node.interpreter.push_input(-1);
+                } else {
+                    idle = false;
+                    for packet in node.queue.drain(..) {
+                        node.interpreter.push_input(packet.x);
+                        node.interpreter.push_input(packet.y);
+                    }
+                }
+
+                let mut sent = vec![];
+                loop {
+                    match node.interpreter.run()? {
+                        State::NeedsInput => break,
+                        State::Halted => {
+                            node.halted = true;
+                            break;
+                        }
+                        State::Output(value) => {
+                            node.pending.push(value);
+
+                            if let [dest, x, y] = node.pending[..] {
+                                sent.push(Packet { dest, x, y });
+                                node.pending.clear();
+                            }
+                        }
+                    }
+                }
+
+                idle &= sent.is_empty();
+                for packet in sent {
+                    if let Some(result) = self.send(packet) {
+                        return Ok(NetworkOutcome::Stopped(result));
+                    }
+                }
+            }
+
+            if self.nodes.iter().all(|node| node.halted) {
+                return Ok(NetworkOutcome::Halted);
+            }
+
+            if idle {
+                if let Some(outcome) = self.wake() {
+                    return Ok(outcome);
+                }
+            }
+        }
+    }
+
+    /// Tells the handlers that the network is idle, returning how the run ends if none of them
+    /// sends a packet
+    fn wake(&mut self) -> Option<NetworkOutcome> {
+        let mut replies = vec![];
+        for handler in self.handlers.values_mut() {
+            replies.push(handler.idle());
+        }
+
+        let mut woken = false;
+        for reply in replies {
+            match reply {
+                Reply::Continue => {}
+                Reply::Send(packet) => {
+                    woken = true;
+                    if let Some(result) = self.send(packet) {
+                        return Some(NetworkOutcome::Stopped(result));
+                    }
+                }
+                Reply::Stop(result) => return Some(NetworkOutcome::Stopped(result)),
+            }
+        }
+
+        (!woken).then_some(NetworkOutcome::Idle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handlers_reply_to_each_other() {
+        // counts x down to 0 while bouncing between addresses 1 and 2, far deeper than the stack
+        // would allow if every reply was a call
+        let bounce = |dest| {
+            move |packet: Packet| match packet.x {
+                0 => Reply::Stop(packet.y),
+                x => Reply::Send(Packet {
+                    dest,
+                    x: x - 1,
+                    ..packet
+                }),
+            }
+        };
+        let mut network = Network::new(&[], 0)
+            .with_handler(1, bounce(2))
+            .with_handler(2, bounce(1));
+
+        assert_eq!(
+            network.send(Packet {
+                dest: 1,
+                x: 1_000_000,
+                y: 7
+            }),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn nat_stops_when_told() {
+        // sends (255, 3, 42) once, then reads input forever
+        let program = crate::parse_program("3,100,104,255,104,3,104,42,3,101,1105,1,8").unwrap();
+
+        let mut sent = 0;
+        let nat = Nat::new(move |packet: Packet| {
+            assert_eq!(
+                packet,
+                Packet {
+                    dest: 255,
+                    x: 3,
+                    y: 42
+                }
+            );
+            sent += 1;
+            sent == 3
+        });
+        let mut network = Network::new(&program, 1).with_handler(255, nat);
+
+        assert_eq!(network.run().unwrap(), NetworkOutcome::Stopped(42));
+        // the packet was sent on twice before the network went idle a third time and stopped
+        let inputs = network.machine(0).unwrap().input_history();
+        assert_eq!(inputs.iter().filter(|value| **value == 42).count(), 2);
+    }
+}
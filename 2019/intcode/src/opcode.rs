@@ -1,6 +1,8 @@
+use core::str::FromStr;
+
 use num_enum::TryFromPrimitive;
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum ModeOpt {
     #[default]
     Position = 0,
@@ -20,7 +22,7 @@ impl TryFrom<u32> for ModeOpt {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Opcode {
     Add = 1,
@@ -35,7 +37,9 @@ pub enum Opcode {
     Halt = 99,
 }
 
+#[allow(clippy::len_without_is_empty)]
 impl Opcode {
+    /// Number of cells the instruction takes up, including the opcode itself
     pub const fn len(&self) -> usize {
         match self {
             Self::Add | Self::Mul | Self::Lt | Self::Eq => 4,
@@ -44,4 +48,51 @@ impl Opcode {
             Self::Halt => 1,
         }
     }
+
+    /// Name of the instruction in assembly, which [`Opcode::from_str`] parses back
+    pub const fn mnemonic(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Mul => "mul",
+            Self::In => "in",
+            Self::Out => "out",
+            Self::Jt => "jt",
+            Self::Jf => "jf",
+            Self::Lt => "lt",
+            Self::Eq => "eq",
+            Self::Rel => "rel",
+            Self::Halt => "halt",
+        }
+    }
+
+    /// Indices of the parameters the instruction reads from memory, unless they are in immediate
+    /// mode, and of the parameter it writes its result to
+    pub const fn accesses(self) -> (&'static [usize], Option<usize>) {
+        match self {
+            Self::Add | Self::Mul | Self::Lt | Self::Eq => (&[0, 1], Some(2)),
+            Self::In => (&[], Some(0)),
+            Self::Out | Self::Rel => (&[0], None),
+            Self::Jt | Self::Jf => (&[0, 1], None),
+            Self::Halt => (&[], None),
+        }
+    }
+}
+
+impl FromStr for Opcode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "add" => Ok(Self::Add),
+            "mul" => Ok(Self::Mul),
+            "in" => Ok(Self::In),
+            "out" => Ok(Self::Out),
+            "jt" => Ok(Self::Jt),
+            "jf" => Ok(Self::Jf),
+            "lt" => Ok(Self::Lt),
+            "eq" => Ok(Self::Eq),
+            "rel" => Ok(Self::Rel),
+            "halt" => Ok(Self::Halt),
+            _ => Err(format!("Unknown instruction `{s}`")),
+        }
+    }
 }
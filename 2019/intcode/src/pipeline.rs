@@ -0,0 +1,247 @@
+use crate::{error::IntcodeError, executor::State, interpreter::Interpreter};
+
+/// Machines chained so that the output of each one is the input of the next
+///
+/// With feedback, the output of the last machine is also fed back into the first one. Stages take
+/// turns in order, each running until it needs input it does not have yet, until every stage has
+/// halted. Values sent to a stage that has already halted are dropped, so a stage halting early
+/// does not block the others.
+pub struct Pipeline {
+    stages: Vec<Interpreter>,
+    feedback: bool,
+}
+
+#[allow(clippy::missing_errors_doc)]
+impl Pipeline {
+    /// Chains machines in the order they are given, with whatever input they already have queued
+    #[must_use]
+    pub fn new(stages: Vec<Interpreter>) -> Self {
+        Self {
+            stages,
+            feedback: false,
+        }
+    }
+
+    /// Chains copies of a program, giving each its own phase setting as its first input
+    #[must_use]
+    pub fn from_phases(program: &[i64], phases: &[i64]) -> Self {
+        Self::new(
+            phases
+                .iter()
+                .map(|phase| {
+                    let mut stage = Interpreter::new(program.to_vec());
+                    stage.push_input(*phase);
+                    stage
+                })
+                .collect(),
+        )
+    }
+
+    /// Feeds the output of the last stage back into the first
+    #[must_use]
+    pub const fn with_feedback(mut self) -> Self {
+        self.feedback = true;
+        self
+    }
+
+    #[must_use]
+    pub fn stages(&self) -> &[Interpreter] {
+        &self.stages
+    }
+
+    /// Whether the output of the last stage is fed back into the first
+    #[must_use]
+    pub const fn feedback(&self) -> bool {
+        self.feedback
+    }
+
+    /// Takes the stages out of the pipeline, to run them some other way
+    #[must_use]
+    pub fn into_stages(self) -> Vec<Interpreter> {
+        self.stages
+    }
+
+    /// Sends input to the first stage and runs the pipeline to completion, returning the last
+    /// value the last stage output
+    ///
+    /// Fails with [`IntcodeError::Deadlock`] if the stages that have not halted all wait for input
+    /// that none of them will send. The run can be picked up again by calling this with more input.
+    pub fn run(
+        &mut self,
+        input: impl IntoIterator<Item = i64>,
+    ) -> Result<Option<i64>, IntcodeError> {
+        let Some(first) = self.stages.first_mut() else {
+            return Ok(None);
+        };
+
+        for value in input {
+            first.push_input(value);
+        }
+
+        let len = self.stages.len();
+        let mut halted = vec![false; len];
+        let mut last_output = None;
+
+        loop {
+            let mut progress = false;
+
+            for i in 0..len {
+                if halted[i] {
+                    continue;
+                }
+
+                let next = if i + 1 < len {
+                    Some(i + 1)
+                } else {
+                    self.feedback.then_some(0)
+                };
+
+                loop {
+                    match self.stages[i].run()? {
+                        State::NeedsInput => break,
+                        State::Halted => {
+                            halted[i] = true;
+                            progress = true;
+                            break;
+                        }
+                        State::Output(value) => {
+                            progress = true;
+                            if i + 1 == len {
+                                last_output = Some(value);
+                            }
+
+                            if let Some(next) = next.filter(|next| !halted[*next]) {
+                                self.stages[next].push_input(value);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if halted.iter().all(|halted| *halted) {
+                return Ok(last_output);
+            }
+
+            if !progress {
+                let waiting = (0..len).filter(|i| !halted[*i]).collect();
+                return Err(IntcodeError::Deadlock { waiting });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    /// Examples from the first part of 2019/07, with their phases and the signal they produce
+    const CHAINED: [(&str, [i64; 5], i64); 3] = [
+        (
+            "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0",
+            [4, 3, 2, 1, 0],
+            43210,
+        ),
+        (
+            "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0",
+            [0, 1, 2, 3, 4],
+            54321,
+        ),
+        (
+            "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,\
+             4,31,99,0,0,0",
+            [1, 0, 4, 3, 2],
+            65210,
+        ),
+    ];
+
+    /// Examples from the second part of 2019/07, which need the feedback loop
+    const LOOPED: [(&str, [i64; 5], i64); 2] = [
+        (
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+            [9, 8, 7, 6, 5],
+            139_629_729,
+        ),
+        (
+            "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,\
+             53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,\
+             10",
+            [9, 7, 8, 5, 6],
+            18216,
+        ),
+    ];
+
+    #[test]
+    fn runs_chained_examples() {
+        for (program, phases, signal) in CHAINED {
+            let program = parse_program(program).unwrap();
+            let mut pipeline = Pipeline::from_phases(&program, &phases);
+
+            assert_eq!(pipeline.run([0]).unwrap(), Some(signal), "{phases:?}");
+        }
+    }
+
+    #[test]
+    fn runs_looped_examples() {
+        for (program, phases, signal) in LOOPED {
+            let program = parse_program(program).unwrap();
+            let mut pipeline = Pipeline::from_phases(&program, &phases).with_feedback();
+
+            assert_eq!(pipeline.run([0]).unwrap(), Some(signal), "{phases:?}");
+        }
+    }
+
+    #[test]
+    fn looped_examples_deadlock_without_feedback() {
+        for (program, phases, _) in LOOPED {
+            let program = parse_program(program).unwrap();
+            let mut pipeline = Pipeline::from_phases(&program, &phases);
+
+            let err = pipeline.run([0]).unwrap_err();
+            assert!(
+                matches!(&err, IntcodeError::Deadlock { waiting } if *waiting == [0, 1, 2, 3, 4]),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn picks_up_after_a_deadlock() {
+        let program = crate::assemble(
+            "
+            ; adds up the values it reads until it reads 0
+            loop:   In      [value]
+                    Jf      [value], done
+                    Add     [value], [sum], [sum]
+                    Jt      1, loop
+            done:   Out     [sum]
+                    Halt
+            value:  .data   0
+            sum:    .data   0
+            ",
+        )
+        .unwrap();
+
+        let mut pipeline = Pipeline::new(vec![Interpreter::new(program)]);
+        assert!(matches!(
+            pipeline.run([1, 2]),
+            Err(IntcodeError::Deadlock { waiting }) if waiting == [0]
+        ));
+        assert_eq!(pipeline.run([3, 0]).unwrap(), Some(6));
+    }
+
+    #[test]
+    fn runs_without_stages() {
+        assert_eq!(Pipeline::new(vec![]).run([1]).unwrap(), None);
+    }
+
+    #[test]
+    fn drops_values_sent_to_halted_stages() {
+        // outputs 1 and 2 to a stage that halts straight away
+        let first = Interpreter::parse("104,1,104,2,99").unwrap();
+        let second = Interpreter::parse("99").unwrap();
+
+        let mut pipeline = Pipeline::new(vec![first, second]).with_feedback();
+        assert_eq!(pipeline.run([]).unwrap(), None);
+    }
+}
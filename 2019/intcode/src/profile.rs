@@ -0,0 +1,356 @@
+//! Counting where a program spends its time
+//!
+//! Profiling is opt-in, see [`Interpreter::with_profiling`](crate::Interpreter::with_profiling).
+//! Counts only ever go up, so undoing a step does not take it back out of the profile.
+
+use core::fmt;
+use std::{cmp::Reverse, collections::HashMap, ops::Range};
+
+use machine::instruction_address;
+
+use crate::{disasm::Disassembly, instruction::Instruction, opcode::Opcode};
+
+/// Execution counts gathered while a program runs
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// Number of instructions executed
+    pub total: u64,
+    /// Times the instruction at each address was executed
+    pub executions: HashMap<usize, u64>,
+    pub opcodes: HashMap<Opcode, u64>,
+    /// Times each cell was read as a parameter
+    pub reads: HashMap<usize, u64>,
+    /// Times each cell was written to
+    pub writes: HashMap<usize, u64>,
+    /// Times each `Jt` or `Jf` jumped back to an address at or before itself, keyed by the
+    /// address of the jump and its target
+    pub back_edges: HashMap<(usize, usize), u64>,
+}
+
+/// Loop found from a jump that went backwards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotLoop {
+    /// Target of the jump, which is the start of the loop
+    pub start: usize,
+    /// Address after the jump that closes the loop
+    pub end: usize,
+    /// Times the jump was taken
+    pub iterations: u64,
+    /// Instructions executed inside the loop, including nested loops
+    pub executed: u64,
+}
+
+impl Profile {
+    /// Counts an executed instruction, given the addresses it accessed and where the program
+    /// counter went afterwards
+    pub(crate) fn record(
+        &mut self,
+        pc: usize,
+        instruction: &Instruction,
+        reads: &[usize],
+        write: Option<usize>,
+        next_pc: usize,
+    ) {
+        self.total += 1;
+        *self.executions.entry(pc).or_default() += 1;
+        *self.opcodes.entry(instruction.opcode).or_default() += 1;
+
+        for addr in reads {
+            *self.reads.entry(*addr).or_default() += 1;
+        }
+
+        if let Some(addr) = write {
+            *self.writes.entry(addr).or_default() += 1;
+        }
+
+        if matches!(instruction.opcode, Opcode::Jt | Opcode::Jf) && next_pc <= pc {
+            *self.back_edges.entry((pc, next_pc)).or_default() += 1;
+        }
+    }
+
+    #[must_use]
+    pub fn executions(&self, addr: usize) -> u64 {
+        self.executions.get(&addr).copied().unwrap_or_default()
+    }
+
+    /// Most executed addresses with their counts, most executed first
+    #[must_use]
+    pub fn hottest(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut hottest = self
+            .executions
+            .iter()
+            .map(|(addr, count)| (*addr, *count))
+            .collect::<Vec<_>>();
+        hottest.sort_unstable_by_key(|(addr, count)| (Reverse(*count), *addr));
+        hottest.truncate(n);
+
+        hottest
+    }
+
+    /// Opcodes with the number of times they were executed, most executed first
+    #[must_use]
+    pub fn opcode_histogram(&self) -> Vec<(Opcode, u64)> {
+        let mut histogram = self
+            .opcodes
+            .iter()
+            .map(|(opcode, count)| (*opcode, *count))
+            .collect::<Vec<_>>();
+        histogram.sort_unstable_by_key(|(opcode, count)| (Reverse(*count), *opcode as u8));
+
+        histogram
+    }
+
+    /// Loops closed by a backwards jump, with the ones that executed the most instructions first
+    #[must_use]
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops = self
+            .back_edges
+            .iter()
+            .map(|((jump, target), iterations)| {
+                // every jump takes up three cells
+                let end = jump + Opcode::Jt.len();
+                let executed = self
+                    .executions
+                    .iter()
+                    .filter(|(addr, _)| (*target..end).contains(*addr))
+                    .map(|(_, count)| count)
+                    .sum();
+
+                HotLoop {
+                    start: *target,
+                    end,
+                    iterations: *iterations,
+                    executed,
+                }
+            })
+            .collect::<Vec<_>>();
+        loops.sort_unstable_by_key(|hot| (Reverse(hot.executed), hot.start, hot.end));
+
+        loops
+    }
+
+    /// Runs of consecutive instructions in the disassembly that were never executed, with the
+    /// number of instructions in each
+    #[must_use]
+    pub fn never_executed(&self, disassembly: &Disassembly) -> Vec<(Range<usize>, usize)> {
+        let mut regions: Vec<(Range<usize>, usize)> = vec![];
+
+        for (addr, instruction) in disassembly.instructions() {
+            if self.executions.contains_key(&addr) {
+                continue;
+            }
+
+            let end = addr + instruction.opcode.len();
+            match regions.last_mut() {
+                Some((range, count)) if range.end == addr => {
+                    range.end = end;
+                    *count += 1;
+                }
+                _ => regions.push((addr..end, 1)),
+            }
+        }
+
+        regions
+    }
+
+    /// Prefixes every instruction line of a listing with the number of times it was executed
+    ///
+    /// Works on the control-flow disassembly as well as the linear listing of an executor.
+    #[must_use]
+    pub fn annotate(&self, listing: &str) -> String {
+        listing
+            .lines()
+            .map(|line| match instruction_address(line) {
+                Some(addr) => format!("{:>10}  {line}", self.executions(addr)),
+                None => format!("{:>10}  {line}", ""),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Report of the `top` most executed addresses, the opcode histogram, hot loops and code that
+    /// never ran, followed by the annotated disassembly
+    #[must_use]
+    pub const fn report<'a>(&'a self, disassembly: &'a Disassembly, top: usize) -> Report<'a> {
+        Report {
+            profile: self,
+            disassembly,
+            top,
+        }
+    }
+}
+
+/// Human-readable summary of a [`Profile`], created by [`Profile::report`]
+#[derive(Debug)]
+pub struct Report<'a> {
+    profile: &'a Profile,
+    disassembly: &'a Disassembly,
+    top: usize,
+}
+
+impl Report<'_> {
+    #[allow(clippy::cast_precision_loss)]
+    fn percent(&self, count: u64) -> f64 {
+        if self.profile.total == 0 {
+            return 0.0;
+        }
+
+        count as f64 * 100.0 / self.profile.total as f64
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let profile = self.profile;
+
+        writeln!(
+            f,
+            "{} instructions executed at {} addresses",
+            profile.total,
+            profile.executions.len()
+        )?;
+
+        writeln!(f, "\nHottest addresses:")?;
+        for (addr, count) in profile.hottest(self.top) {
+            let instruction = self
+                .disassembly
+                .instruction(addr)
+                .map(ToString::to_string)
+                .unwrap_or_default();
+
+            writeln!(
+                f,
+                "{count:>12} {:>6.2}%  {addr:08x}:\t{instruction}",
+                self.percent(count)
+            )?;
+        }
+
+        writeln!(f, "\nOpcodes:")?;
+        for (opcode, count) in profile.opcode_histogram() {
+            writeln!(f, "{count:>12} {:>6.2}%  {opcode:?}", self.percent(count))?;
+        }
+
+        writeln!(f, "\nHot loops:")?;
+        for hot in profile.hot_loops().iter().take(self.top) {
+            writeln!(
+                f,
+                "{:>12} {:>6.2}%  {:08x}..{:08x}\t{} iterations",
+                hot.executed,
+                self.percent(hot.executed),
+                hot.start,
+                hot.end,
+                hot.iterations
+            )?;
+        }
+
+        writeln!(f, "\nNever executed:")?;
+        for (range, count) in profile.never_executed(self.disassembly) {
+            writeln!(
+                f,
+                "{:08x}..{:08x}\t{count} instructions",
+                range.start, range.end
+            )?;
+        }
+
+        writeln!(f, "\nDisassembly:")?;
+        writeln!(f, "{}", profile.annotate(&self.disassembly.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Interpreter, State};
+
+    /// Runs the inner loop three times for each of the two runs of the outer loop
+    const NESTED: &str = "
+        outer:  Add     [i], -1, [i]
+                Add     3, 0, [j]
+        inner:  Add     [j], -1, [j]
+                Jt      [j], inner
+                Jt      [i], outer
+                Out     [i]
+                Halt
+        i:      .data   2
+        j:      .data   0
+        ";
+
+    fn profile(source: &str) -> (Interpreter, Profile) {
+        let mut interpreter = Interpreter::new(assemble(source).unwrap()).with_profiling();
+        while interpreter.step().unwrap() != Some(State::Halted) {}
+
+        let profile = interpreter.profile().unwrap().clone();
+        (interpreter, profile)
+    }
+
+    #[test]
+    fn counts_executions_per_address() {
+        let (_, profile) = profile(NESTED);
+
+        assert_eq!(profile.total, 20);
+        for (addr, count) in [(0, 2), (4, 2), (8, 6), (12, 6), (15, 2), (18, 1), (20, 1)] {
+            assert_eq!(profile.executions(addr), count, "{addr}");
+        }
+        assert_eq!(profile.executions(21), 0);
+        assert_eq!(profile.reads[&22], 12);
+        assert_eq!(profile.writes[&21], 2);
+    }
+
+    #[test]
+    fn orders_hot_spots() {
+        let (_, profile) = profile(NESTED);
+
+        // ties go to the lower address
+        assert_eq!(profile.hottest(3), [(8, 6), (12, 6), (0, 2)]);
+        assert_eq!(
+            profile.opcode_histogram(),
+            [
+                (Opcode::Add, 10),
+                (Opcode::Jt, 8),
+                (Opcode::Out, 1),
+                (Opcode::Halt, 1)
+            ]
+        );
+        assert_eq!(
+            profile.hot_loops(),
+            [
+                HotLoop {
+                    start: 0,
+                    end: 18,
+                    iterations: 1,
+                    executed: 18,
+                },
+                HotLoop {
+                    start: 8,
+                    end: 15,
+                    iterations: 4,
+                    executed: 12,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_code_that_never_ran() {
+        // the jump is always taken, so the output after it never runs
+        let (interpreter, profile) = profile("Jt 1, end\nOut 1\nOut 2\nend: Halt");
+        let disassembly = interpreter.disassemble([]);
+
+        // without a hint the skipped code is data
+        assert!(!disassembly.is_code(3));
+        assert_eq!(profile.never_executed(&disassembly), []);
+
+        let disassembly = interpreter.disassemble([3]);
+        assert_eq!(profile.never_executed(&disassembly), [(3..7, 2)]);
+
+        let annotated = profile.annotate(&disassembly.to_string());
+        assert!(
+            annotated.contains("         1    00000000:\tJt"),
+            "{annotated}"
+        );
+        assert!(
+            annotated.contains("         0    00000003:\tOut"),
+            "{annotated}"
+        );
+    }
+}
@@ -0,0 +1,931 @@
+//! Recording runs of a program to replay and compare them later
+//!
+//! A [`Recording`] holds the memory a run started from, the arithmetic policy and memory limit it
+//! ran under, every value it consumed and produced, how it ended and optionally what every step
+//! did. Programs only depend on their input, so that is enough to replay the run instruction for
+//! instruction, and to find the first point where two runs went different ways.
+//!
+//! Recordings are stored in a compact binary format: a magic number, a version byte and the
+//! arithmetic policy byte, followed by LEB128 varints with signed values zigzag encoded. Memory is
+//! stored as the program image and a list of the cells past it that are not 0, so far writes do
+//! not blow up the file. Steps store their program counter and relative base as differences to
+//! the previous step, and only flag whether they did input or output, since the values are in the
+//! input and output lists already.
+
+use core::{fmt, iter};
+
+use crate::{
+    arithmetic::Arithmetic,
+    error::IntcodeError,
+    executor::State,
+    instruction::Instruction,
+    interpreter::Interpreter,
+    limits::{Limiter, Limits},
+    memory::Memory,
+    trace::StepRecord,
+};
+
+const MAGIC: &[u8; 4] = b"ICRC";
+const VERSION: u8 = 1;
+
+/// Number of differing memory cells listed when describing a [`Divergence`]
+const MEMORY_DIFF_LEN: usize = 8;
+
+const STEP_WRITE: u8 = 1 << 0;
+const STEP_INPUT: u8 = 1 << 1;
+const STEP_OUTPUT: u8 = 1 << 2;
+const STEP_REL: u8 = 1 << 3;
+
+/// How a recorded run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunEnd {
+    Halted,
+    NeedsInput,
+    /// A limit on the run stopped it
+    Stopped,
+    /// The program failed with an error, which is kept as its message
+    Fault(String),
+}
+
+impl fmt::Display for RunEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Halted => write!(f, "halted"),
+            Self::NeedsInput => write!(f, "waiting for input"),
+            Self::Stopped => write!(f, "stopped by a limit"),
+            Self::Fault(message) => write!(f, "fault: {message}"),
+        }
+    }
+}
+
+/// What a single step of a recorded run did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedStep {
+    pub pc: usize,
+    pub rel: isize,
+    /// Address and new value of the cell the step wrote to
+    pub write: Option<(usize, i64)>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+}
+
+impl From<&StepRecord> for RecordedStep {
+    fn from(record: &StepRecord) -> Self {
+        Self {
+            pc: record.pc,
+            rel: record.rel,
+            write: record.write.as_ref().map(|write| (write.addr, write.new)),
+            input: record.input,
+            output: record.output,
+        }
+    }
+}
+
+impl fmt::Display for RecordedStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08x}:\trel {}", self.pc, self.rel)?;
+
+        let mut effects = vec![];
+        if let Some((addr, value)) = self.write {
+            effects.push(format!("[{addr}] = {value}"));
+        }
+        if let Some(input) = self.input {
+            effects.push(format!("in {input}"));
+        }
+        if let Some(output) = self.output {
+            effects.push(format!("out {output}"));
+        }
+
+        if !effects.is_empty() {
+            write!(f, "\t; {}", effects.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Everything needed to replay a run of a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    /// Memory the run started from
+    pub program: Vec<i64>,
+    /// Addresses and values of the cells past the program image that were not 0 when the run
+    /// started
+    pub sparse: Vec<(usize, i64)>,
+    /// How `Add` and `Mul` dealt with results that did not fit in 64 bits
+    pub arithmetic: Arithmetic,
+    /// Number of memory cells the program could allocate
+    pub memory_limit: usize,
+    /// Values consumed by `In` instructions
+    pub inputs: Vec<i64>,
+    pub outputs: Vec<i64>,
+    /// Number of instructions executed
+    pub executed: u64,
+    pub end: RunEnd,
+    /// What every step did, if steps were recorded
+    pub steps: Option<Vec<RecordedStep>>,
+}
+
+/// Builds a [`Recording`] while the caller runs the program one step at a time
+#[derive(Debug)]
+pub struct Recorder {
+    program: Vec<i64>,
+    sparse: Vec<(usize, i64)>,
+    arithmetic: Arithmetic,
+    memory_limit: usize,
+    executed: u64,
+    steps: Option<Vec<RecordedStep>>,
+}
+
+#[allow(clippy::missing_errors_doc)]
+impl Recorder {
+    /// Starts recording a machine that has not executed anything yet
+    #[must_use]
+    pub fn new(interpreter: &Interpreter, with_steps: bool) -> Self {
+        let memory = &interpreter.executor.memory;
+
+        Self {
+            program: memory.dense().to_vec(),
+            sparse: memory
+                .nonzero()
+                .filter(|(addr, _)| *addr >= memory.dense().len())
+                .collect(),
+            arithmetic: interpreter.executor.arithmetic(),
+            memory_limit: memory.limit(),
+            executed: 0,
+            steps: with_steps.then(Vec::new),
+        }
+    }
+
+    /// Executes a single instruction like [`Interpreter::trace_step`] and records it
+    pub fn step(
+        &mut self,
+        interpreter: &mut Interpreter,
+    ) -> Result<(Option<State>, Option<StepRecord>), IntcodeError> {
+        let (state, record) = interpreter.trace_step()?;
+
+        if let Some(record) = &record {
+            self.executed += 1;
+
+            if let Some(steps) = &mut self.steps {
+                steps.push(record.into());
+            }
+        }
+
+        Ok((state, record))
+    }
+
+    /// Completes the recording once the run has ended
+    #[must_use]
+    pub fn finish(self, interpreter: &Interpreter, end: RunEnd) -> Recording {
+        Recording {
+            program: self.program,
+            sparse: self.sparse,
+            arithmetic: self.arithmetic,
+            memory_limit: self.memory_limit,
+            inputs: interpreter.input_history().to_vec(),
+            outputs: interpreter.output_history().to_vec(),
+            executed: self.executed,
+            end,
+            steps: self.steps,
+        }
+    }
+}
+
+/// Where two recorded runs first differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergencePoint {
+    /// Step `index` did something different, or only one of the runs got that far
+    Step {
+        index: u64,
+        left: Option<RecordedStep>,
+        right: Option<RecordedStep>,
+    },
+    /// Output `index` was a different value, or only one of the runs produced it
+    Output {
+        index: usize,
+        left: Option<i64>,
+        right: Option<i64>,
+    },
+    /// The runs ended differently, or after a different number of instructions
+    End {
+        left: (RunEnd, u64),
+        right: (RunEnd, u64),
+    },
+}
+
+/// First difference between two recorded runs, with both machines rebuilt at that point
+#[derive(Debug)]
+pub struct Divergence {
+    pub at: DivergencePoint,
+    /// Machines just before the step that differs, just after the output that differs, or where
+    /// the runs ended
+    pub left: Interpreter,
+    pub right: Interpreter,
+}
+
+#[allow(clippy::missing_errors_doc)]
+impl Recording {
+    /// Runs a program from the start with `input` queued, recording it until it halts, needs more
+    /// input, fails or hits a limit
+    #[must_use]
+    pub fn record(program: Vec<i64>, input: &[i64], limits: Limits, with_steps: bool) -> Self {
+        let mut interpreter = Interpreter::new(program);
+        for value in input {
+            interpreter.push_input(*value);
+        }
+
+        Self::record_machine(interpreter, limits, with_steps)
+    }
+
+    /// Records a machine that has not executed anything yet, like [`Recording::record`]
+    fn record_machine(mut interpreter: Interpreter, limits: Limits, with_steps: bool) -> Self {
+        let mut recorder = Recorder::new(&interpreter, with_steps);
+        let mut limiter = Limiter::new(limits);
+
+        let end = loop {
+            if limiter.check(&interpreter.executor).is_some() {
+                break RunEnd::Stopped;
+            }
+
+            let step = recorder.step(&mut interpreter);
+            if let Ok((state, _)) = &step {
+                limiter.count(*state);
+            }
+
+            match step {
+                Ok((Some(State::Halted), _)) => break RunEnd::Halted,
+                Ok((Some(State::NeedsInput), _)) => break RunEnd::NeedsInput,
+                Ok(_) => {}
+                Err(e) => break RunEnd::Fault(e.to_string()),
+            }
+        };
+
+        recorder.finish(&interpreter, end)
+    }
+
+    /// Runs the recorded program again with the recorded input
+    ///
+    /// The replay is limited to the number of instructions the recording executed, plus the one
+    /// that ended it, so it stops where a run that was cut short by a limit stopped.
+    #[must_use]
+    pub fn replay(&self) -> Self {
+        let budget = self.executed + u64::from(self.end != RunEnd::Stopped);
+
+        Self::record_machine(
+            self.start(),
+            Limits::default().with_instructions(budget),
+            self.steps.is_some(),
+        )
+    }
+
+    /// Replays the recording and compares the replay to it, returning where they differ
+    #[must_use]
+    pub fn verify(&self) -> Option<Divergence> {
+        self.diff(&self.replay())
+    }
+
+    /// Finds the first point at which two runs differ
+    ///
+    /// Steps are compared if both recordings have them, then outputs and then how the runs
+    /// ended.
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> Option<Divergence> {
+        if let (Some(left), Some(right)) = (&self.steps, &other.steps) {
+            let index = (0..left.len().max(right.len())).find(|i| left.get(*i) != right.get(*i));
+
+            if let Some(index) = index {
+                let index = index as u64;
+                return Some(Divergence {
+                    at: DivergencePoint::Step {
+                        index,
+                        left: left.get(index as usize).copied(),
+                        right: right.get(index as usize).copied(),
+                    },
+                    left: self.machine(|_, executed| executed == index),
+                    right: other.machine(|_, executed| executed == index),
+                });
+            }
+        }
+
+        let (left, right) = (&self.outputs, &other.outputs);
+        if let Some(index) =
+            (0..left.len().max(right.len())).find(|i| left.get(*i) != right.get(*i))
+        {
+            let produced =
+                |interpreter: &Interpreter, _| interpreter.output_history().len() > index;
+
+            return Some(Divergence {
+                at: DivergencePoint::Output {
+                    index,
+                    left: left.get(index).copied(),
+                    right: right.get(index).copied(),
+                },
+                left: self.machine(produced),
+                right: other.machine(produced),
+            });
+        }
+
+        if self.end != other.end || self.executed != other.executed {
+            return Some(Divergence {
+                at: DivergencePoint::End {
+                    left: (self.end.clone(), self.executed),
+                    right: (other.end.clone(), other.executed),
+                },
+                left: self.machine(|_, _| false),
+                right: other.machine(|_, _| false),
+            });
+        }
+
+        None
+    }
+
+    /// Replays the recording until `stop` returns true, given the machine and the number of
+    /// instructions executed so far, or until the recorded run ended
+    fn machine(&self, mut stop: impl FnMut(&Interpreter, u64) -> bool) -> Interpreter {
+        let mut interpreter = self.start();
+
+        let mut executed = 0;
+        while executed < self.executed && !stop(&interpreter, executed) {
+            match interpreter.step() {
+                Ok(Some(State::NeedsInput | State::Halted)) | Err(_) => break,
+                Ok(_) => executed += 1,
+            }
+        }
+
+        interpreter
+    }
+
+    /// Machine in the state the recorded run started from, under the same arithmetic policy and
+    /// memory limit, with the recorded input queued
+    fn start(&self) -> Interpreter {
+        let mut interpreter = Interpreter::new(self.program.clone())
+            .with_arithmetic(self.arithmetic)
+            .with_memory_limit(self.memory_limit);
+        for (addr, value) in &self.sparse {
+            // the cells fit in memory when the run was recorded
+            let _ = interpreter.set_memory(*addr, *value);
+        }
+        for value in &self.inputs {
+            interpreter.push_input(*value);
+        }
+
+        interpreter
+    }
+
+    /// Encodes the recording in the binary format described in the [module docs](self)
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        out.push(match self.arithmetic {
+            Arithmetic::Wrapping => 0,
+            Arithmetic::Checked => 1,
+            Arithmetic::Big => 2,
+        });
+        write_varint(&mut out, self.memory_limit as u64);
+
+        write_varint(&mut out, self.executed);
+        match &self.end {
+            RunEnd::Halted => out.push(0),
+            RunEnd::NeedsInput => out.push(1),
+            RunEnd::Stopped => out.push(2),
+            RunEnd::Fault(message) => {
+                out.push(3);
+                write_varint(&mut out, message.len() as u64);
+                out.extend(message.bytes());
+            }
+        }
+
+        write_values(&mut out, &self.program);
+        write_varint(&mut out, self.sparse.len() as u64);
+        for (addr, value) in &self.sparse {
+            write_varint(&mut out, *addr as u64);
+            write_varint(&mut out, zigzag(*value));
+        }
+        write_values(&mut out, &self.inputs);
+        write_values(&mut out, &self.outputs);
+
+        let Some(steps) = &self.steps else {
+            out.push(0);
+            return out;
+        };
+
+        out.push(1);
+        write_varint(&mut out, steps.len() as u64);
+
+        let (mut pc, mut rel) = (0, 0);
+        for step in steps {
+            let mut flags = 0;
+            if step.write.is_some() {
+                flags |= STEP_WRITE;
+            }
+            if step.input.is_some() {
+                flags |= STEP_INPUT;
+            }
+            if step.output.is_some() {
+                flags |= STEP_OUTPUT;
+            }
+            if step.rel != rel {
+                flags |= STEP_REL;
+            }
+            out.push(flags);
+
+            write_varint(&mut out, zigzag(step.pc as i64 - pc as i64));
+            if step.rel != rel {
+                write_varint(&mut out, zigzag(step.rel as i64 - rel as i64));
+            }
+            if let Some((addr, value)) = step.write {
+                write_varint(&mut out, addr as u64);
+                write_varint(&mut out, zigzag(value));
+            }
+
+            (pc, rel) = (step.pc, step.rel);
+        }
+
+        out
+    }
+
+    /// Decodes a recording from the binary format described in the [module docs](self)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, IntcodeError> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a recording"));
+        }
+        if reader.byte()? != VERSION {
+            return Err(invalid("unsupported version"));
+        }
+
+        let arithmetic = match reader.byte()? {
+            0 => Arithmetic::Wrapping,
+            1 => Arithmetic::Checked,
+            2 => Arithmetic::Big,
+            _ => return Err(invalid("unknown arithmetic policy")),
+        };
+        let memory_limit = reader.len()?;
+
+        let executed = reader.varint()?;
+        let end = match reader.byte()? {
+            0 => RunEnd::Halted,
+            1 => RunEnd::NeedsInput,
+            2 => RunEnd::Stopped,
+            3 => {
+                let len = reader.len()?;
+                let message = String::from_utf8(reader.take(len)?.to_vec())
+                    .map_err(|_| invalid("fault message is not UTF-8"))?;
+                RunEnd::Fault(message)
+            }
+            _ => return Err(invalid("unknown end of run")),
+        };
+
+        let program = reader.values()?;
+        let sparse = reader.cells()?;
+        let inputs = reader.values()?;
+        let outputs = reader.values()?;
+
+        let steps = match reader.byte()? {
+            0 => None,
+            1 => Some(reader.steps(&inputs, &outputs)?),
+            _ => return Err(invalid("unknown step format")),
+        };
+
+        if reader.pos != bytes.len() {
+            return Err(invalid("trailing data"));
+        }
+
+        Ok(Self {
+            program,
+            sparse,
+            arithmetic,
+            memory_limit,
+            inputs,
+            outputs,
+            executed,
+            end,
+            steps,
+        })
+    }
+}
+
+fn invalid(message: &str) -> IntcodeError {
+    IntcodeError::Recording(message.to_string())
+}
+
+const fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+const fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn write_values(out: &mut Vec<u8>, values: &[i64]) {
+    write_varint(out, values.len() as u64);
+    for value in values {
+        write_varint(out, zigzag(*value));
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], IntcodeError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        self.pos += len;
+
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, IntcodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, IntcodeError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(invalid("varint is too long"))
+    }
+
+    fn signed(&mut self) -> Result<i64, IntcodeError> {
+        Ok(unzigzag(self.varint()?))
+    }
+
+    fn len(&mut self) -> Result<usize, IntcodeError> {
+        usize::try_from(self.varint()?).map_err(|_| invalid("length does not fit in memory"))
+    }
+
+    fn values(&mut self) -> Result<Vec<i64>, IntcodeError> {
+        let len = self.len()?;
+
+        // every value takes up at least one byte, so a corrupt length can not allocate too much
+        let mut values = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        for _ in 0..len {
+            values.push(self.signed()?);
+        }
+
+        Ok(values)
+    }
+
+    fn cells(&mut self) -> Result<Vec<(usize, i64)>, IntcodeError> {
+        let len = self.len()?;
+
+        // every cell takes up at least two bytes
+        let mut cells = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        for _ in 0..len {
+            cells.push((self.len()?, self.signed()?));
+        }
+
+        Ok(cells)
+    }
+
+    fn steps(
+        &mut self,
+        inputs: &[i64],
+        outputs: &[i64],
+    ) -> Result<Vec<RecordedStep>, IntcodeError> {
+        let len = self.len()?;
+        let mut inputs = inputs.iter().copied();
+        let mut outputs = outputs.iter().copied();
+
+        let mut steps = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+        let (mut pc, mut rel): (usize, isize) = (0, 0);
+        for _ in 0..len {
+            let flags = self.byte()?;
+
+            pc = pc
+                .checked_add_signed(self.signed()? as isize)
+                .ok_or_else(|| invalid("program counter out of range"))?;
+            if flags & STEP_REL != 0 {
+                rel = rel
+                    .checked_add(self.signed()? as isize)
+                    .ok_or_else(|| invalid("relative base out of range"))?;
+            }
+
+            let write = if flags & STEP_WRITE == 0 {
+                None
+            } else {
+                Some((self.len()?, self.signed()?))
+            };
+
+            let input = match flags & STEP_INPUT {
+                0 => None,
+                _ => Some(
+                    inputs
+                        .next()
+                        .ok_or_else(|| invalid("more steps than inputs"))?,
+                ),
+            };
+            let output = match flags & STEP_OUTPUT {
+                0 => None,
+                _ => Some(
+                    outputs
+                        .next()
+                        .ok_or_else(|| invalid("more steps than outputs"))?,
+                ),
+            };
+
+            steps.push(RecordedStep {
+                pc,
+                rel,
+                write,
+                input,
+                output,
+            });
+        }
+
+        Ok(steps)
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+        match &self.at {
+            DivergencePoint::Step { index, left, right } => {
+                writeln!(f, "Runs diverge at step {index}")?;
+                writeln!(f, "  left:\t{}", side(left.map(|step| step.to_string())))?;
+                writeln!(f, "  right:\t{}", side(right.map(|step| step.to_string())))?;
+            }
+            DivergencePoint::Output { index, left, right } => {
+                writeln!(f, "Runs diverge at output {index}")?;
+                writeln!(f, "  left:\t{}", side(left.map(|value| value.to_string())))?;
+                writeln!(
+                    f,
+                    "  right:\t{}",
+                    side(right.map(|value| value.to_string()))
+                )?;
+            }
+            DivergencePoint::End { left, right } => {
+                writeln!(f, "Runs end differently")?;
+                writeln!(f, "  left:\t{} after {} instructions", left.0, left.1)?;
+                writeln!(f, "  right:\t{} after {} instructions", right.0, right.1)?;
+            }
+        }
+
+        for (name, machine) in [("Left", &self.left), ("Right", &self.right)] {
+            let executor = &machine.executor;
+            let next = next_instruction(machine);
+
+            writeln!(
+                f,
+                "{name} machine:\tpc {:#x}, rel {}, {} inputs consumed, {} outputs, next {next}",
+                executor.pc,
+                executor.rel,
+                machine.input_history().len(),
+                machine.output_history().len(),
+            )?;
+        }
+
+        let (left, right) = (&self.left.executor.memory, &self.right.executor.memory);
+        let differing = differing_cells(left, right)
+            .take(MEMORY_DIFF_LEN + 1)
+            .collect::<Vec<_>>();
+
+        if differing.is_empty() {
+            writeln!(f, "Memory is the same")?;
+        } else {
+            writeln!(f, "Memory differs:")?;
+            for addr in differing.iter().take(MEMORY_DIFF_LEN) {
+                writeln!(
+                    f,
+                    "  [{addr:#x}]\t{} != {}",
+                    left.get(*addr),
+                    right.get(*addr)
+                )?;
+            }
+            if differing.len() > MEMORY_DIFF_LEN {
+                writeln!(f, "  ...")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Addresses at which two memories hold different values, in order
+///
+/// Cells that are 0 in both can not differ, so this only walks the cells either of them has in
+/// use, merging the two in order of address.
+fn differing_cells<'a>(left: &'a Memory, right: &'a Memory) -> impl Iterator<Item = usize> + 'a {
+    let mut left = left.nonzero().peekable();
+    let mut right = right.nonzero().peekable();
+
+    iter::from_fn(move || loop {
+        let addr = match (left.peek(), right.peek()) {
+            (None, None) => return None,
+            (Some((addr, _)), None) | (None, Some((addr, _))) => *addr,
+            (Some((l, _)), Some((r, _))) => *l.min(r),
+        };
+
+        let l = left
+            .next_if(|(a, _)| *a == addr)
+            .map_or(0, |(_, value)| value);
+        let r = right
+            .next_if(|(a, _)| *a == addr)
+            .map_or(0, |(_, value)| value);
+        if l != r {
+            return Some(addr);
+        }
+    })
+}
+
+/// Instruction at the program counter of a machine, or why there is none
+fn next_instruction(machine: &Interpreter) -> String {
+    let executor = &machine.executor;
+
+    Instruction::fetch(&executor.memory, executor.pc)
+        .map_or_else(|e| e.to_string(), |instruction| instruction.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_MEMORY_LIMIT;
+
+    /// Reads a value, writes 2 far past the program image, outputs the value and halts
+    const FAR_WRITE: &str = "3,0,1101,1,1,1000000000000000,4,0,99";
+
+    /// Writes twice the value it reads far past the program image
+    const FAR_DOUBLE: &str = "3,0,1,0,0,1000000000000000,4,0,99";
+
+    /// Outputs every value it reads until it reads 0
+    const ECHO: &str = "3,9,4,9,1005,9,0,99,0,0";
+
+    fn record(program: &str, input: &[i64], with_steps: bool) -> Recording {
+        let program = crate::parse_program(program).unwrap();
+
+        Recording::record(program, input, Limits::default(), with_steps)
+    }
+
+    #[test]
+    fn round_trip() {
+        let cases: [(&str, &[i64]); 4] = [
+            (FAR_WRITE, &[5]),
+            (FAR_DOUBLE, &[-3]),
+            (ECHO, &[1, 2, 3, 0]),
+            (ECHO, &[1, 2]),
+        ];
+
+        for (program, input) in cases {
+            for with_steps in [false, true] {
+                let recording = record(program, input, with_steps);
+                let decoded = Recording::from_bytes(&recording.to_bytes()).unwrap();
+
+                assert_eq!(decoded, recording, "{program} with {input:?}");
+                assert_eq!(recording.replay(), recording, "{program} with {input:?}");
+                assert!(recording.verify().is_none(), "{program} with {input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_sparse_image() {
+        let mut interpreter = Interpreter::parse("4,1000000000000001,99").unwrap();
+        interpreter.set_memory(1_000_000_000_000_001, 7).unwrap();
+
+        let mut recorder = Recorder::new(&interpreter, true);
+        while recorder.step(&mut interpreter).unwrap().0 != Some(State::Halted) {}
+        let recording = recorder.finish(&interpreter, RunEnd::Halted);
+
+        assert_eq!(recording.sparse, [(1_000_000_000_000_001, 7)]);
+        assert_eq!(recording.outputs, [7]);
+        assert_eq!(
+            Recording::from_bytes(&recording.to_bytes()).unwrap(),
+            recording
+        );
+        assert!(recording.verify().is_none());
+    }
+
+    #[test]
+    fn diff_finds_output_divergence() {
+        let left = record(FAR_WRITE, &[5], false);
+        let right = record(FAR_WRITE, &[6], false);
+
+        let divergence = left.diff(&right).unwrap();
+        assert_eq!(
+            divergence.at,
+            DivergencePoint::Output {
+                index: 0,
+                left: Some(5),
+                right: Some(6),
+            }
+        );
+
+        let description = divergence.to_string();
+        assert!(description.contains("[0x0]\t5 != 6"), "{description}");
+        assert!(!description.contains("0x38d7ea4c68000"), "{description}");
+    }
+
+    #[test]
+    fn diff_lists_far_cells() {
+        let left = record(FAR_DOUBLE, &[5], false);
+        let right = record(FAR_DOUBLE, &[6], false);
+
+        let description = left.diff(&right).unwrap().to_string();
+        assert!(description.contains("[0x0]\t5 != 6"), "{description}");
+        assert!(
+            description.contains("[0x38d7ea4c68000]\t10 != 12"),
+            "{description}"
+        );
+    }
+
+    #[test]
+    fn diff_finds_step_divergence() {
+        let left = record(ECHO, &[1, 2, 0], true);
+        let right = record(ECHO, &[1, 3, 0], true);
+
+        let divergence = left.diff(&right).unwrap();
+        let DivergencePoint::Step { index, left, right } = divergence.at else {
+            panic!("expected a step divergence, got {:?}", divergence.at);
+        };
+        assert_eq!(index, 3);
+        assert_eq!(left.and_then(|step| step.input), Some(2));
+        assert_eq!(right.and_then(|step| step.input), Some(3));
+    }
+
+    #[test]
+    fn diff_finds_different_ends() {
+        let left = record("3,5,99", &[1], false);
+        let right = record("3,5,99", &[], false);
+
+        let divergence = left.diff(&right).unwrap();
+        assert!(matches!(
+            divergence.at,
+            DivergencePoint::End {
+                left: (RunEnd::Halted, _),
+                right: (RunEnd::NeedsInput, _),
+            }
+        ));
+    }
+
+    #[test]
+    fn replays_under_the_recorded_policy_and_limit() {
+        // adds 1 to the largest value there is, then writes past the start of memory
+        let program =
+            crate::parse_program("1001,9,1,9,1101,0,0,100,99,9223372036854775807").unwrap();
+
+        let cases = [
+            (Arithmetic::Checked, DEFAULT_MEMORY_LIMIT, "overflowed"),
+            (Arithmetic::Wrapping, 16, "cells of memory"),
+        ];
+        for (arithmetic, memory_limit, fault) in cases {
+            let mut interpreter = Interpreter::new(program.clone())
+                .with_arithmetic(arithmetic)
+                .with_memory_limit(memory_limit);
+
+            let mut recorder = Recorder::new(&interpreter, true);
+            let end = loop {
+                match recorder.step(&mut interpreter) {
+                    Ok((Some(State::Halted), _)) => break RunEnd::Halted,
+                    Ok(_) => {}
+                    Err(e) => break RunEnd::Fault(e.to_string()),
+                }
+            };
+            let recording = recorder.finish(&interpreter, end);
+
+            assert!(
+                matches!(&recording.end, RunEnd::Fault(message) if message.contains(fault)),
+                "{:?}",
+                recording.end
+            );
+
+            let decoded = Recording::from_bytes(&recording.to_bytes()).unwrap();
+            assert_eq!(decoded.arithmetic, arithmetic);
+            assert_eq!(decoded.memory_limit, memory_limit);
+            assert_eq!(decoded.replay(), recording);
+            assert!(decoded.verify().is_none());
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = record(ECHO, &[4, 0], false).to_bytes();
+        bytes[MAGIC.len()] = VERSION + 1;
+
+        assert!(Recording::from_bytes(&bytes).is_err());
+    }
+}
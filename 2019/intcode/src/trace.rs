@@ -0,0 +1,65 @@
+use core::fmt;
+
+use crate::{instruction::Instruction, memory::CellValue};
+
+/// A memory cell overwritten by an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub addr: usize,
+    /// Exact value the cell held, so undoing the write restores big integers too
+    pub old: CellValue,
+    /// Value the cell holds now, which for a big integer is its low 64 bits
+    pub new: i64,
+}
+
+/// Everything a single executed instruction looked at and changed
+///
+/// `pc` and `rel` are the values from before the instruction ran, so a record holds enough to
+/// undo the step as well as to describe it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepRecord {
+    pub pc: usize,
+    pub rel: isize,
+    pub instruction: Instruction,
+    /// Addresses and values of the parameters that were read from memory
+    pub reads: Vec<(usize, i64)>,
+    pub write: Option<MemoryWrite>,
+    /// Value consumed by an `In`
+    pub input: Option<i64>,
+    /// Value produced by an `Out`
+    pub output: Option<i64>,
+}
+
+impl fmt::Display for StepRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:08x}:\trel {}\t{}",
+            self.pc, self.rel, self.instruction
+        )?;
+
+        let mut effects = self
+            .reads
+            .iter()
+            .map(|(addr, value)| format!("[{addr}] = {value}"))
+            .collect::<Vec<_>>();
+
+        if let Some(MemoryWrite { addr, old, new }) = &self.write {
+            effects.push(format!("[{addr}]: {old} -> {new}"));
+        }
+
+        if let Some(input) = self.input {
+            effects.push(format!("in {input}"));
+        }
+
+        if let Some(output) = self.output {
+            effects.push(format!("out {output}"));
+        }
+
+        if !effects.is_empty() {
+            write!(f, "\t; {}", effects.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
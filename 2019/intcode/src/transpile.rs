@@ -0,0 +1,506 @@
+//! Ahead-of-time compilation of intcode programs to Rust
+//!
+//! The [`Transpiler`] turns a program image into the source of a Rust function with the signature
+//! of [`Compiled`](crate::executor::Compiled), which runs an [`Executor`](crate::Executor) just
+//! like [`Executor::run`](crate::Executor::run). Once the source has been compiled into a crate,
+//! the function is attached with [`Interpreter::with_compiled`](crate::Interpreter::with_compiled)
+//! and used by everything that runs the interpreter.
+//!
+//! Code found by the [`Disassembler`] is split into segments, which are runs of instructions in a
+//! basic block that are only entered at their first instruction. Every segment becomes a match arm
+//! on the program counter, guarded by a check that its cells still hold what they held in the
+//! image. Where the program counter is not at a segment, because a computed jump went somewhere
+//! that was not found, or because the program modified its own code, the function falls back to
+//! interpreting one instruction at a time until it reaches a segment again.
+
+use std::{fmt::Write, ops::Range};
+
+use crate::{
+    disasm::Disassembler,
+    instruction::Instruction,
+    opcode::{ModeOpt, Opcode},
+};
+
+/// Number of cells per line of the image in the generated source
+const IMAGE_ROW_LEN: usize = 16;
+
+#[derive(Debug)]
+pub struct Transpiler<'a> {
+    program: &'a [i64],
+    name: String,
+}
+
+/// Whether control continues with the next instruction of a segment after the one just emitted
+#[derive(Debug, PartialEq, Eq)]
+enum Flow {
+    Next,
+    Left,
+}
+
+impl<'a> Transpiler<'a> {
+    #[must_use]
+    pub fn new(program: &'a [i64]) -> Self {
+        Self {
+            program,
+            name: "run".to_string(),
+        }
+    }
+
+    /// Name of the generated function, which is `run` by default
+    #[must_use]
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Generates the source of the compiled function and the program image it checks against
+    #[must_use]
+    pub fn transpile(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "// Transpiled from an intcode program with `intcode transpile`, do not edit\n"
+        );
+
+        let image_name = format!("{}_IMAGE", self.name.to_ascii_uppercase());
+        let _ = writeln!(
+            out,
+            "static {image_name}: [i64; {}] = [",
+            self.program.len()
+        );
+        for row in self.program.chunks(IMAGE_ROW_LEN) {
+            let row = row.iter().map(ToString::to_string).collect::<Vec<_>>();
+            let _ = writeln!(out, "    {},", row.join(", "));
+        }
+        let _ = writeln!(out, "];\n");
+
+        let _ = writeln!(
+            out,
+            "/// Runs the program until it needs input, produces an output or halts"
+        );
+        let _ = writeln!(out, "#[allow(clippy::all, clippy::pedantic)]");
+        let _ = writeln!(
+            out,
+            "pub fn {}(executor: &mut intcode::Executor) -> Result<intcode::State, intcode::IntcodeError> {{",
+            self.name
+        );
+        let _ = writeln!(out, "    loop {{");
+        let _ = writeln!(out, "        match executor.pc {{");
+
+        for segment in self.segments() {
+            let start = segment[0].0;
+            let (last_addr, last) = &segment[segment.len() - 1];
+            let range = start..last_addr + last.opcode.len();
+
+            let _ = writeln!(
+                out,
+                "            {start} if executor.memory.dense().get({range:?}) == Some(&{image_name}[{range:?}]) => {{"
+            );
+
+            let mut flow = Flow::Next;
+            for (addr, instruction) in &segment {
+                flow = emit(&mut out, *addr, instruction, &range);
+                if flow == Flow::Left {
+                    break;
+                }
+            }
+
+            if flow == Flow::Next {
+                let _ = writeln!(out, "                executor.pc = {};", range.end);
+            }
+            let _ = writeln!(out, "            }}");
+        }
+
+        let _ = writeln!(out, "            pc if pc >= executor.memory.len() => {{");
+        let _ = writeln!(
+            out,
+            "                return Err(intcode::IntcodeError::EndOfMemory {{ pc }});"
+        );
+        let _ = writeln!(out, "            }}");
+        let _ = writeln!(out, "            _ => {{");
+        let _ = writeln!(
+            out,
+            "                if let Some(state) = executor.step()? {{"
+        );
+        let _ = writeln!(out, "                    return Ok(state);");
+        let _ = writeln!(out, "                }}");
+        let _ = writeln!(out, "            }}");
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out, "}}");
+
+        out
+    }
+
+    /// Splits the code found by the disassembler into runs of instructions that can be compiled
+    /// together
+    ///
+    /// A new segment starts at every basic block, at every `In`, since that is where a run resumes
+    /// after waiting for input, after every `Out`, which returns its value, and after every
+    /// instruction that can not be compiled.
+    fn segments(&self) -> Vec<Vec<(usize, Instruction)>> {
+        let disassembly = Disassembler::new(self.program).disassemble();
+        let mut segments = vec![];
+
+        for block in &disassembly.blocks {
+            let mut segment: Vec<(usize, Instruction)> = vec![];
+
+            for (addr, instruction) in disassembly
+                .instructions()
+                .filter(|(addr, _)| (block.start..block.end).contains(addr))
+            {
+                if !compilable(instruction) {
+                    segments.extend((!segment.is_empty()).then(|| std::mem::take(&mut segment)));
+                    continue;
+                }
+
+                if instruction.opcode == Opcode::In && !segment.is_empty() {
+                    segments.push(std::mem::take(&mut segment));
+                }
+
+                segment.push((addr, instruction.clone()));
+
+                if instruction.opcode == Opcode::Out {
+                    segments.push(std::mem::take(&mut segment));
+                }
+            }
+
+            segments.extend((!segment.is_empty()).then_some(segment));
+        }
+
+        segments
+    }
+}
+
+/// Whether every address the instruction uses is known not to be negative, which is the only
+/// way an instruction that decoded can fail before it runs
+fn compilable(instruction: &Instruction) -> bool {
+    let (reads, write) = instruction.opcode.accesses();
+    let param = |i: usize| instruction.parameters[i + 1];
+
+    let reads_valid = reads
+        .iter()
+        .all(|i| instruction.modes[*i] != ModeOpt::Position || param(*i) >= 0);
+    let write_valid =
+        write.is_none_or(|i| instruction.modes[i] == ModeOpt::Relative || param(i) >= 0);
+    let jump_valid = !matches!(instruction.opcode, Opcode::Jt | Opcode::Jf)
+        || instruction.modes[1] != ModeOpt::Immediate
+        || param(1) >= 0;
+
+    reads_valid && write_valid && jump_valid
+}
+
+/// Expression for the value of a parameter
+fn read(instruction: &Instruction, i: usize) -> String {
+    let param = instruction.parameters[i + 1];
+
+    match instruction.modes[i] {
+        ModeOpt::Immediate => param.to_string(),
+        ModeOpt::Position => format!("executor.memory.get({param})"),
+        ModeOpt::Relative => {
+            format!("executor.memory.get(executor.relative_address({param})?)")
+        }
+    }
+}
+
+/// Expression for the address a parameter writes to, and the address itself if it is known
+/// before running
+fn write_address(instruction: &Instruction, i: usize) -> (String, Option<usize>) {
+    let param = instruction.parameters[i + 1];
+
+    match instruction.modes[i] {
+        ModeOpt::Relative => (format!("executor.relative_address({param})?"), None),
+        ModeOpt::Position | ModeOpt::Immediate => {
+            let addr = usize::try_from(param).expect("Write address was checked when compiling");
+            (addr.to_string(), Some(addr))
+        }
+    }
+}
+
+/// Emits the code of an instruction that stores a value through its parameter `i`, leaving the
+/// segment if the write may have changed its code
+///
+/// `value` is an expression for the value, or `None` if it is in `value` already. Addresses known
+/// only at runtime are worked out before the value if `address_first` is set, to fail the same way
+/// the interpreter does.
+fn emit_write(
+    out: &mut String,
+    instruction: &Instruction,
+    i: usize,
+    value: Option<&str>,
+    address_first: bool,
+    next: usize,
+    segment: &Range<usize>,
+) -> Flow {
+    let (addr, known) = write_address(instruction, i);
+
+    if known.is_none() && address_first {
+        let _ = writeln!(out, "                let addr = {addr};");
+    }
+    if let Some(value) = value {
+        let _ = writeln!(out, "                let value = {value};");
+    }
+    if known.is_none() && !address_first {
+        let _ = writeln!(out, "                let addr = {addr};");
+    }
+
+    match known {
+        Some(known) if segment.contains(&known) => {
+            let _ = writeln!(out, "                executor.set_memory({addr}, value)?;");
+            let _ = writeln!(out, "                executor.pc = {next};");
+            let _ = writeln!(out, "                continue;");
+
+            Flow::Left
+        }
+        Some(_) => {
+            let _ = writeln!(out, "                executor.set_memory({addr}, value)?;");
+
+            Flow::Next
+        }
+        None => {
+            let _ = writeln!(out, "                executor.set_memory(addr, value)?;");
+            let _ = writeln!(out, "                if ({segment:?}).contains(&addr) {{");
+            let _ = writeln!(out, "                    executor.pc = {next};");
+            let _ = writeln!(out, "                    continue;");
+            let _ = writeln!(out, "                }}");
+
+            Flow::Next
+        }
+    }
+}
+
+/// Emits the code of a single instruction
+fn emit(out: &mut String, addr: usize, instruction: &Instruction, segment: &Range<usize>) -> Flow {
+    let next = addr + instruction.opcode.len();
+
+    let _ = writeln!(
+        out,
+        "                // {addr:08x}: {}",
+        instruction.to_string().replace('\t', " ")
+    );
+    let _ = writeln!(out, "                executor.pc = {addr};");
+
+    match instruction.opcode {
+        Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => {
+            let (a, b) = (read(instruction, 0), read(instruction, 1));
+            let value = match instruction.opcode {
+                Opcode::Add => format!("i64::wrapping_add({a}, {b})"),
+                Opcode::Mul => format!("i64::wrapping_mul({a}, {b})"),
+                Opcode::Lt => format!("i64::from({a} < {b})"),
+                _ => format!("i64::from({a} == {b})"),
+            };
+
+            let address_first = matches!(instruction.opcode, Opcode::Add | Opcode::Mul);
+            emit_write(
+                out,
+                instruction,
+                2,
+                Some(&value),
+                address_first,
+                next,
+                segment,
+            )
+        }
+        Opcode::In => {
+            let _ = writeln!(
+                out,
+                "                let Some(value) = executor.consume_input() else {{"
+            );
+            let _ = writeln!(
+                out,
+                "                    return Ok(intcode::State::NeedsInput);"
+            );
+            let _ = writeln!(out, "                }};");
+            emit_write(out, instruction, 0, None, false, next, segment)
+        }
+        Opcode::Out => {
+            let _ = writeln!(out, "                let value = {};", read(instruction, 0));
+            let _ = writeln!(out, "                executor.record_output(value);");
+            let _ = writeln!(out, "                executor.pc = {next};");
+            let _ = writeln!(
+                out,
+                "                return Ok(intcode::State::Output(value));"
+            );
+
+            Flow::Left
+        }
+        Opcode::Jt | Opcode::Jf => {
+            let target = match instruction.modes[1] {
+                ModeOpt::Immediate => instruction.parameters[2].to_string(),
+                _ => format!("executor.jump_target({})?", read(instruction, 1)),
+            };
+
+            // a condition in immediate mode either always or never jumps
+            if instruction.modes[0] == ModeOpt::Immediate {
+                let taken = (instruction.parameters[1] != 0) == (instruction.opcode == Opcode::Jt);
+                if !taken {
+                    return Flow::Next;
+                }
+
+                let _ = writeln!(out, "                executor.pc = {target};");
+                let _ = writeln!(out, "                continue;");
+
+                return Flow::Left;
+            }
+
+            let comparison = if instruction.opcode == Opcode::Jt {
+                "!="
+            } else {
+                "=="
+            };
+            let _ = writeln!(
+                out,
+                "                if {} {comparison} 0 {{",
+                read(instruction, 0)
+            );
+            let _ = writeln!(out, "                    executor.pc = {target};");
+            let _ = writeln!(out, "                    continue;");
+            let _ = writeln!(out, "                }}");
+
+            Flow::Next
+        }
+        Opcode::Rel => {
+            let _ = writeln!(
+                out,
+                "                executor.adjust_relative_base({})?;",
+                read(instruction, 0)
+            );
+
+            Flow::Next
+        }
+        Opcode::Halt => {
+            let _ = writeln!(out, "                return Ok(intcode::State::Halted);");
+
+            Flow::Left
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compiled, Interpreter, State};
+
+    /// Programs transpiled into `testdata/transpiled`, which is regenerated with
+    /// `intcode transpile <file> --name <name>` whenever the generated code changes
+    mod compiled {
+        use crate as intcode;
+
+        include!("../testdata/transpiled/self_modifying.rs");
+        include!("../testdata/transpiled/relative_write.rs");
+        include!("../testdata/transpiled/computed_jump.rs");
+        include!("../testdata/transpiled/running_sum.rs");
+        include!("../testdata/transpiled/quine.rs");
+        include!("../testdata/transpiled/off_the_end.rs");
+    }
+
+    /// Name, program, input and compiled function of every transpiled program
+    const PROGRAMS: [(&str, &str, &[i64], Compiled); 6] = [
+        // overwrites the first instruction of the next segment, so its guard fails
+        (
+            "self_modifying",
+            "1101,0,1102,6,104,1,1101,6,7,13,4,13,99,0",
+            &[],
+            compiled::self_modifying,
+        ),
+        // overwrites a later instruction of its own segment through a relative address
+        (
+            "relative_write",
+            "109,10,21101,0,104,-4,4,9,99,5",
+            &[],
+            compiled::relative_write,
+        ),
+        // jumps to code the disassembler did not find, which jumps back into a segment
+        (
+            "computed_jump",
+            "5,14,15,104,5,99,104,7,1105,1,3,0,0,0,1,6",
+            &[],
+            compiled::computed_jump,
+        ),
+        // resumes at the `In` it stopped at every time it waits for input
+        (
+            "running_sum",
+            "3,15,1,15,16,16,4,16,1105,1,0,99,0,0,0,0,0",
+            &[3, -1, 40],
+            compiled::running_sum,
+        ),
+        (
+            "quine",
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            &[],
+            compiled::quine,
+        ),
+        ("off_the_end", "1105,1,100", &[], compiled::off_the_end),
+    ];
+
+    /// Every state a run stopped in, or the error it failed with, and the interpreter it left
+    /// behind
+    ///
+    /// The input is given one value at a time, only once the program waits for it.
+    fn run(program: &str, input: &[i64], compiled: Option<Compiled>) -> (Vec<String>, Interpreter) {
+        let mut interpreter = Interpreter::parse(program).unwrap();
+        interpreter.executor.set_compiled(compiled);
+        let mut input = input.iter();
+        let mut stops = vec![];
+
+        loop {
+            let state = interpreter.run();
+            stops.push(format!(
+                "{state:?} at {:?}",
+                interpreter.executor.fingerprint()
+            ));
+
+            match state {
+                Ok(State::Output(_)) => {}
+                Ok(State::NeedsInput) => match input.next() {
+                    Some(value) => interpreter.push_input(*value),
+                    None => break,
+                },
+                Ok(State::Halted) | Err(_) => break,
+            }
+        }
+
+        (stops, interpreter)
+    }
+
+    #[test]
+    fn transpiled_sources_are_up_to_date() {
+        for (name, program, _, _) in PROGRAMS {
+            let program = crate::parse_program(program).unwrap();
+            let path = format!(
+                "{}/testdata/transpiled/{name}.rs",
+                env!("CARGO_MANIFEST_DIR")
+            );
+
+            assert_eq!(
+                Transpiler::new(&program).with_name(name).transpile(),
+                std::fs::read_to_string(path).unwrap(),
+                "{name} needs transpiling again"
+            );
+        }
+    }
+
+    #[test]
+    fn compiled_code_runs_like_the_interpreter() {
+        for (name, program, input, compiled) in PROGRAMS {
+            let (interpreted_stops, interpreted) = run(program, input, None);
+            let (compiled_stops, compiled) = run(program, input, Some(compiled));
+
+            assert_eq!(compiled_stops, interpreted_stops, "{name}");
+            assert_eq!(
+                compiled.output_history(),
+                interpreted.output_history(),
+                "{name}"
+            );
+            assert!(
+                compiled
+                    .executor
+                    .memory
+                    .nonzero()
+                    .eq(interpreted.executor.memory.nonzero()),
+                "{name}"
+            );
+        }
+    }
+}
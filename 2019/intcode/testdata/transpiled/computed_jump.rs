@@ -0,0 +1,44 @@
+// Transpiled from an intcode program with `intcode transpile`, do not edit
+
+static COMPUTED_JUMP_IMAGE: [i64; 16] = [
+    5, 14, 15, 104, 5, 99, 104, 7, 1105, 1, 3, 0, 0, 0, 1, 6,
+];
+
+/// Runs the program until it needs input, produces an output or halts
+#[allow(clippy::all, clippy::pedantic)]
+pub fn computed_jump(executor: &mut intcode::Executor) -> Result<intcode::State, intcode::IntcodeError> {
+    loop {
+        match executor.pc {
+            0 if executor.memory.dense().get(0..3) == Some(&COMPUTED_JUMP_IMAGE[0..3]) => {
+                // 00000000: Jt [14], [15]
+                executor.pc = 0;
+                if executor.memory.get(14) != 0 {
+                    executor.pc = executor.jump_target(executor.memory.get(15))?;
+                    continue;
+                }
+                executor.pc = 3;
+            }
+            3 if executor.memory.dense().get(3..5) == Some(&COMPUTED_JUMP_IMAGE[3..5]) => {
+                // 00000003: Out 5
+                executor.pc = 3;
+                let value = 5;
+                executor.record_output(value);
+                executor.pc = 5;
+                return Ok(intcode::State::Output(value));
+            }
+            5 if executor.memory.dense().get(5..6) == Some(&COMPUTED_JUMP_IMAGE[5..6]) => {
+                // 00000005: Halt 
+                executor.pc = 5;
+                return Ok(intcode::State::Halted);
+            }
+            pc if pc >= executor.memory.len() => {
+                return Err(intcode::IntcodeError::EndOfMemory { pc });
+            }
+            _ => {
+                if let Some(state) = executor.step()? {
+                    return Ok(state);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+// Transpiled from an intcode program with `intcode transpile`, do not edit
+
+static OFF_THE_END_IMAGE: [i64; 3] = [
+    1105, 1, 100,
+];
+
+/// Runs the program until it needs input, produces an output or halts
+#[allow(clippy::all, clippy::pedantic)]
+pub fn off_the_end(executor: &mut intcode::Executor) -> Result<intcode::State, intcode::IntcodeError> {
+    loop {
+        match executor.pc {
+            0 if executor.memory.dense().get(0..3) == Some(&OFF_THE_END_IMAGE[0..3]) => {
+                // 00000000: Jt 1, 100
+                executor.pc = 0;
+                executor.pc = 100;
+                continue;
+            }
+            pc if pc >= executor.memory.len() => {
+                return Err(intcode::IntcodeError::EndOfMemory { pc });
+            }
+            _ => {
+                if let Some(state) = executor.step()? {
+                    return Ok(state);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Transpiled from an intcode program with `intcode transpile`, do not edit
+
+static QUINE_IMAGE: [i64; 16] = [
+    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+];
+
+/// Runs the program until it needs input, produces an output or halts
+#[allow(clippy::all, clippy::pedantic)]
+pub fn quine(executor: &mut intcode::Executor) -> Result<intcode::State, intcode::IntcodeError> {
+    loop {
+        match executor.pc {
+            0 if executor.memory.dense().get(0..4) == Some(&QUINE_IMAGE[0..4]) => {
+                // 00000000: Rel 1
+                executor.pc = 0;
+                executor.adjust_relative_base(1)?;
+                // 00000002: Out [-1 + rel]
+                executor.pc = 2;
+                let value = executor.memory.get(executor.relative_address(-1)?);
+                executor.record_output(value);
+                executor.pc = 4;
+                return Ok(intcode::State::Output(value));
+            }
+            4 if executor.memory.dense().get(4..15) == Some(&QUINE_IMAGE[4..15]) => {
+                // 00000004: Add [100], 1, [100]
+                executor.pc = 4;
+                let value = i64::wrapping_add(executor.memory.get(100), 1);
+                executor.set_memory(100, value)?;
+                // 00000008: Eq [100], 16, [101]
+                executor.pc = 8;
+                let value = i64::from(executor.memory.get(100) == 16);
+                executor.set_memory(101, value)?;
+                // 0000000c: Jf [101], 0
+                executor.pc = 12;
+                if executor.memory.get(101) == 0 {
+                    executor.pc = 0;
+                    continue;
+                }
+                executor.pc = 15;
+            }
+            15 if executor.memory.dense().get(15..16) == Some(&QUINE_IMAGE[15..16]) => {
+                // 0000000f: Halt 
+                executor.pc = 15;
+                return Ok(intcode::State::Halted);
+            }
+            pc if pc >= executor.memory.len() => {
+                return Err(intcode::IntcodeError::EndOfMemory { pc });
+            }
+            _ => {
+                if let Some(state) = executor.step()? {
+                    return Ok(state);
+                }
+            }
+        }
+    }
+}
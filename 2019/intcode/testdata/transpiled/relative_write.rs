@@ -0,0 +1,47 @@
+// Transpiled from an intcode program with `intcode transpile`, do not edit
+
+static RELATIVE_WRITE_IMAGE: [i64; 10] = [
+    109, 10, 21101, 0, 104, -4, 4, 9, 99, 5,
+];
+
+/// Runs the program until it needs input, produces an output or halts
+#[allow(clippy::all, clippy::pedantic)]
+pub fn relative_write(executor: &mut intcode::Executor) -> Result<intcode::State, intcode::IntcodeError> {
+    loop {
+        match executor.pc {
+            0 if executor.memory.dense().get(0..8) == Some(&RELATIVE_WRITE_IMAGE[0..8]) => {
+                // 00000000: Rel 10
+                executor.pc = 0;
+                executor.adjust_relative_base(10)?;
+                // 00000002: Add 0, 104, [-4 + rel]
+                executor.pc = 2;
+                let addr = executor.relative_address(-4)?;
+                let value = i64::wrapping_add(0, 104);
+                executor.set_memory(addr, value)?;
+                if (0..8).contains(&addr) {
+                    executor.pc = 6;
+                    continue;
+                }
+                // 00000006: Out [9]
+                executor.pc = 6;
+                let value = executor.memory.get(9);
+                executor.record_output(value);
+                executor.pc = 8;
+                return Ok(intcode::State::Output(value));
+            }
+            8 if executor.memory.dense().get(8..9) == Some(&RELATIVE_WRITE_IMAGE[8..9]) => {
+                // 00000008: Halt 
+                executor.pc = 8;
+                return Ok(intcode::State::Halted);
+            }
+            pc if pc >= executor.memory.len() => {
+                return Err(intcode::IntcodeError::EndOfMemory { pc });
+            }
+            _ => {
+                if let Some(state) = executor.step()? {
+                    return Ok(state);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// Transpiled from an intcode program with `intcode transpile`, do not edit
+
+static RUNNING_SUM_IMAGE: [i64; 17] = [
+    3, 15, 1, 15, 16, 16, 4, 16, 1105, 1, 0, 99, 0, 0, 0, 0,
+    0,
+];
+
+/// Runs the program until it needs input, produces an output or halts
+#[allow(clippy::all, clippy::pedantic)]
+pub fn running_sum(executor: &mut intcode::Executor) -> Result<intcode::State, intcode::IntcodeError> {
+    loop {
+        match executor.pc {
+            0 if executor.memory.dense().get(0..8) == Some(&RUNNING_SUM_IMAGE[0..8]) => {
+                // 00000000: In [15]
+                executor.pc = 0;
+                let Some(value) = executor.consume_input() else {
+                    return Ok(intcode::State::NeedsInput);
+                };
+                executor.set_memory(15, value)?;
+                // 00000002: Add [15], [16], [16]
+                executor.pc = 2;
+                let value = i64::wrapping_add(executor.memory.get(15), executor.memory.get(16));
+                executor.set_memory(16, value)?;
+                // 00000006: Out [16]
+                executor.pc = 6;
+                let value = executor.memory.get(16);
+                executor.record_output(value);
+                executor.pc = 8;
+                return Ok(intcode::State::Output(value));
+            }
+            8 if executor.memory.dense().get(8..11) == Some(&RUNNING_SUM_IMAGE[8..11]) => {
+                // 00000008: Jt 1, 0
+                executor.pc = 8;
+                executor.pc = 0;
+                continue;
+            }
+            pc if pc >= executor.memory.len() => {
+                return Err(intcode::IntcodeError::EndOfMemory { pc });
+            }
+            _ => {
+                if let Some(state) = executor.step()? {
+                    return Ok(state);
+                }
+            }
+        }
+    }
+}
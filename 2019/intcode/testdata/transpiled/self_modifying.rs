@@ -0,0 +1,51 @@
+// Transpiled from an intcode program with `intcode transpile`, do not edit
+
+static SELF_MODIFYING_IMAGE: [i64; 14] = [
+    1101, 0, 1102, 6, 104, 1, 1101, 6, 7, 13, 4, 13, 99, 0,
+];
+
+/// Runs the program until it needs input, produces an output or halts
+#[allow(clippy::all, clippy::pedantic)]
+pub fn self_modifying(executor: &mut intcode::Executor) -> Result<intcode::State, intcode::IntcodeError> {
+    loop {
+        match executor.pc {
+            0 if executor.memory.dense().get(0..6) == Some(&SELF_MODIFYING_IMAGE[0..6]) => {
+                // 00000000: Add 0, 1102, [6]
+                executor.pc = 0;
+                let value = i64::wrapping_add(0, 1102);
+                executor.set_memory(6, value)?;
+                // 00000004: Out 1
+                executor.pc = 4;
+                let value = 1;
+                executor.record_output(value);
+                executor.pc = 6;
+                return Ok(intcode::State::Output(value));
+            }
+            6 if executor.memory.dense().get(6..12) == Some(&SELF_MODIFYING_IMAGE[6..12]) => {
+                // 00000006: Add 6, 7, [13]
+                executor.pc = 6;
+                let value = i64::wrapping_add(6, 7);
+                executor.set_memory(13, value)?;
+                // 0000000a: Out [13]
+                executor.pc = 10;
+                let value = executor.memory.get(13);
+                executor.record_output(value);
+                executor.pc = 12;
+                return Ok(intcode::State::Output(value));
+            }
+            12 if executor.memory.dense().get(12..13) == Some(&SELF_MODIFYING_IMAGE[12..13]) => {
+                // 0000000c: Halt 
+                executor.pc = 12;
+                return Ok(intcode::State::Halted);
+            }
+            pc if pc >= executor.memory.len() => {
+                return Err(intcode::IntcodeError::EndOfMemory { pc });
+            }
+            _ => {
+                if let Some(state) = executor.step()? {
+                    return Ok(state);
+                }
+            }
+        }
+    }
+}
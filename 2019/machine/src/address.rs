@@ -0,0 +1,51 @@
+/// Address typed in by the user, in decimal or in hex with a `0x` prefix
+pub fn parse_address(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse::<usize>(),
+    }
+    .map_err(|e| format!("Invalid address `{s}`: {e}"))
+}
+
+/// Address of the instruction on a line of a listing, which is `None` for labels, comments and
+/// data
+///
+/// Lines of a listing start with a two character program counter marker followed by the address
+/// in hex and a colon.
+#[must_use]
+pub fn instruction_address(line: &str) -> Option<usize> {
+    let (addr, rest) = line.get(2..)?.split_once(':')?;
+    if rest.trim_start().starts_with(".data") {
+        return None;
+    }
+
+    usize::from_str_radix(addr, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_in_decimal_and_hex() {
+        assert_eq!(parse_address("42"), Ok(42));
+        assert_eq!(parse_address(" 0x2a "), Ok(42));
+        assert_eq!(parse_address("0x0"), Ok(0));
+
+        for invalid in ["", "0x", "-1", "2a", "0xg", "1.5"] {
+            assert!(parse_address(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn addresses_of_listing_lines() {
+        assert_eq!(instruction_address("> 0000000a:\tadd 1, 2, [3]"), Some(10));
+        assert_eq!(instruction_address("  00000000:\thalt"), Some(0));
+        assert_eq!(instruction_address("  00000010:\t.data\t7"), None);
+        assert_eq!(instruction_address("loc_00000004:"), None);
+        assert_eq!(instruction_address("; a comment"), None);
+        assert_eq!(instruction_address(""), None);
+    }
+}
@@ -0,0 +1,19 @@
+//! What the virtual machines of the puzzles have in common, shared by the machines themselves and
+//! the tools that run them, like the terminal debugger
+
+mod address;
+mod loops;
+
+pub use crate::address::{instruction_address, parse_address};
+pub use crate::loops::LoopDetector;
+
+/// Why a machine stopped running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// The machine needs input to go on, and has none queued
+    NeedsInput,
+    /// The machine produced a value
+    Output(i64),
+    /// The machine reached the end of its program
+    Halted,
+}
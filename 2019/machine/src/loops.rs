@@ -0,0 +1,87 @@
+/// Notices when a machine gets back to a state it was in before, checked before every instruction
+///
+/// This is Brent's cycle detection: the state at the last checkpoint is compared to every state
+/// after it, and the checkpoint moves forward after twice as many steps each time.
+#[derive(Debug)]
+pub struct LoopDetector<T> {
+    checkpoint: Option<T>,
+    since_checkpoint: u64,
+    checkpoint_interval: u64,
+}
+
+impl<T> Default for LoopDetector<T> {
+    fn default() -> Self {
+        Self {
+            checkpoint: None,
+            since_checkpoint: 0,
+            checkpoint_interval: 1,
+        }
+    }
+}
+
+impl<T: PartialEq> LoopDetector<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks the state before the next instruction is executed, returning the number of
+    /// instructions in the loop if it is one the machine was in before
+    pub fn check(&mut self, state: T) -> Option<u64> {
+        if let Some(checkpoint) = &self.checkpoint {
+            self.since_checkpoint += 1;
+
+            if *checkpoint == state {
+                return Some(self.since_checkpoint);
+            }
+        }
+
+        if self.checkpoint.is_none() || self.since_checkpoint == self.checkpoint_interval {
+            self.checkpoint = Some(state);
+            self.since_checkpoint = 0;
+            self.checkpoint_interval *= 2;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a detector over the states a machine goes through, returning the step at which it
+    /// reports a loop and the period it reports
+    fn detect(states: impl IntoIterator<Item = u64>) -> Option<(usize, u64)> {
+        let mut loops = LoopDetector::new();
+
+        states
+            .into_iter()
+            .enumerate()
+            .find_map(|(step, state)| loops.check(state).map(|period| (step, period)))
+    }
+
+    #[test]
+    fn finds_loops_of_any_period() {
+        for period in [1, 2, 3, 7, 64, 1000] {
+            let lead_in = 0..5;
+            let cycle = (100..100 + period).cycle().take(10 * period as usize);
+
+            let (step, found) = detect(lead_in.chain(cycle)).unwrap();
+            assert_eq!(found, period, "period {period}");
+            assert!(step < 5 + 4 * period as usize, "period {period} found late");
+        }
+    }
+
+    #[test]
+    fn states_that_never_repeat_are_not_loops() {
+        assert_eq!(detect(0..100_000), None);
+    }
+
+    #[test]
+    fn reports_the_steps_since_the_repeated_state() {
+        assert_eq!(detect([1, 1]), Some((1, 1)));
+        assert_eq!(detect([1, 2, 1]), Some((2, 2)));
+        assert_eq!(detect([1, 2, 3, 4, 3, 4]), Some((4, 2)));
+    }
+}
@@ -1,7 +1,13 @@
+use core::fmt;
+
+use debugger::{Machine, State, Stepped};
 use itertools::Itertools;
 use regex::Regex;
 use std::{env, error::Error, fs};
 
+/// Names of the instructions, indexed by opcode
+const MNEMONICS: [&str; 8] = ["adv", "bxl", "bst", "jnz", "bxc", "out", "bdv", "cdv"];
+
 #[derive(Debug, Clone)]
 struct Process {
     a: i64,
@@ -46,42 +52,190 @@ fn parse_input(input_string: &str) -> Result<Process, Box<dyn Error>> {
     })
 }
 
+fn halted(process: &Process) -> bool {
+    process.pc >= process.instructions.len() - 1
+}
+
+fn combo_operand(process: &Process, operand: i64) -> Result<i64, Box<dyn Error>> {
+    match operand {
+        0..=3 => Ok(operand),
+        4 => Ok(process.a),
+        5 => Ok(process.b),
+        6 => Ok(process.c),
+        _ => Err(format!("Invalid operand {operand}"))?,
+    }
+}
+
+/// Executes the instruction at the program counter, returning what it output
+fn step(process: &mut Process) -> Result<Option<i64>, Box<dyn Error>> {
+    let opcode = process.instructions[process.pc];
+    let operand = process.instructions[process.pc + 1];
+
+    match opcode {
+        0 => process.a >>= combo_operand(process, operand)?,
+        1 => process.b ^= operand,
+        2 => process.b = combo_operand(process, operand)? % 8,
+        3 => {
+            if process.a != 0 {
+                process.pc = usize::try_from(operand)?;
+                return Ok(None);
+            }
+        }
+        4 => process.b ^= process.c,
+        5 => {
+            let output = combo_operand(process, operand)? % 8;
+            process.pc += 2;
+            return Ok(Some(output));
+        }
+        6 => process.b = process.a >> combo_operand(process, operand)?,
+        7 => process.c = process.a >> combo_operand(process, operand)?,
+        _ => Err(format!("Invalid opcode {opcode}"))?,
+    }
+
+    process.pc += 2;
+
+    Ok(None)
+}
+
 fn compute(process: &mut Process) -> Result<Vec<i64>, Box<dyn Error>> {
     let mut res = vec![];
 
-    while process.pc < process.instructions.len() - 1 {
-        let opcode = process.instructions[process.pc];
-        let operand = process.instructions[process.pc + 1];
+    while !halted(process) {
+        res.extend(step(process)?);
+    }
+
+    Ok(res)
+}
+
+/// Computer run in the debugger, with everything it has output
+#[derive(Debug)]
+struct Computer {
+    process: Process,
+    outputs: Vec<i64>,
+}
+
+/// An executed instruction, with the registers from before it ran
+#[derive(Debug)]
+struct Executed {
+    a: i64,
+    b: i64,
+    c: i64,
+    pc: usize,
+    instruction: String,
+    output: Option<i64>,
+}
+
+impl fmt::Display for Executed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08x}:\t{}", self.pc, self.instruction)?;
+
+        if let Some(output) = self.output {
+            write!(f, "\tout {output}")?;
+        }
+
+        Ok(())
+    }
+}
 
-        let combo_operand = match operand {
-            0..=3 => operand,
-            4 => process.a,
-            5 => process.b,
-            6 => process.c,
-            _ => panic!("Invalid operand"),
+impl Machine for Computer {
+    type Record = Executed;
+    type Error = Box<dyn Error>;
+
+    const MNEMONICS: &'static [&'static str] = &MNEMONICS;
+
+    fn step(&mut self) -> Result<Stepped<Executed>, Box<dyn Error>> {
+        if halted(&self.process) {
+            return Ok((Some(State::Halted), None));
+        }
+
+        let Process { a, b, c, pc, .. } = self.process;
+        let instruction = self
+            .disassemble(pc)
+            .map(|(instruction, _)| instruction)
+            .unwrap_or_default();
+
+        let output = step(&mut self.process)?;
+        self.outputs.extend(output);
+
+        let state = match output {
+            Some(value) => Some(State::Output(value)),
+            None if halted(&self.process) => Some(State::Halted),
+            None => None,
+        };
+        let executed = Executed {
+            a,
+            b,
+            c,
+            pc,
+            instruction,
+            output,
         };
 
-        match opcode {
-            0 => process.a >>= combo_operand,
-            1 => process.b ^= operand,
-            2 => process.b = combo_operand % 8,
-            3 => {
-                if process.a != 0 {
-                    process.pc = usize::try_from(operand)?;
-                    continue;
-                }
-            }
-            4 => process.b ^= process.c,
-            5 => res.push(combo_operand % 8),
-            6 => process.b = process.a >> combo_operand,
-            7 => process.c = process.a >> combo_operand,
-            _ => panic!("Invalid opcode"),
+        Ok((state, Some(executed)))
+    }
+
+    fn undo(&mut self, executed: &Executed) {
+        self.process.a = executed.a;
+        self.process.b = executed.b;
+        self.process.c = executed.c;
+        self.process.pc = executed.pc;
+
+        if executed.output.is_some() {
+            self.outputs.pop();
         }
+    }
 
-        process.pc += 2;
+    fn pc(&self) -> usize {
+        self.process.pc
     }
 
-    Ok(res)
+    fn registers(&self) -> Vec<(&'static str, i64)> {
+        vec![
+            ("A", self.process.a),
+            ("B", self.process.b),
+            ("C", self.process.c),
+        ]
+    }
+
+    fn memory_len(&self) -> usize {
+        self.process.instructions.len()
+    }
+
+    fn cell(&self, addr: usize) -> i64 {
+        self.process
+            .instructions
+            .get(addr)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn disassemble(&self, addr: usize) -> Option<(String, usize)> {
+        let opcode = *self.process.instructions.get(addr)?;
+        let operand = *self.process.instructions.get(addr + 1)?;
+        let mnemonic = MNEMONICS.get(usize::try_from(opcode).ok()?)?;
+
+        let operand = match (opcode, operand) {
+            (1 | 3, _) => operand.to_string(),
+            (4, _) => String::new(),
+            (_, 0..=3) => operand.to_string(),
+            (_, 4) => "A".to_string(),
+            (_, 5) => "B".to_string(),
+            (_, 6) => "C".to_string(),
+            _ => format!("?{operand}"),
+        };
+
+        Some((format!("{mnemonic}\t{operand}"), 2))
+    }
+
+    fn opcode(&self) -> Option<&'static str> {
+        let opcode = self.process.instructions.get(self.process.pc)?;
+
+        MNEMONICS.get(usize::try_from(*opcode).ok()?).copied()
+    }
+
+    fn outputs(&self) -> &[i64] {
+        &self.outputs
+    }
 }
 
 fn part_1(process: &mut Process) -> Result<String, Box<dyn Error>> {
@@ -121,6 +275,18 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut process = parse_input(&contents)?;
 
+    if env::args().nth(2).as_deref() == Some("--debug") {
+        let computer = Computer {
+            process,
+            outputs: vec![],
+        };
+        tokio::runtime::Runtime::new()?
+            .block_on(debugger::debug(computer))
+            .map_err(|e| e as Box<dyn Error>)?;
+
+        return Ok(());
+    }
+
     println!("Part 1: {}", part_1(&mut process.clone())?);
     println!("Part 2: {}", part_2(&mut process)?);
 